@@ -1,23 +1,58 @@
 #version 460
 
-const int MAX_LIGHTS = 170;
-
 uniform sampler2D textureSampler;
+uniform sampler2D visibilityMask; // White where the player can see
+uniform sampler2D shadowMask; // White where walls block light
 uniform vec2 screenSize;
-uniform vec2 lightsPosition[MAX_LIGHTS];
-uniform vec4 lightsColor[MAX_LIGHTS];
+// One column per light: (position, radius, type), color, (rotation, cone angle, unused)
+uniform sampler2D lightsData;
 uniform int lightsAmount;
-uniform float lightsRadius[MAX_LIGHTS];
-uniform float lightsRotation[MAX_LIGHTS]; // Cone angle for cone lights
-uniform int lightsType[MAX_LIGHTS];
-uniform float lightsAngle[MAX_LIGHTS];
 
 const int RADIAL_LIGHT = 0;
 const int AMBIENT_LIGHT = 1;
 const int CONE_LIGHT = 2;
 
+// How bright areas out of the player's sight are
+const float UNSEEN_BRIGHTNESS = 0.3;
+
+// Shadows are traced along a few rays spread across the light's width to soften their edges
+const int SHADOW_RAYS = 3;
+const float LIGHT_WIDTH = 12.0; // Pixels
+const float SHADOW_STEP = 3.0; // Pixels between shadow mask samples along a ray
+const int MAX_SHADOW_STEPS = 160;
+
 out vec4 fragColor;  // Define the output variable
 
+// Fraction of a light reaching the pixel, from 0 when walls fully block it to 1.
+// A pixel inside a wall is lit by rays leaving that wall, so walls light up on their faces.
+float lightVisibility(vec2 lightPosition) {
+    vec2 toLight = lightPosition - gl_FragCoord.xy;
+    int steps = min(int(length(toLight) / SHADOW_STEP), MAX_SHADOW_STEPS);
+    vec2 side = normalize(vec2(-toLight.y, toLight.x)) * LIGHT_WIDTH / 2.0;
+    float visibility = 0.0;
+    for (int ray = 0; ray < SHADOW_RAYS; ray++) {
+        vec2 rayTarget = lightPosition + side * (float(ray) / float(SHADOW_RAYS - 1) * 2.0 - 1.0);
+        bool leavingWall = true;
+        float lit = 1.0;
+        for (int s = 1; s <= MAX_SHADOW_STEPS; s++) {
+            if (s > steps) {
+                break;
+            }
+            vec2 samplePosition = mix(gl_FragCoord.xy, rayTarget, float(s) / float(steps));
+            bool blocked = texture(shadowMask, samplePosition / screenSize).r > 0.5;
+            if (leavingWall) {
+                leavingWall = blocked;
+            }
+            else if (blocked) {
+                lit = 0.0;
+                break;
+            }
+        }
+        visibility += lit;
+    }
+    return visibility / float(SHADOW_RAYS);
+}
+
 void main() {
     vec2 uv = gl_FragCoord.xy / screenSize;
     vec3 color_gradient = vec3(0.0);
@@ -25,33 +60,41 @@ void main() {
     float falloffFactor;
 
     for (int i = 0; i < lightsAmount; i++) {
-        float lightAlpha = lightsColor[i].a;
-        if (lightsType[i] == AMBIENT_LIGHT) {
-            color_gradient += lightsColor[i].rgb * lightAlpha;
+        vec4 lightData = texelFetch(lightsData, ivec2(i, 0), 0);
+        vec4 lightColor = texelFetch(lightsData, ivec2(i, 1), 0);
+        vec4 coneData = texelFetch(lightsData, ivec2(i, 2), 0);
+        float lightRadius = lightData.z;
+        int lightType = int(lightData.w + 0.5);
+
+        float lightAlpha = lightColor.a;
+        if (lightType == AMBIENT_LIGHT) {
+            color_gradient += lightColor.rgb * lightAlpha;
         }
-        else if (lightsType[i] == RADIAL_LIGHT) {
+        else if (lightType == RADIAL_LIGHT) {
             float curveAmount = 1.5;
-            vec2 lightPosition = vec2(lightsPosition[i].x, -lightsPosition[i].y + screenSize.y);
+            vec2 lightPosition = vec2(lightData.x, -lightData.y + screenSize.y);
             float worldDistanceToLight = distance(lightPosition, gl_FragCoord.xy);
 
             // Calculate the distance from the current pixel to the center of the light
-            falloffFactor = 1.0 / (-max(0.0, 1.0 - worldDistanceToLight / lightsRadius[i]) / 2.0 + 1.0);
+            falloffFactor = 1.0 / (-max(0.0, 1.0 - worldDistanceToLight / lightRadius) / 2.0 + 1.0);
             float cur_gradient = max(0.0, pow((falloffFactor - 1.0), curveAmount));
 
             // Apply the gradient as a mask to the texture color
-            color_gradient += cur_gradient * lightsColor[i].rgb * lightAlpha;
+            if (cur_gradient > 0.0) {
+                color_gradient += cur_gradient * lightColor.rgb * lightAlpha * lightVisibility(lightPosition);
+            }
         }
-        else if (lightsType[i] == CONE_LIGHT) {
+        else if (lightType == CONE_LIGHT) {
             float curveAmount = 1.5;
-            vec2 light_pos = vec2(lightsPosition[i].x, -lightsPosition[i].y + screenSize.y);
+            vec2 light_pos = vec2(lightData.x, -lightData.y + screenSize.y);
             float worldDistanceToLight = distance(light_pos, gl_FragCoord.xy);
 
             // Calculate the distance from the current pixel to the center of the light
-            falloffFactor = 1.0 / (-max(0.0, 1.0 - worldDistanceToLight / lightsRadius[i]) / 2.0 + 1.0);
+            falloffFactor = 1.0 / (-max(0.0, 1.0 - worldDistanceToLight / lightRadius) / 2.0 + 1.0);
             float cur_gradient = max(0.0, pow((falloffFactor - 1.0), curveAmount));
 
             float cone_factor = 1.0;
-            float light_angle = lightsRotation[i];
+            float light_angle = coneData.x;
             vec2 light_angle_v = vec2(cos(light_angle), sin(light_angle));
 
             vec2 light_direction = light_pos - gl_FragCoord.xy;
@@ -59,7 +102,7 @@ void main() {
             
             // Smoothly attenuate intensity towards the edges of the cone
             float softness = 0.15; // Adjust this value to control softness of the edge
-            float cone_angle = lightsAngle[i];
+            float cone_angle = coneData.y;
             float softness_factor = smoothstep(cos(cone_angle/2.0), cos((cone_angle/2.0) - softness), cone);
             
             // Apply softness to cone_factor
@@ -70,9 +113,14 @@ void main() {
             }
 
             // Apply the gradient as a mask to the texture color
-            color_gradient += cur_gradient * lightsColor[i].rgb * lightAlpha * cone_factor;
+            if (cur_gradient * cone_factor > 0.0) {
+                color_gradient += cur_gradient * lightColor.rgb * lightAlpha * cone_factor * lightVisibility(light_pos);
+            }
         }
     }
+    float visible = texture(visibilityMask, uv).r;
+    color_gradient *= mix(UNSEEN_BRIGHTNESS, 1.0, visible);
+
     // Output the final color with the original alpha
     fragColor = color * vec4(color_gradient, 1.0);
 }
@@ -0,0 +1,373 @@
+use rand::Rng;
+use raylib::prelude::*;
+
+use crate::{
+    clock::WorldClock, collision_world::CollisionWorld, damage::DamageEvent, input::PlayerInput,
+    nav::NavGrid, Player,
+};
+
+// What a hostile character is currently doing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AiState {
+    // Standing still until the timer runs out
+    Idle { timer: f32 },
+    // Walking to a random point around home
+    Patrol { target: Vector2 },
+    // Heard or felt something, going to have a look
+    Investigate { pos: Vector2, timer: f32 },
+    // Lost sight of the target, heading to where it was last seen
+    Chase { last_seen: Vector2 },
+    // Shooting at the target once the reaction time has passed
+    Attack { target: Vector2, reaction: f32 },
+    // Hurt, running away from where the threat was and patching up once out of sight
+    Flee { from: Vector2 },
+}
+
+// What a hostile character knows about the world this frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Perception {
+    pub pos: Vector2,
+    // Position of the target while it can be seen
+    pub target: Option<Vector2>,
+    // Roughly where the shot that just hurt the character came from
+    pub hurt_from: Option<Vector2>,
+    pub health: f32,
+    pub bleeding: bool,
+}
+
+// Decision making for one hostile character. It drives the character with the same
+// `PlayerInput` the player's controls produce, so it moves and shoots like the player does.
+#[derive(Clone, Debug)]
+pub struct Brain {
+    pub state: AiState,
+    home: Vector2,
+    trigger_pulled: bool,
+    // Waypoints left to walk through to reach `path_goal`
+    path: Vec<Vector2>,
+    path_goal: Option<Vector2>,
+}
+
+impl Brain {
+    const SIGHT_RANGE: f32 = 30.0;
+    // Sight range in the dark of midnight, growing to `SIGHT_RANGE` with daylight
+    const NIGHT_SIGHT_RANGE: f32 = 12.0;
+    // Half the angle in degrees the character can see in front of it
+    const HALF_FIELD_OF_VIEW: f32 = 70.0;
+    // Anything this close is noticed whichever way the character faces
+    const AWARENESS_RADIUS: f32 = 3.0;
+    const ATTACK_RANGE: f32 = 20.0;
+    const REACTION_TIME: f32 = 0.4;
+    const FLEE_HEALTH: f32 = 30.0;
+    // How far from the threat a fleeing character stops running
+    const SAFE_DISTANCE: f32 = 35.0;
+    const PATROL_RADIUS: f32 = 10.0;
+    const IDLE_TIME: (f32, f32) = (2.0, 5.0);
+    const LOOK_AROUND_TIME: f32 = 3.0;
+    // Close enough to a point to count as having reached it
+    const ARRIVE_DISTANCE: f32 = 1.0;
+    // How far back along a bullet's path the character guesses the shooter is
+    const SHOT_TRACE_DISTANCE: f32 = 15.0;
+
+    pub fn new(home: Vector2) -> Self {
+        Brain {
+            state: AiState::Idle { timer: 0.0 },
+            home,
+            trigger_pulled: false,
+            path: Vec::new(),
+            path_goal: None,
+        }
+    }
+
+    // Looks around, updates the state and returns the input to drive the character with.
+    // `damage_events` are the hits of the last physics step.
+    #[allow(clippy::too_many_arguments)]
+    pub fn think(
+        &mut self,
+        dt: f32,
+        character: &Player,
+        target: &Player,
+        damage_events: &[DamageEvent],
+        collision_world: &CollisionWorld,
+        nav_grid: &NavGrid,
+        clock: &WorldClock,
+        rng: &mut impl Rng,
+    ) -> PlayerInput {
+        let pos = character.collider.get_pos(collision_world);
+        let target_pos = target.collider.get_pos(collision_world);
+        let sight_range = Self::NIGHT_SIGHT_RANGE
+            + (Self::SIGHT_RANGE - Self::NIGHT_SIGHT_RANGE) * clock.daylight();
+        let perception = Perception {
+            pos,
+            target: (target.health > 0.0
+                && Self::in_view(pos, character.angle, target_pos, sight_range)
+                && collision_world.can_see(&character.collider, &target.collider))
+            .then_some(target_pos),
+            // Getting shot gives away which way the shooter is, bleeding and hunger don't
+            hurt_from: damage_events
+                .iter()
+                .find(|event| event.collider == character.collider.collider_handle)
+                .map(|event| event.pos - event.direction * Self::SHOT_TRACE_DISTANCE),
+            health: character.health,
+            bleeding: character.bleeding > 0.0,
+        };
+        self.state = self.next_state(dt, &perception, rng);
+        self.input(&perception, character, nav_grid)
+    }
+
+    // Whether `target_pos` is in sight range and inside the field of view of a character
+    // facing `angle`
+    fn in_view(pos: Vector2, angle: f32, target_pos: Vector2, sight_range: f32) -> bool {
+        let to_target = target_pos - pos;
+        if to_target.length() > sight_range {
+            return false;
+        }
+        // Characters face 90 degrees past their angle, see `Player::aim_at`
+        let facing = (angle + 90.0).to_radians();
+        let facing = Vector2::new(facing.cos(), facing.sin());
+        let cos = facing.dot(to_target.normalized());
+        cos >= Self::HALF_FIELD_OF_VIEW.to_radians().cos()
+            || to_target.length() < Self::AWARENESS_RADIUS
+    }
+
+    pub fn next_state(&self, dt: f32, perception: &Perception, rng: &mut impl Rng) -> AiState {
+        let arrived = |point: Vector2| perception.pos.distance_to(point) < Self::ARRIVE_DISTANCE;
+        // Staying alive comes first
+        if perception.health < Self::FLEE_HEALTH {
+            let threat = perception.target.or(perception.hurt_from);
+            return match (self.state, threat) {
+                (_, Some(from)) => AiState::Flee { from },
+                (AiState::Flee { from }, None) => AiState::Flee { from },
+                (_, None) => AiState::Flee {
+                    from: perception.pos,
+                },
+            };
+        }
+        if let Some(target) = perception.target {
+            if perception.pos.distance_to(target) > Self::ATTACK_RANGE {
+                return AiState::Chase { last_seen: target };
+            }
+            return match self.state {
+                AiState::Attack { reaction, .. } => AiState::Attack {
+                    target,
+                    reaction: reaction - dt,
+                },
+                _ => AiState::Attack {
+                    target,
+                    reaction: Self::REACTION_TIME,
+                },
+            };
+        }
+        if let Some(pos) = perception.hurt_from {
+            return AiState::Investigate {
+                pos,
+                timer: Self::LOOK_AROUND_TIME,
+            };
+        }
+        match self.state {
+            AiState::Idle { timer } if timer > 0.0 => AiState::Idle { timer: timer - dt },
+            AiState::Idle { .. } => {
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let distance = rng.gen_range(0.0..Self::PATROL_RADIUS);
+                AiState::Patrol {
+                    target: self.home + Vector2::new(angle.cos(), angle.sin()) * distance,
+                }
+            }
+            AiState::Patrol { target } if arrived(target) => AiState::Idle {
+                timer: rng.gen_range(Self::IDLE_TIME.0..Self::IDLE_TIME.1),
+            },
+            AiState::Patrol { target } => AiState::Patrol { target },
+            AiState::Investigate { pos, timer } if arrived(pos) => {
+                if timer > 0.0 {
+                    AiState::Investigate {
+                        pos,
+                        timer: timer - dt,
+                    }
+                } else {
+                    AiState::Idle { timer: 0.0 }
+                }
+            }
+            AiState::Investigate { pos, timer } => AiState::Investigate { pos, timer },
+            // Lost sight of the target, go after it
+            AiState::Attack { target, .. } => AiState::Chase { last_seen: target },
+            AiState::Chase { last_seen } if arrived(last_seen) => AiState::Investigate {
+                pos: last_seen,
+                timer: Self::LOOK_AROUND_TIME,
+            },
+            AiState::Chase { last_seen } => AiState::Chase { last_seen },
+            AiState::Flee { from } => {
+                if perception.pos.distance_to(from) > Self::SAFE_DISTANCE && !perception.bleeding {
+                    AiState::Idle { timer: 0.0 }
+                } else {
+                    AiState::Flee { from }
+                }
+            }
+        }
+    }
+
+    // Next point to head for on the way to `goal`, finding a new path when the goal has moved
+    fn next_waypoint(&mut self, pos: Vector2, goal: Vector2, nav_grid: &NavGrid) -> Vector2 {
+        let goal_moved = self.path_goal.map_or(true, |path_goal| {
+            path_goal.distance_to(goal) > Self::ARRIVE_DISTANCE
+        });
+        if goal_moved {
+            // Walk straight at unreachable goals, the character may still get close enough
+            self.path = nav_grid
+                .find_path(pos, goal)
+                .unwrap_or_else(|| vec![pos, goal]);
+            self.path.remove(0);
+            self.path_goal = Some(goal);
+        }
+        while self.path.len() > 1 && pos.distance_to(self.path[0]) < Self::ARRIVE_DISTANCE {
+            self.path.remove(0);
+        }
+        self.path.first().copied().unwrap_or(goal)
+    }
+
+    fn input(
+        &mut self,
+        perception: &Perception,
+        character: &Player,
+        nav_grid: &NavGrid,
+    ) -> PlayerInput {
+        let pos = perception.pos;
+        let facing = {
+            let angle = (character.angle + 90.0).to_radians();
+            pos + Vector2::new(angle.cos(), angle.sin())
+        };
+        let (state, trigger_pulled) = (self.state, self.trigger_pulled);
+        let mut walk_to = |goal: Vector2| {
+            let point = self.next_waypoint(pos, goal, nav_grid);
+            PlayerInput {
+                movement: point - pos,
+                aim_pos: point,
+                ..Default::default()
+            }
+        };
+        let mut input = match state {
+            AiState::Idle { .. } => PlayerInput {
+                aim_pos: facing,
+                ..Default::default()
+            },
+            AiState::Patrol { target } => walk_to(target),
+            AiState::Investigate { pos: point, .. } | AiState::Chase { last_seen: point } => {
+                PlayerInput {
+                    sprinting: matches!(state, AiState::Chase { .. }),
+                    ..walk_to(point)
+                }
+            }
+            AiState::Attack { target, reaction } => PlayerInput {
+                aim_pos: target,
+                // Semi automatic firearms need the trigger released between shots
+                firing: reaction <= 0.0 && !trigger_pulled,
+                ..Default::default()
+            },
+            AiState::Flee { from } => {
+                // Kept relative to the threat so the path doesn't change every step
+                let away = from + (pos - from).normalized() * Self::SAFE_DISTANCE * 1.5;
+                if perception.target.is_none() && perception.bleeding {
+                    // Out of sight, stop to bandage up
+                    PlayerInput {
+                        aim_pos: facing,
+                        quick_heal: character.consumption.is_none(),
+                        ..Default::default()
+                    }
+                } else {
+                    PlayerInput {
+                        sprinting: true,
+                        ..walk_to(away)
+                    }
+                }
+            }
+        };
+        if input.movement.length() < Self::ARRIVE_DISTANCE / 2.0 {
+            input.movement = Vector2::zero();
+        }
+        self.trigger_pulled = input.firing;
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn perception(target: Option<Vector2>, health: f32) -> Perception {
+        Perception {
+            pos: Vector2::zero(),
+            target,
+            hurt_from: None,
+            health,
+            bleeding: false,
+        }
+    }
+
+    #[test]
+    fn attacks_after_reacting_to_a_visible_target() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut brain = Brain::new(Vector2::zero());
+        let seen = perception(Some(Vector2::new(5.0, 0.0)), 100.0);
+        brain.state = brain.next_state(0.1, &seen, &mut rng);
+        assert_eq!(
+            brain.state,
+            AiState::Attack {
+                target: Vector2::new(5.0, 0.0),
+                reaction: Brain::REACTION_TIME
+            }
+        );
+        brain.state = brain.next_state(Brain::REACTION_TIME, &seen, &mut rng);
+        assert!(matches!(brain.state, AiState::Attack { reaction, .. } if reaction <= 0.0));
+
+        // Losing sight of the target sends the character after it
+        brain.state = brain.next_state(0.1, &perception(None, 100.0), &mut rng);
+        assert_eq!(
+            brain.state,
+            AiState::Chase {
+                last_seen: Vector2::new(5.0, 0.0)
+            }
+        );
+
+        let far = perception(Some(Vector2::new(25.0, 0.0)), 100.0);
+        brain.state = brain.next_state(0.1, &far, &mut rng);
+        assert_eq!(
+            brain.state,
+            AiState::Chase {
+                last_seen: Vector2::new(25.0, 0.0)
+            }
+        );
+    }
+
+    #[test]
+    fn investigates_when_hurt_and_flees_when_low() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let brain = Brain::new(Vector2::zero());
+        let shooter = Vector2::new(0.0, 10.0);
+        let hurt = Perception {
+            hurt_from: Some(shooter),
+            ..perception(None, 80.0)
+        };
+        assert!(matches!(
+            brain.next_state(0.1, &hurt, &mut rng),
+            AiState::Investigate { pos, .. } if pos == shooter
+        ));
+        let badly_hurt = Perception {
+            health: 10.0,
+            ..hurt
+        };
+        assert_eq!(
+            brain.next_state(0.1, &badly_hurt, &mut rng),
+            AiState::Flee { from: shooter }
+        );
+    }
+
+    #[test]
+    fn idles_then_patrols_near_home() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut brain = Brain::new(Vector2::new(3.0, 3.0));
+        brain.state = brain.next_state(0.1, &perception(None, 100.0), &mut rng);
+        let AiState::Patrol { target } = brain.state else {
+            panic!("expected to patrol, got {:?}", brain.state);
+        };
+        assert!(target.distance_to(Vector2::new(3.0, 3.0)) <= Brain::PATROL_RADIUS);
+    }
+}
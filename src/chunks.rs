@@ -0,0 +1,401 @@
+use raylib::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    collision_world::CollisionWorld, game_map::MapCollider, lighting::LightEngine,
+    world_collider::WorldColliderHandle, Corpse, GameWorld, GroundItem, LootTarget, Player,
+    WorldContainer,
+};
+
+// Grid position of a chunk, chunk (0, 0) starts at the world origin
+pub type ChunkPos = (i32, i32);
+
+// Width and height of a chunk, in world units
+pub const CHUNK_SIZE: f32 = 32.0;
+
+pub fn chunk_at(pos: Vector2) -> ChunkPos {
+    (
+        (pos.x / CHUNK_SIZE).floor() as i32,
+        (pos.y / CHUNK_SIZE).floor() as i32,
+    )
+}
+
+pub fn chunk_rect(chunk: ChunkPos) -> Rectangle {
+    Rectangle::new(
+        chunk.0 as f32 * CHUNK_SIZE,
+        chunk.1 as f32 * CHUNK_SIZE,
+        CHUNK_SIZE,
+        CHUNK_SIZE,
+    )
+}
+
+// Chunks a rectangle overlaps
+pub fn chunks_in(rect: &Rectangle) -> impl Iterator<Item = ChunkPos> {
+    let min = chunk_at(Vector2::new(rect.x, rect.y));
+    let max = chunk_at(Vector2::new(rect.x + rect.width, rect.y + rect.height));
+    (min.1..=max.1).flat_map(move |y| (min.0..=max.0).map(move |x| (x, y)))
+}
+
+// The square of chunks kept loaded around a point, usually the player.
+// Chunks load within `LOAD_RADIUS` but only unload past `UNLOAD_RADIUS`, so walking back
+// and forth over a chunk border doesn't keep loading and unloading the same chunks.
+pub struct ChunkWindow {
+    loaded: HashSet<ChunkPos>,
+}
+
+impl ChunkWindow {
+    // Enough to cover the screen and how far the player can see
+    pub const LOAD_RADIUS: i32 = 2;
+    pub const UNLOAD_RADIUS: i32 = 3;
+
+    pub fn new() -> Self {
+        ChunkWindow {
+            loaded: HashSet::new(),
+        }
+    }
+
+    // Moves the window to `center`, returning the chunks that were loaded and unloaded
+    pub fn update(&mut self, center: Vector2) -> (Vec<ChunkPos>, Vec<ChunkPos>) {
+        let center = chunk_at(center);
+        let distance =
+            |chunk: &ChunkPos| (chunk.0 - center.0).abs().max((chunk.1 - center.1).abs());
+        let unloaded = self
+            .loaded
+            .iter()
+            .copied()
+            .filter(|chunk| distance(chunk) > Self::UNLOAD_RADIUS)
+            .collect::<Vec<ChunkPos>>();
+        for chunk in &unloaded {
+            self.loaded.remove(chunk);
+        }
+        let radius = Self::LOAD_RADIUS;
+        let loaded = (-radius..=radius)
+            .flat_map(|y| (-radius..=radius).map(move |x| (center.0 + x, center.1 + y)))
+            .filter(|chunk| self.loaded.insert(*chunk))
+            .collect();
+        (loaded, unloaded)
+    }
+
+    pub fn is_loaded(&self, chunk: ChunkPos) -> bool {
+        self.loaded.contains(&chunk)
+    }
+
+    pub fn loaded(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        self.loaded.iter().copied()
+    }
+}
+
+// Entities of an unloaded chunk, taken out of the simulation until it loads again
+#[derive(Default)]
+pub struct DormantChunk {
+    // Dummies with their bodies and lights removed, and where they stood
+    pub dummies: Vec<(Vector2, Player)>,
+    pub corpses: Vec<Corpse>,
+    pub ground_items: Vec<GroundItem>,
+    pub containers: Vec<WorldContainer>,
+}
+
+// Streams the simulation in chunks around the player. Static colliders of the map only exist
+// in the physics world while a chunk they overlap is loaded, and entities in unloaded chunks
+// are put to sleep so they cost nothing until the player comes back.
+pub struct WorldChunks {
+    window: ChunkWindow,
+    colliders: Vec<MapCollider>,
+    // Indices into `colliders` of the ones overlapping each chunk
+    chunk_colliders: HashMap<ChunkPos, Vec<usize>>,
+    walls: HashMap<usize, WorldColliderHandle>,
+    dormant: HashMap<ChunkPos, DormantChunk>,
+}
+
+impl WorldChunks {
+    pub fn new(colliders: Vec<MapCollider>) -> Self {
+        let mut chunk_colliders = HashMap::<ChunkPos, Vec<usize>>::new();
+        for (i, collider) in colliders.iter().enumerate() {
+            for chunk in chunks_in(&collider.bounds()) {
+                chunk_colliders.entry(chunk).or_default().push(i);
+            }
+        }
+        WorldChunks {
+            window: ChunkWindow::new(),
+            colliders,
+            chunk_colliders,
+            walls: HashMap::new(),
+            dormant: HashMap::new(),
+        }
+    }
+
+    // Map colliders currently in the physics world
+    pub fn walls(&self) -> impl Iterator<Item = &WorldColliderHandle> {
+        self.walls.values()
+    }
+
+    pub fn dormant(&self) -> impl Iterator<Item = &DormantChunk> {
+        self.dormant.values()
+    }
+
+    pub fn dormant_dummies(&self) -> usize {
+        self.dormant.values().map(|chunk| chunk.dummies.len()).sum()
+    }
+
+    pub fn update(
+        &mut self,
+        center: Vector2,
+        collision_world: &mut CollisionWorld,
+        game_world: &mut GameWorld,
+        light_engine: &mut LightEngine,
+        looting: &mut Option<LootTarget>,
+    ) {
+        let (loaded, unloaded) = self.window.update(center);
+        if !loaded.is_empty() || !unloaded.is_empty() {
+            self.update_walls(collision_world);
+        }
+        self.sleep_unloaded(collision_world, game_world, light_engine, looting);
+        for chunk in loaded {
+            let Some(dormant) = self.dormant.remove(&chunk) else {
+                continue;
+            };
+            // Dummies without room for their light sleep on until the chunk loads again
+            let mut sleeping = vec![];
+            for (pos, mut dummy) in dormant.dummies {
+                match Player::spawn_light(light_engine, pos) {
+                    Ok(light) => {
+                        dummy.player_light = light;
+                        dummy.collider = Player::spawn_body(collision_world, pos);
+                        game_world.dummies.push(dummy);
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to wake dummy: {}", err);
+                        sleeping.push((pos, dummy));
+                    }
+                }
+            }
+            if !sleeping.is_empty() {
+                self.dormant.entry(chunk).or_default().dummies = sleeping;
+            }
+            game_world.corpses.extend(dormant.corpses);
+            game_world.ground_items.extend(dormant.ground_items);
+            game_world.containers.extend(dormant.containers);
+        }
+    }
+
+    // Spawns the colliders of loaded chunks and removes those no loaded chunk overlaps
+    fn update_walls(&mut self, collision_world: &mut CollisionWorld) {
+        let needed = self
+            .window
+            .loaded()
+            .filter_map(|chunk| self.chunk_colliders.get(&chunk))
+            .flatten()
+            .copied()
+            .collect::<HashSet<usize>>();
+        let removed = self
+            .walls
+            .keys()
+            .copied()
+            .filter(|i| !needed.contains(i))
+            .collect::<Vec<usize>>();
+        for i in removed {
+            collision_world.delete_collider(self.walls.remove(&i).unwrap());
+        }
+        for i in needed {
+            self.walls.entry(i).or_insert_with(|| {
+                let collider = &self.colliders[i];
+                collision_world.spawn_static(collider.pos, collider.shape.clone())
+            });
+        }
+    }
+
+    // Moves the entities that ended up in unloaded chunks into their dormant chunk
+    fn sleep_unloaded(
+        &mut self,
+        collision_world: &mut CollisionWorld,
+        game_world: &mut GameWorld,
+        light_engine: &mut LightEngine,
+        looting: &mut Option<LootTarget>,
+    ) {
+        let window = &self.window;
+        let dormant = &mut self.dormant;
+        let dummies = take_unloaded(
+            &mut game_world.dummies,
+            |dummy| dummy.collider.get_pos(collision_world),
+            window,
+            &mut None,
+        );
+        for (chunk, dummy) in dummies {
+            let pos = dummy.collider.get_pos(collision_world);
+            collision_world.delete_collider(dummy.collider.clone());
+            light_engine.remove_light(&dummy.player_light);
+            dormant.entry(chunk).or_default().dummies.push((pos, dummy));
+        }
+
+        let mut looted_corpse = match *looting {
+            Some(LootTarget::Corpse(i)) => Some(i),
+            _ => None,
+        };
+        let mut looted_crate = match *looting {
+            Some(LootTarget::Crate(i)) => Some(i),
+            _ => None,
+        };
+        for (chunk, corpse) in take_unloaded(
+            &mut game_world.corpses,
+            |corpse| corpse.pos,
+            window,
+            &mut looted_corpse,
+        ) {
+            dormant.entry(chunk).or_default().corpses.push(corpse);
+        }
+        for (chunk, container) in take_unloaded(
+            &mut game_world.containers,
+            |container| container.pos,
+            window,
+            &mut looted_crate,
+        ) {
+            dormant.entry(chunk).or_default().containers.push(container);
+        }
+        for (chunk, ground_item) in take_unloaded(
+            &mut game_world.ground_items,
+            |ground_item| ground_item.pos(),
+            window,
+            &mut None,
+        ) {
+            dormant
+                .entry(chunk)
+                .or_default()
+                .ground_items
+                .push(ground_item);
+        }
+        *looting = match *looting {
+            Some(LootTarget::Corpse(_)) => looted_corpse.map(LootTarget::Corpse),
+            Some(LootTarget::Crate(_)) => looted_crate.map(LootTarget::Crate),
+            None => None,
+        };
+    }
+}
+
+// Removes the entities outside loaded chunks, along with the chunk each is in.
+// `index` follows the entity it points at as the ones before it are removed, and is
+// cleared if that entity is removed.
+fn take_unloaded<T>(
+    entities: &mut Vec<T>,
+    pos: impl Fn(&T) -> Vector2,
+    window: &ChunkWindow,
+    index: &mut Option<usize>,
+) -> Vec<(ChunkPos, T)> {
+    let mut taken = vec![];
+    let mut followed = None;
+    for (i, entity) in std::mem::take(entities).into_iter().enumerate() {
+        let chunk = chunk_at(pos(&entity));
+        if window.is_loaded(chunk) {
+            if *index == Some(i) {
+                followed = Some(entities.len());
+            }
+            entities.push(entity);
+        } else {
+            taken.push((chunk, entity));
+        }
+    }
+    *index = followed;
+    taken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{collision_world::ShapeArgs, Item};
+
+    #[test]
+    fn window_unloads_past_its_margin() {
+        let mut window = ChunkWindow::new();
+        let (loaded, unloaded) = window.update(Vector2::new(1.0, 1.0));
+        assert_eq!(loaded.len(), 25);
+        assert!(unloaded.is_empty());
+        assert!(window.update(Vector2::new(2.0, 1.0)).0.is_empty());
+
+        // A chunk over, a new column loads but the old one is kept
+        let (loaded, unloaded) = window.update(Vector2::new(CHUNK_SIZE + 1.0, 1.0));
+        assert_eq!(loaded.len(), 5);
+        assert!(unloaded.is_empty());
+        assert!(window.is_loaded((-2, 0)));
+
+        let (_, unloaded) = window.update(Vector2::new(CHUNK_SIZE * 2.0 + 1.0, 1.0));
+        assert_eq!(unloaded.len(), 5);
+        assert!(!window.is_loaded((-2, 0)));
+    }
+
+    #[test]
+    fn far_entities_sleep_until_their_chunk_loads() {
+        let mut collision_world = CollisionWorld::default();
+        let mut light_engine = LightEngine::new();
+        let mut game_world = GameWorld::new();
+        let far = Vector2::new(CHUNK_SIZE * 10.0, 0.0);
+        let wall = MapCollider {
+            pos: far,
+            shape: ShapeArgs::Cuboid {
+                half_extents: Vector2::new(1.0, 1.0),
+            },
+        };
+        let mut chunks = WorldChunks::new(vec![wall]);
+        let dummy = Player::new(&mut collision_world, &mut light_engine).unwrap();
+        dummy.collider.set_pos(far, &mut collision_world);
+        game_world.dummies.push(dummy);
+        game_world
+            .ground_items
+            .push(Item::Bandage.to_ground_item(Vector2::new(1.0, 1.0)));
+        game_world
+            .ground_items
+            .push(Item::MedKit.to_ground_item(far));
+        game_world.corpses.push(Corpse {
+            pos: far,
+            animation_stage: 0,
+            time_elapsed: 0.0,
+            angle: 0.0,
+            loot: crate::Container::new(Corpse::LOOT_SIZE),
+            time_since_death: 0.0,
+        });
+        game_world.corpses.push(Corpse {
+            pos: Vector2::zero(),
+            animation_stage: 0,
+            time_elapsed: 0.0,
+            angle: 0.0,
+            loot: crate::Container::new(Corpse::LOOT_SIZE),
+            time_since_death: 0.0,
+        });
+        let mut looting = Some(LootTarget::Corpse(1));
+        let bodies = collision_world.rapier.rigid_body_set.len();
+
+        chunks.update(
+            Vector2::zero(),
+            &mut collision_world,
+            &mut game_world,
+            &mut light_engine,
+            &mut looting,
+        );
+        assert!(game_world.dummies.is_empty());
+        assert_eq!(chunks.dormant_dummies(), 1);
+        assert_eq!(light_engine.spawned_lights(), 0);
+        assert_eq!(game_world.ground_items.len(), 1);
+        assert_eq!(game_world.corpses.len(), 1);
+        // The corpse being looted moved down in the list
+        assert!(matches!(looting, Some(LootTarget::Corpse(0))));
+        assert_eq!(chunks.walls().count(), 0);
+        assert_eq!(collision_world.rapier.rigid_body_set.len(), bodies - 1);
+
+        chunks.update(
+            far,
+            &mut collision_world,
+            &mut game_world,
+            &mut light_engine,
+            &mut None,
+        );
+        assert_eq!(game_world.dummies.len(), 1);
+        assert_eq!(chunks.dormant_dummies(), 0);
+        let light_id = game_world.dummies[0].player_light.id();
+        assert!(light_engine.lights().any(|(id, _)| id == light_id));
+        assert_eq!(
+            game_world.dummies[0].collider.get_pos(&collision_world),
+            far
+        );
+        assert_eq!(game_world.ground_items.len(), 1);
+        assert_eq!(game_world.corpses.len(), 1);
+        assert_eq!(chunks.walls().count(), 1);
+    }
+}
@@ -0,0 +1,203 @@
+use raylib::prelude::*;
+
+use crate::lighting::{
+    Light, AMBIENT_LIGHT_DAY, AMBIENT_LIGHT_MIDNIGHT, AMBIENT_LIGHT_NIGHT, AMBIENT_LIGHT_SUNRISE,
+};
+
+// Time of day in the world. Drives the ambient light and lets gameplay tell day from night.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldClock {
+    // Hours since midnight, from 0 up to 24
+    time: f32,
+    // Whole days passed since the world started
+    day: u32,
+    // Real seconds a whole day takes at normal speed
+    pub day_length: f32,
+    // How many times faster than normal time passes, raised to fast-forward
+    pub speed: f32,
+}
+
+impl WorldClock {
+    pub const DEFAULT_DAY_LENGTH: f32 = 20.0 * 60.0;
+    pub const FAST_FORWARD_SPEED: f32 = 60.0;
+    pub const START_TIME: f32 = 8.0;
+    const HOURS_PER_DAY: f32 = 24.0;
+    // Below this much daylight it counts as night
+    const NIGHT_DAYLIGHT: f32 = 0.3;
+    // Hour of the day each ambient light is reached at, the light is blended in between.
+    // Must be sorted and span the whole day.
+    const KEYFRAMES: [(f32, Light); 8] = [
+        (0.0, AMBIENT_LIGHT_MIDNIGHT),
+        (2.0, AMBIENT_LIGHT_NIGHT),
+        (5.0, AMBIENT_LIGHT_SUNRISE),
+        (8.0, AMBIENT_LIGHT_DAY),
+        (18.0, AMBIENT_LIGHT_DAY),
+        (20.0, AMBIENT_LIGHT_SUNRISE),
+        (22.0, AMBIENT_LIGHT_NIGHT),
+        (24.0, AMBIENT_LIGHT_MIDNIGHT),
+    ];
+
+    pub fn new(day_length: f32) -> Self {
+        WorldClock {
+            time: Self::START_TIME,
+            day: 0,
+            day_length,
+            speed: 1.0,
+        }
+    }
+
+    // Picks up on a given day and time, used when loading a save
+    pub fn at(day: u32, time: f32, day_length: f32) -> Self {
+        let mut clock = Self::new(day_length);
+        clock.day = day;
+        clock.set_time(time);
+        clock
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt * self.speed * Self::HOURS_PER_DAY / self.day_length;
+        while self.time >= Self::HOURS_PER_DAY {
+            self.time -= Self::HOURS_PER_DAY;
+            self.day += 1;
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    // Sets the hour of the current day
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.rem_euclid(Self::HOURS_PER_DAY);
+    }
+
+    // Reads an hour of the day typed as "HH" or "HH:MM"
+    pub fn parse_time(text: &str) -> Option<f32> {
+        let (hours, minutes) = text.trim().split_once(':').unwrap_or((text.trim(), "0"));
+        let hours: u32 = hours.parse().ok()?;
+        let minutes: u32 = minutes.parse().ok()?;
+        if hours >= 24 || minutes >= 60 {
+            return None;
+        }
+        Some(hours as f32 + minutes as f32 / 60.0)
+    }
+
+    // Jumps ahead to the next keyframe, moving on to the next day past midnight
+    pub fn skip_to_next_keyframe(&mut self) {
+        let next = Self::KEYFRAMES
+            .iter()
+            .map(|(hour, _)| *hour)
+            .find(|hour| *hour > self.time)
+            .unwrap_or(Self::HOURS_PER_DAY);
+        self.time = next;
+        self.update(0.0);
+    }
+
+    // The ambient light at the current time, blended between the keyframes around it
+    pub fn ambient_light(&self) -> Light {
+        let i = Self::KEYFRAMES
+            .iter()
+            .rposition(|(hour, _)| *hour <= self.time)
+            .unwrap_or(0)
+            .min(Self::KEYFRAMES.len() - 2);
+        let (from_hour, from) = &Self::KEYFRAMES[i];
+        let (to_hour, to) = &Self::KEYFRAMES[i + 1];
+        let t = (self.time - from_hour) / (to_hour - from_hour);
+        let (from, to) = (from.color(), to.color());
+        Light::Ambient {
+            color: Vector4::new(
+                from.x + (to.x - from.x) * t,
+                from.y + (to.y - from.y) * t,
+                from.z + (to.z - from.z) * t,
+                from.w + (to.w - from.w) * t,
+            ),
+        }
+    }
+
+    // How bright the ambient light is, from 0 at midnight to 1 in full daylight
+    pub fn daylight(&self) -> f32 {
+        let color = self.ambient_light().color();
+        color.w * (color.x + color.y + color.z) / 3.0
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.daylight() < Self::NIGHT_DAYLIGHT
+    }
+
+    // Time as shown to the player, such as "Day 2 18:30"
+    pub fn display(&self) -> String {
+        let minutes = (self.time * 60.0) as u32;
+        format!(
+            "Day {} {:02}:{:02}",
+            self.day + 1,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_wraps_into_the_next_day() {
+        let mut clock = WorldClock::new(24.0);
+        clock.set_time(23.0);
+        clock.update(2.0);
+        assert_eq!(clock.day(), 1);
+        assert!((clock.time() - 1.0).abs() < 1e-4);
+
+        clock.speed = WorldClock::FAST_FORWARD_SPEED;
+        clock.update(1.0);
+        assert_eq!(clock.day(), 3);
+    }
+
+    #[test]
+    fn ambient_light_blends_between_keyframes() {
+        let mut clock = WorldClock::new(WorldClock::DEFAULT_DAY_LENGTH);
+        clock.set_time(12.0);
+        assert_eq!(clock.ambient_light().color(), AMBIENT_LIGHT_DAY.color());
+        assert!(!clock.is_night());
+
+        clock.set_time(0.0);
+        assert_eq!(
+            clock.ambient_light().color(),
+            AMBIENT_LIGHT_MIDNIGHT.color()
+        );
+        assert!(clock.is_night());
+
+        // Halfway from sunset to night
+        clock.set_time(21.0);
+        let (sunset, night) = (AMBIENT_LIGHT_SUNRISE.color(), AMBIENT_LIGHT_NIGHT.color());
+        let color = clock.ambient_light().color();
+        assert!((color.w - (sunset.w + night.w) / 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn skipping_goes_to_the_next_keyframe() {
+        let mut clock = WorldClock::new(WorldClock::DEFAULT_DAY_LENGTH);
+        clock.set_time(19.0);
+        clock.skip_to_next_keyframe();
+        assert_eq!(clock.time(), 20.0);
+        clock.set_time(23.0);
+        clock.skip_to_next_keyframe();
+        assert_eq!((clock.day(), clock.time()), (1, 0.0));
+        assert_eq!(clock.display(), "Day 2 00:00");
+    }
+
+    #[test]
+    fn typed_times_are_parsed() {
+        assert_eq!(WorldClock::parse_time("18"), Some(18.0));
+        assert_eq!(WorldClock::parse_time("06:30"), Some(6.5));
+        assert_eq!(WorldClock::parse_time("0:00"), Some(0.0));
+        assert_eq!(WorldClock::parse_time("24"), None);
+        assert_eq!(WorldClock::parse_time("12:60"), None);
+        assert_eq!(WorldClock::parse_time("noon"), None);
+        assert_eq!(WorldClock::parse_time(""), None);
+    }
+}
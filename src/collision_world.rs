@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 
+use crate::damage::{DamageEvent, DamageTarget, Hit, HitZone};
 use crate::lighting::LightEngine;
+use crate::weapon::Bullet;
 use crate::world_collider::WorldColliderHandle;
 use crate::GameWorld;
 use crate::Player;
@@ -13,6 +15,9 @@ use crate::traits::*;
 #[derive(Default)]
 pub struct CollisionWorld {
     pub rapier: RapierCollisionWorld, //TODO: Make private
+    // Areas where static colliders were added or removed since the navigation grid last
+    // caught up
+    pub changed_static_areas: Vec<Rectangle>,
 }
 
 pub struct ColliderUserData;
@@ -53,10 +58,27 @@ impl RigidBodyArgs {
     }
 }
 
+#[derive(Clone)]
 pub enum ShapeArgs {
     Cuboid { half_extents: Vector2 },
     Ball { radius: f32 },
     Triangle { points: (Vector2, Vector2, Vector2) },
+    // Closed outline, may be concave. Split into convex parts when built.
+    Polygon { points: Vec<Vector2> },
+    Polyline { points: Vec<Vector2> },
+}
+
+impl ShapeArgs {
+    fn vertices(points: &[Vector2]) -> Vec<Point<Real>> {
+        points.iter().map(|p| point![p.x, p.y]).collect()
+    }
+
+    // Segment indices connecting each point to the next, wrapping around
+    fn outline_indices(num_points: usize) -> Vec<[u32; 2]> {
+        (0..num_points as u32)
+            .map(|i| [i, (i + 1) % num_points as u32])
+            .collect()
+    }
 }
 
 type RelitiveShapeArgs = (Vector2, ShapeArgs);
@@ -81,6 +103,13 @@ impl ColliderArgs {
                 rapier2d::na::Vector2::from_raylib_vector2(points.1).into(),
                 rapier2d::na::Vector2::from_raylib_vector2(points.2).into(),
             ),
+            ShapeArgs::Polygon { points } => ColliderBuilder::convex_decomposition(
+                &ShapeArgs::vertices(points),
+                &ShapeArgs::outline_indices(points.len()),
+            ),
+            ShapeArgs::Polyline { points } => {
+                ColliderBuilder::polyline(ShapeArgs::vertices(points), None)
+            }
         }
         .restitution(self.restitution)
         .density(self.density)
@@ -109,6 +138,13 @@ impl ColliderArgs {
                             nalgebra::Vector2::new(points.1.x, points.1.y).into(),
                             nalgebra::Vector2::new(points.2.x, points.2.y).into(),
                         )),
+                        ShapeArgs::Polygon { points } => SharedShape::convex_decomposition(
+                            &ShapeArgs::vertices(points),
+                            &ShapeArgs::outline_indices(points.len()),
+                        ),
+                        ShapeArgs::Polyline { points } => {
+                            SharedShape::polyline(ShapeArgs::vertices(points), None)
+                        }
                     };
                     (isometry, shape)
                 })
@@ -118,6 +154,7 @@ impl ColliderArgs {
             .restitution(self.restitution)
             .density(self.density)
             .friction(self.friction)
+            .sensor(self.sensor)
             .active_events(ActiveEvents::COLLISION_EVENTS)
             .build()
     }
@@ -157,6 +194,15 @@ impl CollisionWorld {
     }
 
     pub fn delete_collider(&mut self, collider: WorldColliderHandle) {
+        let is_static = self
+            .rapier
+            .rigid_body_set
+            .get(collider.rigid_body_handle)
+            .is_some_and(|rigid_body| rigid_body.is_fixed());
+        if is_static {
+            let area = self.get_static_area(&collider);
+            self.changed_static_areas.push(area);
+        }
         self.rapier.rigid_body_set.remove(
             collider.rigid_body_handle,
             &mut self.rapier.island_manager,
@@ -167,6 +213,58 @@ impl CollisionWorld {
         );
     }
 
+    pub fn spawn_bullet(&mut self, pos: Vector2, vel: Vector2, radius: f32) -> WorldColliderHandle {
+        self.spawn_collider(
+            RigidBodyArgs {
+                dynamic: true,
+                pos,
+                vel,
+                user_data: ColliderUserData::BULLET,
+            },
+            ColliderArgs {
+                density: 1.5,
+                restitution: 0.1,
+                friction: 0.7,
+                user_data: ColliderUserData::BULLET,
+                sensor: false,
+            },
+            ShapeArgs::Ball { radius },
+        )
+    }
+
+    pub fn spawn_static(&mut self, pos: Vector2, shape_args: ShapeArgs) -> WorldColliderHandle {
+        let handle = self.spawn_collider(
+            RigidBodyArgs {
+                dynamic: false,
+                pos,
+                vel: Vector2::zero(),
+                user_data: ColliderUserData::WALL,
+            },
+            ColliderArgs {
+                density: 1.0,
+                restitution: 0.5,
+                friction: 0.5,
+                user_data: ColliderUserData::WALL,
+                sensor: false,
+            },
+            shape_args,
+        );
+        let area = self.get_static_area(&handle);
+        self.changed_static_areas.push(area);
+        handle
+    }
+
+    // Bounding box of a static collider, in world units
+    fn get_static_area(&self, collider: &WorldColliderHandle) -> Rectangle {
+        let aabb = self.rapier.collider_set[collider.collider_handle].compute_aabb();
+        Rectangle::new(
+            aabb.mins.x,
+            aabb.mins.y,
+            aabb.maxs.x - aabb.mins.x,
+            aabb.maxs.y - aabb.mins.y,
+        )
+    }
+
     pub fn spawn_compound(
         &mut self,
         rigid_body_args: RigidBodyArgs,
@@ -192,13 +290,65 @@ impl CollisionWorld {
     const FIXED_TIME_STEP: f32 = 1.0 / 200.0;
     const MAX_FRAME_TIME: f32 = 0.25; // To prevent spiral of death in case of a long frame
 
+    // Walls of any solid shape block sight and light. Sensors, characters and open
+    // polylines don't.
+    pub fn blocks_sight(&self, collider: &Collider) -> bool {
+        let is_fixed = collider
+            .parent()
+            .is_some_and(|parent| self.rapier.rigid_body_set[parent].is_fixed());
+        is_fixed && !collider.is_sensor() && collider.shape().as_polyline().is_none()
+    }
+
+    // Whether any of 4 points around the target can be seen from the viewer without a wall
+    // in the way
+    pub fn can_see(&self, viewer: &WorldColliderHandle, target: &WorldColliderHandle) -> bool {
+        let viewer_radius = viewer.get_bounding_sphere(self).radius;
+        let target_radius = target.get_bounding_sphere(self).radius;
+        let viewer_pos = viewer.get_pos(self);
+        let predicate = |_handle: ColliderHandle, collider: &Collider| self.blocks_sight(collider);
+        [
+            Vector2::new(0.0, 1.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(0.0, -1.0),
+            Vector2::new(-1.0, 0.0),
+        ]
+        .into_iter()
+        .any(|offset| {
+            let dx = target.get_pos(self) + offset - viewer_pos;
+            let dn = dx.normalized();
+            let ray_origin = viewer_pos + dn * 2.0 * viewer_radius;
+            let ray = rapier2d::prelude::Ray::new(
+                point![ray_origin.x, ray_origin.y],
+                vector![dn.x, dn.y],
+            );
+            let ray_length = dx.length() - (target_radius + viewer_radius);
+            self.rapier
+                .query_pipeline
+                .cast_ray_and_get_normal(
+                    &self.rapier.rigid_body_set,
+                    &self.rapier.collider_set,
+                    &ray,
+                    ray_length,
+                    true,
+                    QueryFilter {
+                        exclude_rigid_body: Some(target.rigid_body_handle),
+                        predicate: Some(&predicate),
+                        ..Default::default()
+                    },
+                )
+                .is_none()
+        })
+    }
+
+    // Hurts the character with the first bullet touching it fast enough, then removes the bullet
     pub fn apply_collision_damage(
         &mut self,
         player: &mut Player,
-        bullets: &mut Vec<WorldColliderHandle>,
+        target: DamageTarget,
+        bullets: &mut Vec<Bullet>,
+        damage_events: &mut Vec<DamageEvent>,
     ) {
         let mut bullet = None;
-        let player_deflection_level = 60.0;
         for collision in self
             .rapier
             .narrow_phase
@@ -209,57 +359,83 @@ impl CollisionWorld {
             } else {
                 collision.collider1
             };
-            let other_collider = &self
-                .rapier
-                .collider_set
-                .get(other_collider_handle);
-            if other_collider.is_none() {
+            let Some(other_collider) = self.rapier.collider_set.get(other_collider_handle) else {
                 break;
-            }
-            let other_collider = other_collider.unwrap();
-            let other_rigid_body_handle = other_collider.parent().unwrap();
-            let other_rigid_body = &self.rapier.rigid_body_set[other_rigid_body_handle];
-            let other_collider_speed = other_rigid_body.linvel().to_raylib_vector2().length();
-            if other_rigid_body.user_data == ColliderUserData::BULLET
-                && dbg!(other_collider_speed) > player_deflection_level
-            {
-                let bullet_damage =
-                    (other_collider_speed / 1.0 - player_deflection_level).clamp(0.0, 25.0);
-                player.health -= dbg!(bullet_damage);
+            };
+            let Some(hit_bullet) = bullets
+                .iter()
+                .position(|bullet| bullet.collider.collider_handle == other_collider_handle)
+            else {
+                continue;
+            };
+            let other_rigid_body = &self.rapier.rigid_body_set[other_collider.parent().unwrap()];
+            let velocity = other_rigid_body.linvel().to_raylib_vector2();
+            if velocity.length() > Bullet::MIN_DAMAGING_SPEED {
                 bullet = Some((
-                    WorldColliderHandle {
-                        rigid_body_handle: other_rigid_body_handle,
-                        collider_handle: other_collider_handle,
-                    },
-                    other_rigid_body.linvel().to_raylib_vector2().normalized()
-                        * other_collider_speed
-                        * other_collider.mass(),
+                    hit_bullet,
+                    other_rigid_body.translation().to_raylib_vector2(),
+                    velocity,
+                    velocity * other_collider.mass(),
                 ));
                 break;
             }
         }
-        if let Some((bullet, force)) = bullet {
-            bullets.retain(|b| *b != bullet);
-            self.delete_collider(bullet);
+        if let Some((i, bullet_pos, velocity, force)) = bullet {
+            let bullet = bullets.remove(i);
+            let player_pos = player.collider.get_pos(self);
+            // How far from the centre of the character the bullet's path passes
+            let reach = player.collider.get_bounding_sphere(self).radius
+                + bullet.collider.get_bounding_sphere(self).radius;
+            let to_center = player_pos - bullet_pos;
+            let direction = velocity.normalized();
+            let offset = (direction.x * to_center.y - direction.y * to_center.x).abs() / reach;
+            let zone = HitZone::from_hit(offset, player.facing(), direction);
+            let wound = player.take_hit(Hit {
+                damage: bullet.damage,
+                penetration: bullet.penetration,
+                zone,
+            });
+            damage_events.push(DamageEvent {
+                target,
+                collider: player.collider.collider_handle,
+                pos: bullet_pos,
+                direction,
+                zone,
+                wound,
+            });
+            self.delete_collider(bullet.collider);
             player.collider.apply_impulse(force, self);
         }
     }
 
-
-    pub fn step(&mut self, rl: &RaylibHandle, player: &mut Player, game_world: &mut GameWorld, light_engine: &mut LightEngine) {
-        // Get the elapsed time for the current frame
-        let frame_time = rl.get_frame_time().min(Self::MAX_FRAME_TIME);
+    pub fn step(
+        &mut self,
+        dt: f32,
+        player: &mut Player,
+        game_world: &mut GameWorld,
+        light_engine: &mut LightEngine,
+    ) {
+        // Clamp the elapsed time for the current frame
+        let frame_time = dt.min(Self::MAX_FRAME_TIME);
 
         // Accumulate the elapsed time
         self.rapier.accumulated_time += frame_time;
 
         // Perform physics updates in fixed time steps
         while self.rapier.accumulated_time >= Self::FIXED_TIME_STEP {
-            self.apply_collision_damage(player, &mut game_world.bullets);
+            self.apply_collision_damage(
+                player,
+                DamageTarget::Player,
+                &mut game_world.bullets,
+                &mut game_world.damage_events,
+            );
             for dummy in &mut game_world.dummies {
-                self.apply_collision_damage(dummy, &mut game_world.bullets);
-                dummy.handle_movement(rl, self, &mut Vector2::zero());
-                dummy.aim_at(player.collider.get_pos(self), self);
+                self.apply_collision_damage(
+                    dummy,
+                    DamageTarget::Dummy,
+                    &mut game_world.bullets,
+                    &mut game_world.damage_events,
+                );
                 if dummy.health <= 0.0 {
                     game_world.corpses.push(dummy.get_corpse(self));
                     self.delete_collider(dummy.collider.clone());
@@ -273,3 +449,89 @@ impl CollisionWorld {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_character(collision_world: &mut CollisionWorld, pos: Vector2) -> WorldColliderHandle {
+        collision_world.spawn_collider(
+            RigidBodyArgs {
+                pos,
+                ..Default::default()
+            },
+            ColliderArgs::default(),
+            ShapeArgs::Ball { radius: 0.5 },
+        )
+    }
+
+    #[test]
+    fn walls_of_any_solid_shape_block_sight() {
+        let ellipse = (0..16)
+            .map(|i| {
+                let angle = i as f32 / 16.0 * std::f32::consts::TAU;
+                Vector2::new(angle.cos(), angle.sin() * 3.0)
+            })
+            .collect();
+        // Concave, opening towards the viewer
+        let bracket = [
+            (-1.0, -3.0),
+            (1.0, -3.0),
+            (1.0, 3.0),
+            (-1.0, 3.0),
+            (-1.0, 2.0),
+            (0.0, 2.0),
+            (0.0, -2.0),
+            (-1.0, -2.0),
+        ]
+        .map(|(x, y)| Vector2::new(x, y))
+        .to_vec();
+        let walls = [
+            None,
+            Some(ShapeArgs::Ball { radius: 2.0 }),
+            Some(ShapeArgs::Polygon { points: ellipse }),
+            Some(ShapeArgs::Polygon { points: bracket }),
+        ];
+        for wall in walls {
+            let mut collision_world = CollisionWorld::default();
+            let viewer = spawn_character(&mut collision_world, Vector2::zero());
+            let target = spawn_character(&mut collision_world, Vector2::new(10.0, 0.0));
+            let blocked = wall.is_some();
+            if let Some(wall) = wall {
+                collision_world.spawn_static(Vector2::new(5.0, 0.0), wall);
+            }
+            // Updates the query pipeline
+            collision_world.rapier.step();
+            assert_eq!(collision_world.can_see(&viewer, &target), !blocked);
+        }
+    }
+
+    #[test]
+    fn sensors_and_polylines_do_not_block_sight() {
+        let mut collision_world = CollisionWorld::default();
+        let viewer = spawn_character(&mut collision_world, Vector2::zero());
+        let target = spawn_character(&mut collision_world, Vector2::new(10.0, 0.0));
+        collision_world.spawn_static(
+            Vector2::new(5.0, 0.0),
+            ShapeArgs::Polyline {
+                points: vec![Vector2::new(0.0, -3.0), Vector2::new(0.0, 3.0)],
+            },
+        );
+        collision_world.spawn_collider(
+            RigidBodyArgs {
+                dynamic: false,
+                pos: Vector2::new(7.0, 0.0),
+                ..Default::default()
+            },
+            ColliderArgs {
+                sensor: true,
+                ..Default::default()
+            },
+            ShapeArgs::Cuboid {
+                half_extents: Vector2::new(0.5, 3.0),
+            },
+        );
+        collision_world.rapier.step();
+        assert!(collision_world.can_see(&viewer, &target));
+    }
+}
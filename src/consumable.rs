@@ -0,0 +1,294 @@
+#![allow(dead_code)]
+
+use crate::inventory::{Item, ItemLocation};
+use crate::Player;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConsumableEffect {
+    // Heals bit by bit while the item is being used
+    Heal(f32),
+    // Heals and stops all bleeding
+    Bandage(f32),
+    // Lowers hunger, thirst and fatigue
+    Nourish {
+        hunger: f32,
+        thirst: f32,
+        fatigue: f32,
+    },
+}
+
+impl ConsumableEffect {
+    // Applies the part of the effect gained over time, `share` being the part of the use time
+    // that just passed
+    pub fn apply_gradually(&self, player: &mut Player, share: f32) {
+        if let ConsumableEffect::Heal(amount) = self {
+            player.health = (player.health + amount * share).min(Player::MAX_HEALTH);
+        }
+    }
+
+    // Applies the rest of the effect once the item has been used
+    pub fn apply(&self, player: &mut Player) {
+        match self {
+            ConsumableEffect::Heal(_) => {}
+            ConsumableEffect::Bandage(amount) => {
+                player.health = (player.health + amount).min(Player::MAX_HEALTH);
+                player.bleeding = 0.0;
+            }
+            ConsumableEffect::Nourish {
+                hunger,
+                thirst,
+                fatigue,
+            } => player.needs.nourish(*hunger, *thirst, *fatigue),
+        }
+    }
+
+    pub fn is_gradual(&self) -> bool {
+        matches!(self, ConsumableEffect::Heal(_))
+    }
+
+    // Whether using the item now would do anything, a medkit does nothing at full health
+    pub fn has_effect(&self, player: &Player) -> bool {
+        match self {
+            ConsumableEffect::Heal(_) => player.health < Player::MAX_HEALTH,
+            ConsumableEffect::Bandage(_) => {
+                player.health < Player::MAX_HEALTH || player.bleeding > 0.0
+            }
+            ConsumableEffect::Nourish {
+                hunger,
+                thirst,
+                fatigue,
+            } => {
+                let needs = &player.needs;
+                (*hunger > 0.0 && needs.hunger > 0.0)
+                    || (*thirst > 0.0 && needs.thirst > 0.0)
+                    || (*fatigue > 0.0 && needs.fatigue > 0.0)
+            }
+        }
+    }
+
+    pub fn is_healing(&self) -> bool {
+        matches!(
+            self,
+            ConsumableEffect::Heal(_) | ConsumableEffect::Bandage(_)
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ConsumableStats {
+    // Seconds the user has to stand still and unharmed to use the item
+    pub use_time: f32,
+    // Uses before the item is used up
+    pub charges: u32,
+    pub effect: ConsumableEffect,
+}
+
+impl Item {
+    pub fn consumable_stats(&self) -> Option<ConsumableStats> {
+        match self {
+            Item::MedKit => Some(ConsumableStats {
+                use_time: 4.0,
+                charges: 3,
+                effect: ConsumableEffect::Heal(40.0),
+            }),
+            Item::Bandage => Some(ConsumableStats {
+                use_time: 1.5,
+                charges: 1,
+                effect: ConsumableEffect::Bandage(10.0),
+            }),
+            Item::CannedFood => Some(ConsumableStats {
+                use_time: 3.0,
+                charges: 2,
+                effect: ConsumableEffect::Nourish {
+                    hunger: 0.3,
+                    thirst: 0.0,
+                    fatigue: 0.0,
+                },
+            }),
+            Item::WaterBottle => Some(ConsumableStats {
+                use_time: 2.0,
+                charges: 3,
+                effect: ConsumableEffect::Nourish {
+                    hunger: 0.0,
+                    thirst: 0.25,
+                    fatigue: 0.0,
+                },
+            }),
+            Item::EnergyDrink => Some(ConsumableStats {
+                use_time: 1.5,
+                charges: 1,
+                effect: ConsumableEffect::Nourish {
+                    hunger: 0.0,
+                    thirst: 0.15,
+                    fatigue: 0.4,
+                },
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConsumptionEvent {
+    Finished,
+    Interrupted,
+}
+
+// An item being used from the inventory. Getting hit, moving or losing the item cancels it.
+#[derive(Clone, Debug)]
+pub struct Consumption {
+    pub location: ItemLocation,
+    pub item: Item,
+    pub stats: ConsumableStats,
+    timer: f32,
+}
+
+impl Consumption {
+    pub fn new(item: &Item, location: ItemLocation) -> Option<Self> {
+        let stats = item.consumable_stats()?;
+        Some(Consumption {
+            location,
+            item: item.clone(),
+            stats,
+            timer: stats.use_time,
+        })
+    }
+
+    // From 0 to 1 while the item is being used
+    pub fn progress(&self) -> f32 {
+        1.0 - self.timer / self.stats.use_time
+    }
+
+    pub fn update(&mut self, dt: f32, moving: bool) -> Option<ConsumptionEvent> {
+        if moving {
+            return Some(ConsumptionEvent::Interrupted);
+        }
+        self.timer -= dt;
+        if self.timer <= 0.0 {
+            return Some(ConsumptionEvent::Finished);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        collision_world::CollisionWorld, input::PlayerInput, inventory::GridId,
+        lighting::LightEngine,
+    };
+    use raylib::prelude::Vector2;
+
+    fn player_with(item: Item) -> (Player, ItemLocation) {
+        let mut player =
+            Player::new(&mut CollisionWorld::default(), &mut LightEngine::new()).unwrap();
+        let location = player
+            .inventory
+            .try_insert(item.to_inventory_item(false))
+            .unwrap();
+        (player, location)
+    }
+
+    // Runs whole seconds so the use times divide evenly
+    fn use_for(player: &mut Player, seconds: u32, input: &PlayerInput) {
+        for _ in 0..seconds {
+            player.handle_consumables(input, 1.0);
+        }
+    }
+
+    #[test]
+    fn medkit_heals_while_being_used() {
+        let (mut player, location) = player_with(Item::MedKit);
+        player.health = 20.0;
+        player.inventory.use_requested = Some(location);
+        use_for(&mut player, 1, &PlayerInput::default());
+        assert_eq!(player.health, 30.0);
+        assert_eq!(player.inventory.get(location).unwrap().charges, Some(3));
+
+        use_for(&mut player, 3, &PlayerInput::default());
+        assert_eq!(player.health, 60.0);
+        assert!(player.consumption.is_none());
+        assert_eq!(player.inventory.get(location).unwrap().charges, Some(2));
+    }
+
+    #[test]
+    fn medkit_stops_once_health_is_full() {
+        let (mut player, location) = player_with(Item::MedKit);
+        player.health = 90.0;
+        player.inventory.use_requested = Some(location);
+        use_for(&mut player, 1, &PlayerInput::default());
+        assert_eq!(player.health, Player::MAX_HEALTH);
+        assert!(player.consumption.is_none());
+        assert_eq!(player.inventory.get(location).unwrap().charges, Some(2));
+    }
+
+    #[test]
+    fn interrupted_medkit_keeps_what_it_healed_and_uses_a_charge() {
+        let (mut player, location) = player_with(Item::MedKit);
+        player.health = 20.0;
+        player.inventory.use_requested = Some(location);
+        use_for(&mut player, 1, &PlayerInput::default());
+        let moving = PlayerInput {
+            movement: Vector2::new(1.0, 0.0),
+            ..Default::default()
+        };
+        use_for(&mut player, 1, &moving);
+        assert_eq!(player.health, 30.0);
+        assert!(player.consumption.is_none());
+        assert_eq!(player.inventory.get(location).unwrap().charges, Some(2));
+    }
+
+    #[test]
+    fn bandage_applies_when_finished() {
+        let (mut player, location) = player_with(Item::Bandage);
+        player.health = 50.0;
+        player.bleeding = 2.0;
+        player.inventory.use_requested = Some(location);
+        use_for(&mut player, 1, &PlayerInput::default());
+        assert_eq!((player.health, player.bleeding), (50.0, 2.0));
+        use_for(&mut player, 1, &PlayerInput::default());
+        assert_eq!((player.health, player.bleeding), (60.0, 0.0));
+        assert!(player.inventory.get(location).is_none());
+    }
+
+    #[test]
+    fn items_that_would_do_nothing_are_not_used() {
+        let (mut player, medkit) = player_with(Item::MedKit);
+        player.inventory.use_requested = Some(medkit);
+        use_for(&mut player, 1, &PlayerInput::default());
+        assert!(player.consumption.is_none());
+        assert_eq!(player.inventory.get(medkit).unwrap().charges, Some(3));
+
+        let water = player
+            .inventory
+            .try_insert(Item::WaterBottle.to_inventory_item(false))
+            .unwrap();
+        player.needs.thirst = 0.0;
+        player.inventory.use_requested = Some(water);
+        player.handle_consumables(&PlayerInput::default(), 0.0);
+        assert!(player.consumption.is_none());
+        player.needs.thirst = 0.5;
+        player.inventory.use_requested = Some(water);
+        player.handle_consumables(&PlayerInput::default(), 0.0);
+        assert_eq!(player.consumption.as_ref().unwrap().item, Item::WaterBottle);
+    }
+
+    #[test]
+    fn quick_heal_skips_items_that_would_do_nothing() {
+        let (mut player, _) = player_with(Item::MedKit);
+        let bandage = player
+            .inventory
+            .try_insert(Item::Bandage.to_inventory_item(false))
+            .unwrap();
+        assert_eq!(bandage.grid, GridId::Pockets);
+        // Bleeding at full health, only the bandage helps
+        player.bleeding = 1.0;
+        let quick_heal = PlayerInput {
+            quick_heal: true,
+            ..Default::default()
+        };
+        player.handle_consumables(&quick_heal, 0.0);
+        assert_eq!(player.consumption.as_ref().unwrap().location, bandage);
+    }
+}
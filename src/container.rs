@@ -0,0 +1,720 @@
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    assets::Assets,
+    inventory::{InventoryFull, InventoryItem, Item},
+    lighting_renderer::LightingRenderer,
+    ImprovedCamera,
+};
+
+// A grid of items. The player's pockets, backpacks, rigs and loot crates are all containers.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Container {
+    pub size: (i32, i32),
+    #[serde(with = "grid_items")]
+    pub items: HashMap<(i32, i32), InventoryItem>,
+}
+
+// Json maps need string keys, so the items are stored as a list of positions and items
+mod grid_items {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    use crate::inventory::InventoryItem;
+
+    pub fn serialize<S: Serializer>(
+        items: &HashMap<(i32, i32), InventoryItem>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut slots = items.iter().collect::<Vec<(&(i32, i32), &InventoryItem)>>();
+        slots.sort_by_key(|(grid_pos, _)| (grid_pos.1, grid_pos.0));
+        slots.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<(i32, i32), InventoryItem>, D::Error> {
+        Ok(
+            Vec::<((i32, i32), InventoryItem)>::deserialize(deserializer)?
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+impl Container {
+    pub const GRID_SPACING: f32 = 17.0;
+    // Width of the frame around the cells
+    const BORDER: f32 = 4.0;
+
+    pub fn new(size: (i32, i32)) -> Self {
+        Container {
+            size,
+            items: HashMap::new(),
+        }
+    }
+
+    // Size of the grid on screen before scaling
+    pub fn get_pixel_size(size: (i32, i32)) -> Vector2 {
+        Vector2::new(
+            size.0 as f32 * Self::GRID_SPACING + Self::BORDER + 3.0,
+            size.1 as f32 * Self::GRID_SPACING + Self::BORDER + 3.0,
+        )
+    }
+
+    // Area covered by the grid on screen
+    pub fn get_screen_rect(size: (i32, i32), scale: f32, top_left: Vector2) -> Rectangle {
+        let pixel_size = Self::get_pixel_size(size);
+        Rectangle::new(
+            top_left.x,
+            top_left.y,
+            pixel_size.x * scale,
+            pixel_size.y * scale,
+        )
+    }
+
+    // Which item, by its grid position, covers each cell. Indexed as `[y][x]`.
+    pub fn occupancy_grid(&self) -> Vec<Vec<Option<(i32, i32)>>> {
+        let mut grid = vec![vec![None; self.size.0 as usize]; self.size.1 as usize];
+        for (grid_pos, item) in &self.items {
+            let size = item.grid_size();
+            for y in grid_pos.1..grid_pos.1 + size.1 {
+                for x in grid_pos.0..grid_pos.0 + size.0 {
+                    if self.in_bounds((x, y), (1, 1)) {
+                        grid[y as usize][x as usize] = Some(*grid_pos);
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn in_bounds(&self, grid_pos: (i32, i32), size: (i32, i32)) -> bool {
+        grid_pos.0 >= 0
+            && grid_pos.1 >= 0
+            && grid_pos.0 + size.0 <= self.size.0
+            && grid_pos.1 + size.1 <= self.size.1
+    }
+
+    // Grid positions of the items covering any cell of the area, excluding `ignored` ones
+    fn items_in_area(
+        &self,
+        grid_pos: (i32, i32),
+        size: (i32, i32),
+        ignored: &[(i32, i32)],
+    ) -> Vec<(i32, i32)> {
+        let occupancy_grid = self.occupancy_grid();
+        let mut found = vec![];
+        for y in grid_pos.1.max(0)..(grid_pos.1 + size.1).min(self.size.1) {
+            for x in grid_pos.0.max(0)..(grid_pos.0 + size.0).min(self.size.0) {
+                if let Some(other) = occupancy_grid[y as usize][x as usize] {
+                    if !ignored.contains(&other) && !found.contains(&other) {
+                        found.push(other);
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    // Whether an item of `size` cells fits at `grid_pos` without leaving the grid
+    // or covering another item
+    pub fn can_place(
+        &self,
+        grid_pos: (i32, i32),
+        size: (i32, i32),
+        ignored: &[(i32, i32)],
+    ) -> bool {
+        self.in_bounds(grid_pos, size) && self.items_in_area(grid_pos, size, ignored).is_empty()
+    }
+
+    // Tops up existing stacks of the item first, then places the rest in the first free spot
+    pub fn try_insert(&mut self, mut item: InventoryItem) -> Result<(i32, i32), InventoryFull> {
+        let mut stacks = self
+            .items
+            .iter()
+            .filter(|(_, stack)| stack.can_stack_with(&item))
+            .map(|(grid_pos, _)| *grid_pos)
+            .collect::<Vec<(i32, i32)>>();
+        stacks.sort_by_key(|grid_pos| (grid_pos.1, grid_pos.0));
+        for grid_pos in stacks {
+            let stack = self.items.get_mut(&grid_pos).unwrap();
+            let moved = item
+                .count
+                .min(stack.item.get_max_stack().saturating_sub(stack.count));
+            stack.count += moved;
+            item.count -= moved;
+            if item.count == 0 {
+                return Ok(grid_pos);
+            }
+        }
+        self.try_insert_without_merging(item)
+    }
+
+    // Places the item in the first free spot scanning from the top left, rotating it if
+    // that is the only way it fits
+    fn try_insert_without_merging(
+        &mut self,
+        mut item: InventoryItem,
+    ) -> Result<(i32, i32), InventoryFull> {
+        let square = item.size.0 == item.size.1;
+        for rotated in [item.rotated, !item.rotated] {
+            if square && rotated != item.rotated {
+                continue;
+            }
+            item.rotated = rotated;
+            for y in 0..self.size.1 {
+                for x in 0..self.size.0 {
+                    if self.can_place((x, y), item.grid_size(), &[]) {
+                        self.items.insert((x, y), item);
+                        return Ok((x, y));
+                    }
+                }
+            }
+        }
+        Err(InventoryFull(item))
+    }
+
+    // Puts an item from another grid at `grid_pos`, or onto the stack it is dropped on.
+    // Whatever could not be placed is handed back.
+    pub fn place(
+        &mut self,
+        grid_pos: (i32, i32),
+        mut item: InventoryItem,
+    ) -> Result<(), InventoryFull> {
+        let size = item.grid_size();
+        if !self.in_bounds(grid_pos, size) {
+            return Err(InventoryFull(item));
+        }
+        let covered = self.items_in_area(grid_pos, size, &[]);
+        if covered.is_empty() {
+            self.items.insert(grid_pos, item);
+            return Ok(());
+        }
+        if let &[other_pos] = covered.as_slice() {
+            let stack = self.items.get_mut(&other_pos).unwrap();
+            if stack.can_stack_with(&item) {
+                let moved = item
+                    .count
+                    .min(stack.item.get_max_stack().saturating_sub(stack.count));
+                stack.count += moved;
+                item.count -= moved;
+                if item.count == 0 {
+                    return Ok(());
+                }
+            }
+        }
+        Err(InventoryFull(item))
+    }
+
+    // Uses up one charge of a consumable. Once none are left the next item of the stack
+    // is started, and the item is removed after the last one.
+    pub fn use_charge(&mut self, grid_pos: (i32, i32)) {
+        let Some(item) = self.items.get_mut(&grid_pos) else {
+            return;
+        };
+        let charges = item.charges.unwrap_or(1).saturating_sub(1);
+        item.charges = Some(charges);
+        if charges > 0 {
+            return;
+        }
+        if item.count > 1 {
+            item.count -= 1;
+            item.charges = item.item.consumable_stats().map(|stats| stats.charges);
+        } else {
+            self.items.remove(&grid_pos);
+        }
+    }
+
+    // Moves half of a stack into a free spot, returning where it went
+    pub fn split_stack(&mut self, grid_pos: (i32, i32)) -> Option<(i32, i32)> {
+        let item = self.items.get(&grid_pos)?;
+        if item.count < 2 {
+            return None;
+        }
+        let mut split = item.clone();
+        split.count = item.count / 2;
+        let split_count = split.count;
+        let split_pos = self.try_insert_without_merging(split).ok()?;
+        self.items.get_mut(&grid_pos).unwrap().count -= split_count;
+        Some(split_pos)
+    }
+
+    // Moves as much of the stack at `from` as fits onto the stack at `to`.
+    // Returns whether anything was merged.
+    pub fn merge_stacks(&mut self, from: (i32, i32), to: (i32, i32)) -> bool {
+        if from == to {
+            return false;
+        }
+        let (Some(source), Some(target)) = (self.items.get(&from), self.items.get(&to)) else {
+            return false;
+        };
+        if !target.can_stack_with(source) {
+            return false;
+        }
+        let moved = source
+            .count
+            .min(target.item.get_max_stack().saturating_sub(target.count));
+        if moved == 0 {
+            return false;
+        }
+        self.items.get_mut(&to).unwrap().count += moved;
+        let source = self.items.get_mut(&from).unwrap();
+        source.count -= moved;
+        if source.count == 0 {
+            self.items.remove(&from);
+        }
+        true
+    }
+
+    // Moves an item to a free spot, merges it onto a matching stack, or swaps it with the
+    // item it is dropped on if each fits in the other's old spot.
+    // Returns the old and new positions of every item that changed place.
+    pub fn move_item(
+        &mut self,
+        from: (i32, i32),
+        to: (i32, i32),
+        rotated: bool,
+    ) -> Vec<((i32, i32), (i32, i32))> {
+        let Some(item) = self.items.get(&from) else {
+            return vec![];
+        };
+        let mut moved_item = item.clone();
+        moved_item.rotated = rotated;
+        let size = moved_item.grid_size();
+        if !self.in_bounds(to, size) {
+            return vec![];
+        }
+
+        let covered = self.items_in_area(to, size, &[from]);
+        if covered.is_empty() {
+            self.items.remove(&from);
+            self.items.insert(to, moved_item);
+            return vec![(from, to)];
+        }
+
+        let &[other_pos] = covered.as_slice() else {
+            return vec![];
+        };
+        if self.items[&other_pos].can_stack_with(item) {
+            self.merge_stacks(from, other_pos);
+            return vec![];
+        }
+        // The dragged item lands where the other one was and the other one takes the dragged
+        // one's old spot, so both footprints have to fit there without covering anything
+        let other_size = self.items[&other_pos].grid_size();
+        let ignored = [from, other_pos];
+        let fits = self.can_place(other_pos, size, &ignored)
+            && self.can_place(from, other_size, &ignored)
+            && !areas_overlap(other_pos, size, from, other_size);
+        if !fits {
+            return vec![];
+        }
+        let other = self.items.remove(&other_pos).unwrap();
+        self.items.remove(&from);
+        self.items.insert(other_pos, moved_item);
+        self.items.insert(from, other);
+        vec![(from, other_pos), (other_pos, from)]
+    }
+
+    // Every item of the given kind that still has rounds to give, with how many
+    pub fn rounds_of(&self, item: &Item) -> impl Iterator<Item = (&(i32, i32), u32)> + '_ {
+        let item = item.clone();
+        self.items
+            .iter()
+            .filter(move |(_, inventory_item)| inventory_item.item == item)
+            .map(|(grid_pos, inventory_item)| (grid_pos, inventory_item.available_rounds()))
+            .filter(|(_, rounds)| *rounds > 0)
+    }
+
+    // Screen position of a cell
+    pub fn get_cell_pos(grid_pos: (i32, i32), scale: f32, top_left: Vector2) -> Vector2 {
+        Vector2::new(
+            top_left.x + (grid_pos.0 as f32 * Self::GRID_SPACING * scale) + (Self::BORDER * scale),
+            top_left.y + (grid_pos.1 as f32 * Self::GRID_SPACING * scale) + (Self::BORDER * scale),
+        )
+    }
+
+    // The cell under a screen position, which may lie outside the grid
+    pub fn get_cell_at(pos: Vector2, scale: f32, top_left: Vector2) -> (i32, i32) {
+        (
+            ((pos.x - top_left.x - Self::BORDER * scale) / Self::GRID_SPACING / scale).round()
+                as i32,
+            ((pos.y - top_left.y - Self::BORDER * scale) / Self::GRID_SPACING / scale).round()
+                as i32,
+        )
+    }
+
+    pub fn get_item_rect(&self, grid_pos: &(i32, i32), scale: f32, top_left: Vector2) -> Rectangle {
+        let item = &self.items[grid_pos];
+        let rotation_offset = if item.rotated {
+            item.get_rotation_offset(scale)
+        } else {
+            0.0
+        };
+        let cell_pos = Self::get_cell_pos(*grid_pos, scale, top_left);
+        Rectangle {
+            x: cell_pos.x + rotation_offset,
+            y: cell_pos.y,
+            width: (item.size.0 as f32 * Self::GRID_SPACING - 1.0) * scale + 0.1,
+            height: (item.size.1 as f32 * Self::GRID_SPACING - 1.0) * scale,
+        }
+    }
+
+    pub fn get_hovered_rect(
+        &self,
+        scale: f32,
+        top_left: Vector2,
+        mouse_pos: Vector2,
+    ) -> Option<(&(i32, i32), &InventoryItem)> {
+        for (grid_pos, item) in &self.items {
+            let mut rect = self.get_item_rect(grid_pos, scale, top_left);
+            if item.rotated {
+                rect = item.rotate_back(&rect, scale);
+            }
+            if rect.check_collision_point_rec(mouse_pos) {
+                return Some((grid_pos, item));
+            }
+        }
+        None
+    }
+
+    pub fn draw_slots(&self, d: &mut RaylibDrawHandle, scale: f32, top_left: Vector2) {
+        let pixel_size = Self::get_pixel_size(self.size);
+        d.draw_rectangle_rec(
+            Rectangle::new(
+                top_left.x,
+                top_left.y,
+                pixel_size.x * scale,
+                pixel_size.y * scale,
+            ),
+            Color::new(40, 40, 40, 235),
+        );
+        for y in 0..self.size.1 {
+            for x in 0..self.size.0 {
+                let cell_pos = Self::get_cell_pos((x, y), scale, top_left);
+                d.draw_rectangle_rec(
+                    Rectangle::new(
+                        cell_pos.x,
+                        cell_pos.y,
+                        (Self::GRID_SPACING - 1.0) * scale,
+                        (Self::GRID_SPACING - 1.0) * scale,
+                    ),
+                    Color::new(80, 80, 80, 235),
+                );
+            }
+        }
+    }
+
+    pub fn draw_items(
+        &self,
+        d: &mut RaylibDrawHandle,
+        assets: &Assets,
+        scale: f32,
+        top_left: Vector2,
+    ) {
+        for (grid_pos, item) in &self.items {
+            let texture = assets.get_texture(&item.item.get_asset_name());
+            let dest_rect = self.get_item_rect(grid_pos, scale, top_left);
+            d.draw_texture_pro(
+                texture,
+                Rectangle {
+                    x: 0.0,
+                    y: 0.0,
+                    width: texture.width() as f32,
+                    height: texture.height() as f32,
+                },
+                dest_rect,
+                Vector2::new(0.0, 0.0),
+                if item.rotated { 90.0 } else { 0.0 },
+                Color::new(255, 255, 255, 255),
+            );
+            if let Some(label) = item.get_label() {
+                let mut rect = dest_rect;
+                if item.rotated {
+                    rect = item.rotate_back(&rect, scale);
+                }
+                let font_size = (5.0 * scale) as i32;
+                d.draw_text(
+                    &label,
+                    (rect.x + rect.width - scale) as i32 - measure_text(&label, font_size),
+                    (rect.y + rect.height - 6.0 * scale) as i32,
+                    font_size,
+                    Color::WHITE,
+                );
+            }
+        }
+    }
+}
+
+// Whether two areas of grid cells share a cell
+fn areas_overlap(
+    a_pos: (i32, i32),
+    a_size: (i32, i32),
+    b_pos: (i32, i32),
+    b_size: (i32, i32),
+) -> bool {
+    a_pos.0 < b_pos.0 + b_size.0
+        && b_pos.0 < a_pos.0 + a_size.0
+        && a_pos.1 < b_pos.1 + b_size.1
+        && b_pos.1 < a_pos.1 + a_size.1
+}
+
+// A container lying in the world, such as a loot crate
+pub struct WorldContainer {
+    pub pos: Vector2,
+    pub container: Container,
+}
+
+impl WorldContainer {
+    // Distance from the player at which the container can be opened
+    pub const OPEN_RADIUS: f32 = 3.5;
+    const SIZE: f32 = 2.5;
+
+    pub fn new(pos: Vector2, container: Container) -> Self {
+        WorldContainer { pos, container }
+    }
+
+    pub fn render(
+        &self,
+        d: &mut RaylibDrawHandle,
+        camera: &Camera2D,
+        assets: &Assets,
+        lighting_renderer: &mut LightingRenderer,
+        thread: &RaylibThread,
+    ) {
+        let screen_pos = camera.to_screen(self.pos);
+        let texture = assets.get_texture("crate.png");
+        let size = Self::SIZE * camera.zoom;
+        let mut d = d.begin_texture_mode(thread, &mut lighting_renderer.target);
+        d.draw_texture_pro(
+            texture,
+            Rectangle {
+                x: 0.0,
+                y: 0.0,
+                width: texture.width as f32,
+                height: texture.height as f32,
+            },
+            Rectangle {
+                x: screen_pos.x - size / 2.0,
+                y: screen_pos.y - size / 2.0,
+                width: size,
+                height: size,
+            },
+            Vector2::zero(),
+            0.0,
+            Color::WHITE,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapon::Caliber;
+
+    fn fill_except(container: &mut Container, free: &[(i32, i32)]) {
+        for y in 0..container.size.1 {
+            for x in 0..container.size.0 {
+                if !free.contains(&(x, y)) {
+                    let ammo = Item::Ammo(Caliber::Pistol).to_inventory_item(false);
+                    container.items.insert((x, y), ammo);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn occupancy_grid_covers_rotated_items() {
+        let mut container = Container::new((8, 4));
+        container
+            .items
+            .insert((2, 1), Item::Pistol.to_inventory_item(true));
+        let grid = container.occupancy_grid();
+        assert_eq!(grid[1][2], Some((2, 1)));
+        assert_eq!(grid[2][2], Some((2, 1)));
+        assert_eq!(grid[1][3], None);
+    }
+
+    #[test]
+    fn try_insert_uses_first_free_spot() {
+        let mut container = Container::new((8, 4));
+        assert_eq!(
+            container
+                .try_insert(Item::Rifle.to_inventory_item(false))
+                .unwrap(),
+            (0, 0)
+        );
+        assert_eq!(
+            container
+                .try_insert(Item::Rifle.to_inventory_item(false))
+                .unwrap(),
+            (4, 0)
+        );
+        assert_eq!(
+            container
+                .try_insert(Item::MedKit.to_inventory_item(false))
+                .unwrap(),
+            (0, 2)
+        );
+    }
+
+    #[test]
+    fn try_insert_rotates_to_fit() {
+        let mut container = Container::new((8, 4));
+        fill_except(&mut container, &[(7, 2), (7, 3)]);
+        let grid_pos = container
+            .try_insert(Item::Pistol.to_inventory_item(false))
+            .unwrap();
+        assert_eq!(grid_pos, (7, 2));
+        assert!(container.items[&grid_pos].rotated);
+    }
+
+    #[test]
+    fn try_insert_hands_back_item_when_full() {
+        let mut container = Container::new((8, 4));
+        fill_except(&mut container, &[(7, 3)]);
+        let InventoryFull(item) = container
+            .try_insert(Item::Pistol.to_inventory_item(false))
+            .unwrap_err();
+        assert_eq!(item.item, Item::Pistol);
+        assert_eq!(container.items.len(), 31);
+    }
+
+    #[test]
+    fn move_item_rejects_overlap() {
+        let mut container = Container::new((8, 4));
+        container
+            .items
+            .insert((0, 0), Item::Rifle.to_inventory_item(false));
+        container
+            .items
+            .insert((4, 0), Item::MedKit.to_inventory_item(false));
+        assert!(container.move_item((4, 0), (2, 1), false).is_empty());
+        assert!(container.items.contains_key(&(4, 0)));
+        assert_eq!(
+            container.move_item((4, 0), (4, 2), false),
+            vec![((4, 0), (4, 2))]
+        );
+        assert!(container.items.contains_key(&(4, 2)));
+        assert!(!container.items.contains_key(&(4, 0)));
+    }
+
+    #[test]
+    fn move_item_swaps_equal_sizes() {
+        let mut container = Container::new((8, 4));
+        container
+            .items
+            .insert((0, 0), Item::Rifle.to_inventory_item(false));
+        container
+            .items
+            .insert((4, 2), Item::Rifle.to_inventory_item(false));
+        container.items.get_mut(&(4, 2)).unwrap().rounds = Some(3);
+
+        let moves = container.move_item((0, 0), (4, 1), false);
+        assert_eq!(moves, vec![((0, 0), (4, 2)), ((4, 2), (0, 0))]);
+        assert_eq!(container.items[&(0, 0)].rounds, Some(3));
+        assert_eq!(container.items[&(4, 2)].rounds, Some(20));
+    }
+
+    #[test]
+    fn move_item_swaps_different_sizes_that_fit() {
+        let mut container = Container::new((8, 4));
+        container
+            .items
+            .insert((0, 0), Item::Pistol.to_inventory_item(false));
+        container
+            .items
+            .insert((4, 0), Item::Bandage.to_inventory_item(false));
+        let moves = container.move_item((0, 0), (4, 0), false);
+        assert_eq!(moves, vec![((0, 0), (4, 0)), ((4, 0), (0, 0))]);
+        assert_eq!(container.items[&(0, 0)].item, Item::Bandage);
+        assert_eq!(container.items[&(4, 0)].item, Item::Pistol);
+    }
+
+    #[test]
+    fn move_item_does_not_swap_when_the_displaced_item_does_not_fit() {
+        // The pistol would land on the rifle next to the bandage's old spot
+        let mut container = Container::new((8, 4));
+        container
+            .items
+            .insert((0, 0), Item::Bandage.to_inventory_item(false));
+        container
+            .items
+            .insert((1, 0), Item::Rifle.to_inventory_item(false));
+        container
+            .items
+            .insert((6, 0), Item::Pistol.to_inventory_item(false));
+        assert!(container.move_item((0, 0), (6, 0), false).is_empty());
+
+        // The pistol would cover the cell the bandage lands on
+        let mut container = Container::new((4, 1));
+        container
+            .items
+            .insert((0, 0), Item::Bandage.to_inventory_item(false));
+        container
+            .items
+            .insert((1, 0), Item::Pistol.to_inventory_item(false));
+        assert!(container.move_item((0, 0), (1, 0), false).is_empty());
+        assert_eq!(container.items[&(0, 0)].item, Item::Bandage);
+        assert_eq!(container.items[&(1, 0)].item, Item::Pistol);
+    }
+
+    #[test]
+    fn try_insert_tops_up_stacks_first() {
+        let mut container = Container::new((8, 4));
+        let mut ammo = Item::Ammo(Caliber::Rifle).to_inventory_item(false);
+        ammo.count = 25;
+        container.items.insert((2, 0), ammo.clone());
+        ammo.count = 10;
+        assert_eq!(container.try_insert(ammo).unwrap(), (0, 0));
+        assert_eq!(container.items[&(2, 0)].count, 30);
+        assert_eq!(container.items[&(0, 0)].count, 5);
+    }
+
+    #[test]
+    fn move_item_merges_stacks() {
+        let mut container = Container::new((8, 4));
+        let mut bandage = Item::Bandage.to_inventory_item(false);
+        bandage.count = 4;
+        container.items.insert((0, 0), bandage.clone());
+        bandage.count = 3;
+        container.items.insert((1, 0), bandage);
+
+        container.move_item((1, 0), (0, 0), false);
+        assert_eq!(container.items[&(0, 0)].count, 5);
+        assert_eq!(container.items[&(1, 0)].count, 2);
+        container.move_item((1, 0), (0, 0), false);
+        assert_eq!(container.items[&(1, 0)].count, 2);
+    }
+
+    #[test]
+    fn split_stack_moves_half_into_free_spot() {
+        let mut container = Container::new((8, 4));
+        container
+            .items
+            .insert((0, 0), Item::Ammo(Caliber::Pistol).to_inventory_item(false));
+        assert_eq!(container.split_stack((0, 0)), Some((1, 0)));
+        assert_eq!(container.items[&(0, 0)].count, 25);
+        assert_eq!(container.items[&(1, 0)].count, 25);
+
+        container
+            .items
+            .insert((2, 0), Item::Bandage.to_inventory_item(false));
+        assert_eq!(container.split_stack((2, 0)), None);
+    }
+
+    #[test]
+    fn use_charge_moves_through_stack() {
+        let mut container = Container::new((8, 4));
+        let mut bandage = Item::Bandage.to_inventory_item(false);
+        bandage.count = 2;
+        container.items.insert((0, 0), bandage);
+        container.use_charge((0, 0));
+        assert_eq!(container.items[&(0, 0)].count, 1);
+        container.use_charge((0, 0));
+        assert!(container.items.is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+use raylib::prelude::*;
+
+use crate::input::{Action, Binding, BindingConflict, InputBindings, CONTROLS_PATH};
+
+// In-game screen listing every action, click one then press a key or button to rebind it
+pub struct ControlsMenu {
+    pub open: bool,
+    waiting_for: Option<Action>,
+    // Shown after a rebind was rejected, until the next one
+    warning: Option<String>,
+}
+
+impl ControlsMenu {
+    const FONT_SIZE: i32 = 20;
+    const ROW_HEIGHT: i32 = 28;
+    const TOP: i32 = 80;
+    const LEFT: i32 = 60;
+
+    pub fn new() -> Self {
+        ControlsMenu {
+            open: false,
+            waiting_for: None,
+            warning: None,
+        }
+    }
+
+    pub fn update(&mut self, rl: &mut RaylibHandle, bindings: &mut InputBindings) {
+        if let Some(action) = self.waiting_for {
+            if let Some(binding) = Binding::pressed_this_frame(rl) {
+                self.waiting_for = None;
+                if let Err(BindingConflict(other)) = bindings.rebind(action, binding) {
+                    self.warning = Some(format!(
+                        "{} is already bound to {}",
+                        binding.name(),
+                        other.display_name()
+                    ));
+                    return;
+                }
+                self.warning = None;
+                if let Err(err) = bindings.save(CONTROLS_PATH) {
+                    eprintln!("Failed to save controls: {}", err);
+                }
+            }
+            return;
+        }
+        if bindings.is_pressed(rl, Action::ToggleControlsMenu) {
+            self.open = !self.open;
+        }
+        if self.open && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            let mouse_pos = rl.get_mouse_position();
+            self.waiting_for = Action::ALL
+                .iter()
+                .enumerate()
+                .find(|(i, _)| Self::row_rect(*i).check_collision_point_rec(mouse_pos))
+                .map(|(_, action)| *action);
+        }
+    }
+
+    fn row_rect(row: usize) -> Rectangle {
+        Rectangle::new(
+            Self::LEFT as f32,
+            (Self::TOP + row as i32 * Self::ROW_HEIGHT) as f32,
+            700.0,
+            Self::ROW_HEIGHT as f32,
+        )
+    }
+
+    pub fn render(&self, d: &mut RaylibDrawHandle, bindings: &InputBindings) {
+        if !self.open {
+            return;
+        }
+        let screen_size = Vector2::new(d.get_screen_width() as f32, d.get_screen_height() as f32);
+        d.draw_rectangle(
+            0,
+            0,
+            screen_size.x as i32,
+            screen_size.y as i32,
+            Color::new(0, 0, 0, 180),
+        );
+        d.draw_text(
+            "Controls (click an action, then press a key or button)",
+            Self::LEFT,
+            Self::TOP - Self::ROW_HEIGHT,
+            Self::FONT_SIZE,
+            Color::WHITE,
+        );
+        let mouse_pos = d.get_mouse_position();
+        for (i, action) in Action::ALL.iter().enumerate() {
+            let rect = Self::row_rect(i);
+            if self.waiting_for == Some(*action) {
+                d.draw_rectangle_rec(rect, Color::new(255, 255, 255, 80));
+            } else if rect.check_collision_point_rec(mouse_pos) {
+                d.draw_rectangle_rec(rect, Color::new(255, 255, 255, 30));
+            }
+            let binding_names = if self.waiting_for == Some(*action) {
+                "...".to_string()
+            } else {
+                bindings
+                    .get(*action)
+                    .iter()
+                    .map(|binding| binding.name())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            };
+            d.draw_text(
+                action.display_name(),
+                rect.x as i32 + 5,
+                rect.y as i32 + 4,
+                Self::FONT_SIZE,
+                Color::WHITE,
+            );
+            d.draw_text(
+                &binding_names,
+                rect.x as i32 + 200,
+                rect.y as i32 + 4,
+                Self::FONT_SIZE,
+                Color::LIGHTGRAY,
+            );
+        }
+        if let Some(warning) = &self.warning {
+            d.draw_text(
+                warning,
+                Self::LEFT,
+                Self::TOP + Action::ALL.len() as i32 * Self::ROW_HEIGHT + 5,
+                Self::FONT_SIZE,
+                Color::ORANGE,
+            );
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use rapier2d::prelude::ColliderHandle;
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{equipment::Equipment, ImprovedCamera};
+
+// Part of the body a bullet struck. Seen from above the torso fills the middle of the
+// character and grazing bullets hit the limbs. The head sits at the front of the torso, so
+// only bullets coming from ahead and through the very centre reach it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HitZone {
+    Head,
+    Torso,
+    Limbs,
+}
+
+impl HitZone {
+    const HEAD_OFFSET: f32 = 0.2;
+    const TORSO_OFFSET: f32 = 0.75;
+    // Cosine of the widest angle, about 45 degrees, between where the character faces and
+    // where a bullet comes from for it to reach the head
+    const HEAD_ARC_COS: f32 = 0.7;
+
+    // `offset` is how far from the centre the bullet passed, 0 through the middle and 1 at the
+    // edge. `facing` is where the character looks and `direction` where the bullet travels.
+    pub fn from_hit(offset: f32, facing: Vector2, direction: Vector2) -> Self {
+        let from_ahead = -direction.dot(facing) >= Self::HEAD_ARC_COS;
+        if offset < Self::HEAD_OFFSET && from_ahead {
+            HitZone::Head
+        } else if offset < Self::TORSO_OFFSET {
+            HitZone::Torso
+        } else {
+            HitZone::Limbs
+        }
+    }
+
+    pub fn damage_multiplier(&self) -> f32 {
+        match self {
+            HitZone::Head => 2.5,
+            HitZone::Torso => 1.0,
+            HitZone::Limbs => 0.6,
+        }
+    }
+}
+
+// Protection given by a piece of armor to one hit zone
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArmorStats {
+    // Bullets need a higher penetration than the class to go through
+    pub class: u32,
+    pub zone: HitZone,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitOutcome {
+    Unarmored,
+    // Went through the armor, losing some of its damage
+    Penetrated,
+    // Stopped by the armor, only the blunt force gets through
+    Stopped,
+}
+
+// A bullet striking a character
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub damage: f32,
+    pub penetration: u32,
+    pub zone: HitZone,
+}
+
+impl Hit {
+    // Damage lost for every armor class a penetrating bullet goes through
+    const PENETRATED_DAMAGE_LOSS_PER_CLASS: f32 = 0.1;
+    const BLUNT_DAMAGE: f32 = 0.2;
+    // Health lost per second for every point of damage an open wound took
+    const BLEED_RATE: f32 = 0.03;
+
+    // Works out how much of the hit gets through the armor covering its zone
+    pub fn resolve(&self, equipment: &Equipment) -> Wound {
+        let damage = self.damage * self.zone.damage_multiplier();
+        let (outcome, damage) = match equipment.armor_for(self.zone) {
+            None => (HitOutcome::Unarmored, damage),
+            Some(armor) if self.penetration > armor.class => (
+                HitOutcome::Penetrated,
+                damage
+                    * (1.0 - Self::PENETRATED_DAMAGE_LOSS_PER_CLASS * armor.class as f32).max(0.0),
+            ),
+            Some(_) => (HitOutcome::Stopped, damage * Self::BLUNT_DAMAGE),
+        };
+        let bleeding = match outcome {
+            HitOutcome::Stopped => 0.0,
+            _ => damage * Self::BLEED_RATE,
+        };
+        Wound {
+            outcome,
+            damage,
+            bleeding,
+        }
+    }
+}
+
+// What a hit did to a character
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wound {
+    pub outcome: HitOutcome,
+    pub damage: f32,
+    // Health lost per second until the wound is bandaged
+    pub bleeding: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DamageTarget {
+    Player,
+    Dummy,
+}
+
+// Emitted every time a character is hit, kept for the frame it happened in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DamageEvent {
+    pub target: DamageTarget,
+    // Collider of the character that was hit
+    pub collider: ColliderHandle,
+    pub pos: Vector2,
+    // Which way the bullet was flying, normalized
+    pub direction: Vector2,
+    pub zone: HitZone,
+    pub wound: Wound,
+}
+
+// Damage number floating up from where a hit landed
+pub struct HitMarker {
+    event: DamageEvent,
+    age: f32,
+}
+
+impl HitMarker {
+    const LIFETIME: f32 = 1.0;
+    // World units the number rises over its lifetime
+    const RISE: f32 = 1.5;
+
+    pub fn new(event: DamageEvent) -> Self {
+        HitMarker { event, age: 0.0 }
+    }
+
+    // Ages the marker, returning whether it should still be shown
+    pub fn update(&mut self, dt: f32) -> bool {
+        self.age += dt;
+        self.age < Self::LIFETIME
+    }
+
+    pub fn render(&self, d: &mut impl RaylibDraw, camera: &Camera2D) {
+        let fade = 1.0 - self.age / Self::LIFETIME;
+        let pos = camera
+            .to_screen(self.event.pos - Vector2::new(0.0, Self::RISE * self.age / Self::LIFETIME));
+        let color = match self.event.wound.outcome {
+            HitOutcome::Stopped => Color::LIGHTGRAY,
+            _ if self.event.zone == HitZone::Head => Color::ORANGE,
+            _ => Color::RED,
+        };
+        d.draw_text(
+            &format!("{:.0}", self.event.wound.damage),
+            pos.x as i32,
+            pos.y as i32,
+            (camera.zoom * 0.8) as i32,
+            color.fade(fade),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Item;
+
+    fn hit(zone: HitZone, penetration: u32) -> Hit {
+        Hit {
+            damage: 20.0,
+            penetration,
+            zone,
+        }
+    }
+
+    #[test]
+    fn zones_scale_damage() {
+        let equipment = Equipment::default();
+        let head = hit(HitZone::Head, 0).resolve(&equipment);
+        let limbs = hit(HitZone::Limbs, 0).resolve(&equipment);
+        assert_eq!(head.outcome, HitOutcome::Unarmored);
+        assert_eq!(head.damage, 50.0);
+        assert!((limbs.damage - 12.0).abs() < 1e-4);
+        assert!(limbs.bleeding > 0.0);
+    }
+
+    #[test]
+    fn armor_stops_weaker_rounds() {
+        let mut equipment = Equipment::default();
+        equipment.body_armor = Some(Item::BodyArmor.to_inventory_item(false));
+        let class = equipment.armor_for(HitZone::Torso).unwrap().class;
+
+        let stopped = hit(HitZone::Torso, class).resolve(&equipment);
+        assert_eq!(stopped.outcome, HitOutcome::Stopped);
+        assert_eq!(stopped.bleeding, 0.0);
+        assert!(stopped.damage < 20.0);
+
+        let penetrated = hit(HitZone::Torso, class + 1).resolve(&equipment);
+        assert_eq!(penetrated.outcome, HitOutcome::Penetrated);
+        assert!(penetrated.damage > stopped.damage && penetrated.damage < 20.0);
+
+        // Body armor does nothing for the head
+        let head = hit(HitZone::Head, 0).resolve(&equipment);
+        assert_eq!(head.outcome, HitOutcome::Unarmored);
+    }
+
+    #[test]
+    fn hit_zone_from_offset_and_direction() {
+        let facing = Vector2::new(1.0, 0.0);
+        let from_ahead = Vector2::new(-1.0, 0.0);
+        assert_eq!(HitZone::from_hit(0.0, facing, from_ahead), HitZone::Head);
+        assert_eq!(HitZone::from_hit(0.5, facing, from_ahead), HitZone::Torso);
+        assert_eq!(HitZone::from_hit(1.0, facing, from_ahead), HitZone::Limbs);
+        // Through the centre from behind or the side hits the torso
+        assert_eq!(HitZone::from_hit(0.0, facing, facing), HitZone::Torso);
+        let from_side = Vector2::new(0.0, 1.0);
+        assert_eq!(HitZone::from_hit(0.0, facing, from_side), HitZone::Torso);
+    }
+}
@@ -1,9 +1,15 @@
 use raylib::prelude::*;
 
+use crate::clock::WorldClock;
+use crate::input::{Action, InputBindings};
+
 // Stores debug info about the world
 pub struct DebugInfo {
     pub info: Vec<String>,
     pub debug: bool,
+    toggle_key_name: String,
+    // Time of day typed in so far, while setting the time
+    time_prompt: Option<String>,
 }
 
 impl DebugInfo {
@@ -11,15 +17,57 @@ impl DebugInfo {
         DebugInfo {
             info: vec![],
             debug: false,
+            toggle_key_name: String::new(),
+            time_prompt: None,
         }
     }
-    pub fn update(&mut self, rl: &mut RaylibHandle) {
+    pub fn update(&mut self, rl: &mut RaylibHandle, bindings: &InputBindings) {
         self.info = vec![];
-        if rl.is_key_pressed(KeyboardKey::KEY_F1) {
+        if bindings.is_pressed(rl, Action::ToggleDebug) {
             self.debug = !self.debug;
         }
+        self.toggle_key_name = bindings
+            .get(Action::ToggleDebug)
+            .first()
+            .map(|binding| binding.name())
+            .unwrap_or_default();
+        self.info.push(format!(
+            "(Press {} to shrink debug info)",
+            self.toggle_key_name
+        ));
+    }
+    // Lets the time of day be typed in as HH:MM, returns the hour once Enter is pressed
+    pub fn prompt_time(&mut self, rl: &mut RaylibHandle, bindings: &InputBindings) -> Option<f32> {
+        if !self.debug {
+            self.time_prompt = None;
+            return None;
+        }
+        if bindings.is_pressed(rl, Action::SetTime) {
+            self.time_prompt = match self.time_prompt {
+                Some(_) => None,
+                None => Some(String::new()),
+            };
+        }
+        let text = self.time_prompt.as_mut()?;
+        while let Some(c) = rl.get_char_pressed() {
+            if c.is_ascii_digit() || c == ':' {
+                text.push(c);
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            text.pop();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            let time = WorldClock::parse_time(text);
+            self.time_prompt = None;
+            return time;
+        }
         self.info
-            .push("(Press F1 to shrink debug info)".to_string());
+            .push(format!("Set time (HH:MM, Enter to apply): {}_", text));
+        None
+    }
+    pub fn is_prompting(&self) -> bool {
+        self.time_prompt.is_some()
     }
     pub fn add(&mut self, info: String) {
         self.info.push(info)
@@ -38,7 +86,7 @@ impl DebugInfo {
             }
         } else {
             d.draw_text(
-                "(Press F1 to expand debug menu)",
+                &format!("(Press {} to expand debug menu)", self.toggle_key_name),
                 font_size / 5,
                 font_size / 10,
                 font_size,
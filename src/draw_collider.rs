@@ -45,6 +45,26 @@ pub fn draw_shape(
             camera.to_screen(points.2),
             color,
         );
+    } else if let Some(collider) = isometry_shape.1.as_convex_polygon() {
+        let mut d = d.begin_texture_mode(thread, target);
+        // Reversed so the fan winds counter clockwise on screen, where y points down
+        let points = collider
+            .points()
+            .iter()
+            .rev()
+            .map(|point| camera.to_screen((isometry_shape.0 * point).coords.to_raylib_vector2()))
+            .collect::<Vec<Vector2>>();
+        d.draw_triangle_fan(&points, color);
+    } else if let Some(collider) = isometry_shape.1.as_polyline() {
+        let mut d = d.begin_texture_mode(thread, target);
+        for segment in collider.segments() {
+            d.draw_line_ex(
+                camera.to_screen((isometry_shape.0 * segment.a).coords.to_raylib_vector2()),
+                camera.to_screen((isometry_shape.0 * segment.b).coords.to_raylib_vector2()),
+                0.1 * camera.zoom,
+                color,
+            );
+        }
     } else if let Some(collider) = isometry_shape.1.as_compound() {
         for (mut isometery, shape) in collider.shapes() {
             isometery.translation.vector += isometry_shape.0.translation.vector;
@@ -0,0 +1,271 @@
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    assets::Assets,
+    damage::{ArmorStats, HitZone},
+    inventory::{InventoryItem, Item},
+};
+
+// A place on the character where an item is worn or carried, shown in the paper doll
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Primary,
+    Secondary,
+    Head,
+    BodyArmor,
+    Rig,
+    Backpack,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 6] = [
+        EquipmentSlot::Head,
+        EquipmentSlot::BodyArmor,
+        EquipmentSlot::Primary,
+        EquipmentSlot::Backpack,
+        EquipmentSlot::Rig,
+        EquipmentSlot::Secondary,
+    ];
+
+    pub fn get_display_name(&self) -> &'static str {
+        match self {
+            EquipmentSlot::Primary => "Primary",
+            EquipmentSlot::Secondary => "Secondary",
+            EquipmentSlot::Head => "Head",
+            EquipmentSlot::BodyArmor => "Armor",
+            EquipmentSlot::Rig => "Rig",
+            EquipmentSlot::Backpack => "Backpack",
+        }
+    }
+
+    // Column and row in the paper doll, the middle column shows the character
+    pub fn get_doll_cell(&self) -> (i32, i32) {
+        match self {
+            EquipmentSlot::Head => (0, 0),
+            EquipmentSlot::BodyArmor => (0, 1),
+            EquipmentSlot::Primary => (0, 2),
+            EquipmentSlot::Backpack => (2, 0),
+            EquipmentSlot::Rig => (2, 1),
+            EquipmentSlot::Secondary => (2, 2),
+        }
+    }
+
+    pub fn is_weapon(&self) -> bool {
+        matches!(self, EquipmentSlot::Primary | EquipmentSlot::Secondary)
+    }
+}
+
+// How a worn item changes the character
+#[derive(Clone, Copy, Debug)]
+pub struct EquipmentStats {
+    pub armor: Option<ArmorStats>,
+    pub speed_multiplier: f32,
+}
+
+impl Default for EquipmentStats {
+    fn default() -> Self {
+        EquipmentStats {
+            armor: None,
+            speed_multiplier: 1.0,
+        }
+    }
+}
+
+impl Item {
+    pub fn get_equipment_slot(&self) -> Option<EquipmentSlot> {
+        match self {
+            Item::Rifle => Some(EquipmentSlot::Primary),
+            Item::Pistol => Some(EquipmentSlot::Secondary),
+            Item::Helmet => Some(EquipmentSlot::Head),
+            Item::BodyArmor => Some(EquipmentSlot::BodyArmor),
+            Item::Rig => Some(EquipmentSlot::Rig),
+            Item::Backpack => Some(EquipmentSlot::Backpack),
+            _ => None,
+        }
+    }
+
+    pub fn equipment_stats(&self) -> EquipmentStats {
+        match self {
+            Item::Rifle => EquipmentStats {
+                speed_multiplier: 0.95,
+                ..Default::default()
+            },
+            Item::Helmet => EquipmentStats {
+                armor: Some(ArmorStats {
+                    class: 2,
+                    zone: HitZone::Head,
+                }),
+                speed_multiplier: 1.0,
+            },
+            Item::BodyArmor => EquipmentStats {
+                armor: Some(ArmorStats {
+                    class: 3,
+                    zone: HitZone::Torso,
+                }),
+                speed_multiplier: 0.9,
+            },
+            Item::Rig => EquipmentStats {
+                speed_multiplier: 0.97,
+                ..Default::default()
+            },
+            Item::Backpack => EquipmentStats {
+                speed_multiplier: 0.95,
+                ..Default::default()
+            },
+            _ => EquipmentStats::default(),
+        }
+    }
+
+    // Sprite layered over the character while the item is worn
+    pub fn get_worn_asset_name(&self) -> Option<String> {
+        match self {
+            Item::Helmet => Some("worn/helmet.png".to_string()),
+            Item::BodyArmor => Some("worn/body_armor.png".to_string()),
+            Item::Rig => Some("worn/rig.png".to_string()),
+            Item::Backpack => Some("worn/backpack.png".to_string()),
+            _ => None,
+        }
+    }
+
+    // Character sprite while holding the item
+    pub fn get_held_asset_name(&self) -> Option<String> {
+        match self {
+            Item::Rifle => Some("rifle.png".to_string()),
+            Item::Pistol => Some("player_pistol.png".to_string()),
+            _ => None,
+        }
+    }
+}
+
+// Items worn or carried outside the grids. Backpacks and rigs keep their own grid.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Equipment {
+    pub primary: Option<InventoryItem>,
+    pub secondary: Option<InventoryItem>,
+    pub head: Option<InventoryItem>,
+    pub body_armor: Option<InventoryItem>,
+    pub rig: Option<InventoryItem>,
+    pub backpack: Option<InventoryItem>,
+}
+
+impl Equipment {
+    pub fn slot_mut(&mut self, slot: EquipmentSlot) -> &mut Option<InventoryItem> {
+        match slot {
+            EquipmentSlot::Primary => &mut self.primary,
+            EquipmentSlot::Secondary => &mut self.secondary,
+            EquipmentSlot::Head => &mut self.head,
+            EquipmentSlot::BodyArmor => &mut self.body_armor,
+            EquipmentSlot::Rig => &mut self.rig,
+            EquipmentSlot::Backpack => &mut self.backpack,
+        }
+    }
+
+    pub fn get(&self, slot: EquipmentSlot) -> Option<&InventoryItem> {
+        match slot {
+            EquipmentSlot::Primary => self.primary.as_ref(),
+            EquipmentSlot::Secondary => self.secondary.as_ref(),
+            EquipmentSlot::Head => self.head.as_ref(),
+            EquipmentSlot::BodyArmor => self.body_armor.as_ref(),
+            EquipmentSlot::Rig => self.rig.as_ref(),
+            EquipmentSlot::Backpack => self.backpack.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, slot: EquipmentSlot) -> Option<&mut InventoryItem> {
+        self.slot_mut(slot).as_mut()
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &InventoryItem> + '_ {
+        EquipmentSlot::ALL
+            .into_iter()
+            .filter_map(|slot| self.get(slot))
+    }
+
+    // The strongest armor covering the zone
+    pub fn armor_for(&self, zone: HitZone) -> Option<ArmorStats> {
+        self.items()
+            .filter_map(|item| item.item.equipment_stats().armor)
+            .filter(|armor| armor.zone == zone)
+            .max_by_key(|armor| armor.class)
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.items()
+            .map(|item| item.item.equipment_stats().speed_multiplier)
+            .product()
+    }
+
+    // Character sprite first, then everything worn over it, bottom layer first
+    pub fn get_sprite_layers(&self, active_weapon: Option<EquipmentSlot>) -> Vec<String> {
+        let body = active_weapon
+            .and_then(|slot| self.get(slot))
+            .and_then(|item| item.item.get_held_asset_name())
+            .unwrap_or_else(|| "player_unarmed.png".to_string());
+        let worn = [
+            EquipmentSlot::Backpack,
+            EquipmentSlot::BodyArmor,
+            EquipmentSlot::Rig,
+            EquipmentSlot::Head,
+        ]
+        .into_iter()
+        .filter_map(|slot| self.get(slot)?.item.get_worn_asset_name());
+        std::iter::once(body).chain(worn).collect()
+    }
+
+    // Draws the character with everything it wears, the way `draw_texture_pro` would
+    pub fn draw_character(
+        &self,
+        d: &mut impl RaylibDraw,
+        assets: &Assets,
+        active_weapon: Option<EquipmentSlot>,
+        dest: Rectangle,
+        origin: Vector2,
+        rotation: f32,
+    ) {
+        for layer in self.get_sprite_layers(active_weapon) {
+            let texture = assets.get_texture(&layer);
+            d.draw_texture_pro(
+                texture,
+                Rectangle::new(0.0, 0.0, texture.width() as f32, texture.height() as f32),
+                dest,
+                origin,
+                rotation,
+                Color::WHITE,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn armor_covers_its_zone() {
+        let mut equipment = Equipment::default();
+        assert_eq!(equipment.armor_for(HitZone::Torso), None);
+        equipment.body_armor = Some(Item::BodyArmor.to_inventory_item(false));
+        equipment.head = Some(Item::Helmet.to_inventory_item(false));
+        assert_eq!(equipment.armor_for(HitZone::Head).unwrap().class, 2);
+        assert_eq!(equipment.armor_for(HitZone::Torso).unwrap().class, 3);
+        assert_eq!(equipment.armor_for(HitZone::Limbs), None);
+        assert!((equipment.speed_multiplier() - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn sprite_follows_active_weapon() {
+        let mut equipment = Equipment::default();
+        equipment.secondary = Some(Item::Pistol.to_inventory_item(false));
+        equipment.head = Some(Item::Helmet.to_inventory_item(false));
+        assert_eq!(
+            equipment.get_sprite_layers(Some(EquipmentSlot::Secondary)),
+            vec!["player_pistol.png", "worn/helmet.png"]
+        );
+        assert_eq!(
+            equipment.get_sprite_layers(Some(EquipmentSlot::Primary))[0],
+            "player_unarmed.png"
+        );
+    }
+}
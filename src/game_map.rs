@@ -1,16 +1,478 @@
 use raylib::prelude::*;
-use tiled::Map;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use tiled::{
+    ChunkData, LayerTile, LayerType, Map, ObjectData, ObjectShape, PropertyValue, TileLayer,
+};
 
-use crate::{assets::Assets, ImprovedCamera};
+use crate::{
+    assets::Assets,
+    chunks::{chunk_at, chunk_rect, ChunkPos, ChunkWindow},
+    collision_world::ShapeArgs,
+    ground::{Ground, GroundMap},
+    terrain::{Terrain, TerrainGenerator},
+    ImprovedCamera,
+};
 
+// A static collider authored in Tiled, already converted to world units
+#[derive(Clone)]
+pub struct MapCollider {
+    pub pos: Vector2,
+    pub shape: ShapeArgs,
+}
+
+impl MapCollider {
+    // Bounding box, in world units
+    pub fn bounds(&self) -> Rectangle {
+        let (min, max) = match &self.shape {
+            ShapeArgs::Cuboid { half_extents } => (-*half_extents, *half_extents),
+            ShapeArgs::Ball { radius } => (
+                Vector2::new(-radius, -radius),
+                Vector2::new(*radius, *radius),
+            ),
+            ShapeArgs::Triangle { points } => Self::points_bounds(&[points.0, points.1, points.2]),
+            ShapeArgs::Polygon { points } | ShapeArgs::Polyline { points } => {
+                Self::points_bounds(points)
+            }
+        };
+        Rectangle::new(
+            self.pos.x + min.x,
+            self.pos.y + min.y,
+            max.x - min.x,
+            max.y - min.y,
+        )
+    }
+
+    fn points_bounds(points: &[Vector2]) -> (Vector2, Vector2) {
+        points.iter().fold(
+            (
+                Vector2::new(f32::MAX, f32::MAX),
+                Vector2::new(f32::MIN, f32::MIN),
+            ),
+            |(min, max), point| {
+                (
+                    Vector2::new(min.x.min(point.x), min.y.min(point.y)),
+                    Vector2::new(max.x.max(point.x), max.y.max(point.y)),
+                )
+            },
+        )
+    }
+}
+
+// A tile of a loaded chunk, ready to draw
+struct TileDraw {
+    texture: String,
+    source_rect: Rectangle,
+    // In world units
+    dest_rect: Rectangle,
+    rotation: f32,
+    tint: Color,
+}
+
+// Tiles are only kept for the chunks around the camera, loaded as it moves with `stream`
 pub struct GameMap {
     map: Map,
+    colliders: Vec<MapCollider>,
+    window: ChunkWindow,
+    // Tiles of every loaded chunk, one list per tile layer
+    tile_chunks: HashMap<ChunkPos, Vec<Vec<TileDraw>>>,
 }
 
 impl GameMap {
+    // World units per map pixel
+    pub const SCALE: f32 = 0.1;
+    // Size of generated maps, in tiles
+    pub const GENERATED_SIZE: (u32, u32) = (48, 48);
+    // Tileset of generated maps, relative to the maps folder they are generated into
+    const GENERATED_TILESET: &str = "../tilesets/ground_tiles.tsx";
+    const ELLIPSE_SEGMENTS: usize = 16;
+
     pub fn load_map(path: &str) -> Self {
+        Self::from_map(tiled::Loader::new().load_tmx_map(path).unwrap())
+    }
+
+    fn from_map(map: Map) -> Self {
+        let mut colliders = vec![];
+        for layer in map.layers() {
+            Self::collect_layer_colliders(&map, &layer, Vector2::zero(), &mut colliders);
+        }
         GameMap {
-            map: tiled::Loader::new().load_tmx_map(path).unwrap(),
+            map,
+            colliders,
+            window: ChunkWindow::new(),
+            tile_chunks: HashMap::new(),
+        }
+    }
+
+    // Generates a map from the seed, the same seed always gives the same map. Nothing is
+    // written to disk, the map is loaded as if it were in the maps folder so its tileset is
+    // found from there.
+    pub fn generate(seed: u64) -> Result<Self, tiled::Error> {
+        let tmx = Self::terrain(seed).to_tmx(Self::GENERATED_TILESET);
+        Ok(Self::from_map(Self::load_tmx(
+            &Self::generated_path(seed),
+            &tmx,
+        )?))
+    }
+
+    // Loads tmx text as if it were the map file at `path`, anything it refers to is read from disk
+    fn load_tmx(path: &str, tmx: &str) -> Result<Map, tiled::Error> {
+        let reader = |file: &Path| -> std::io::Result<Box<dyn Read>> {
+            if file == Path::new(path) {
+                Ok(Box::new(Cursor::new(tmx.as_bytes().to_vec())))
+            } else {
+                Ok(Box::new(File::open(file)?))
+            }
+        };
+        tiled::Loader::with_reader(reader).load_tmx_map(path)
+    }
+
+    // Writes the map generated from the seed next to the hand made ones, so it can be opened
+    // in Tiled. Returns where it was written.
+    pub fn export_generated(seed: u64) -> std::io::Result<String> {
+        let path = Self::generated_path(seed);
+        Self::terrain(seed).export(&path, Self::GENERATED_TILESET)?;
+        Ok(path)
+    }
+
+    fn generated_path(seed: u64) -> String {
+        format!("maps/generated_{}.tmx", seed)
+    }
+
+    fn terrain(seed: u64) -> Terrain {
+        let (width, height) = Self::GENERATED_SIZE;
+        TerrainGenerator::new(seed, width, height).generate()
+    }
+
+    // Centre of the walkable tile closest to the middle of the map
+    pub fn spawn_point(&self) -> Vector2 {
+        let bounds = self.bounds();
+        let center = Vector2::new(bounds.width / 2.0, bounds.height / 2.0);
+        let tile_size =
+            Vector2::new(self.map.tile_width as f32, self.map.tile_height as f32) * Self::SCALE;
+        let unwalkable = self.unwalkable_areas();
+        (0..self.map.height)
+            .flat_map(|y| (0..self.map.width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                Vector2::new(
+                    (x as f32 + 0.5) * tile_size.x,
+                    (y as f32 + 0.5) * tile_size.y,
+                )
+            })
+            .filter(|pos| {
+                !unwalkable
+                    .iter()
+                    .any(|rect| rect.check_collision_point_rec(*pos))
+            })
+            .min_by(|a, b| a.distance_to(center).total_cmp(&b.distance_to(center)))
+            .unwrap_or(center)
+    }
+
+    pub fn colliders(&self) -> &[MapCollider] {
+        &self.colliders
+    }
+
+    // Area covered by the map's tile grid, in world units
+    pub fn bounds(&self) -> Rectangle {
+        Rectangle::new(
+            0.0,
+            0.0,
+            (self.map.width * self.map.tile_width) as f32 * Self::SCALE,
+            (self.map.height * self.map.tile_height) as f32 * Self::SCALE,
+        )
+    }
+
+    // Tiles that can't be walked on, such as water. A tile layer is made unwalkable in Tiled
+    // with a `walkable` bool property set to false, and only counts where no layer above it
+    // has a tile.
+    pub fn unwalkable_areas(&self) -> Vec<Rectangle> {
+        // Keyed by grid position and layer offset in map pixels, so offset layers don't mix
+        let mut walkable = HashMap::new();
+        for layer in self.map.layers() {
+            Self::for_each_tile(&layer, Vector2::zero(), &mut |layer, offset, (x, y), _| {
+                let layer_walkable = !matches!(
+                    layer.properties.get("walkable"),
+                    Some(PropertyValue::BoolValue(false))
+                );
+                walkable.insert((x, y, offset.x as i32, offset.y as i32), layer_walkable);
+            });
+        }
+        let tile_size = Vector2::new(self.map.tile_width as f32, self.map.tile_height as f32);
+        walkable
+            .into_iter()
+            .filter(|(_, walkable)| !walkable)
+            .map(|((x, y, offset_x, offset_y), _)| {
+                Rectangle::new(
+                    (offset_x as f32 + x as f32 * tile_size.x) * Self::SCALE,
+                    (offset_y as f32 + y as f32 * tile_size.y) * Self::SCALE,
+                    tile_size.x * Self::SCALE,
+                    tile_size.y * Self::SCALE,
+                )
+            })
+            .collect()
+    }
+
+    // The ground of every tile, from the properties of the topmost layer with a tile there
+    // and of that tile. Tiles of offset layers count for the grid cell their centre is in.
+    pub fn ground(&self) -> GroundMap {
+        let tile_size = Vector2::new(self.map.tile_width as f32, self.map.tile_height as f32);
+        let mut ground_map = GroundMap::new(tile_size * Self::SCALE);
+        for layer in self.map.layers() {
+            Self::for_each_tile(
+                &layer,
+                Vector2::zero(),
+                &mut |layer, offset, (x, y), tile| {
+                    let mut ground = Ground::default();
+                    ground.apply_properties(&layer.properties);
+                    if let Some(tile) = tile.get_tile() {
+                        ground.apply_properties(&tile.properties);
+                    }
+                    let cell = (
+                        ((offset.x / tile_size.x) + x as f32 + 0.5).floor() as i32,
+                        ((offset.y / tile_size.y) + y as f32 + 0.5).floor() as i32,
+                    );
+                    ground_map.set(cell, ground);
+                },
+            );
+        }
+        ground_map
+    }
+
+    // Calls `f` with every tile of a tile layer, or of the tile layers in a group from the
+    // bottom up. It is given the layer the tile is in, its offset in map pixels, and the
+    // tile's grid position in the layer.
+    fn for_each_tile<'map>(
+        layer: &tiled::Layer<'map>,
+        offset: Vector2,
+        f: &mut impl FnMut(&tiled::Layer<'map>, Vector2, (i32, i32), LayerTile<'map>),
+    ) {
+        let offset = offset + Vector2::new(layer.offset_x, layer.offset_y);
+        match layer.layer_type() {
+            LayerType::Tiles(TileLayer::Finite(tile_layer)) => {
+                for y in 0..tile_layer.height() as i32 {
+                    for x in 0..tile_layer.width() as i32 {
+                        if let Some(tile) = tile_layer.get_tile(x, y) {
+                            f(layer, offset, (x, y), tile);
+                        }
+                    }
+                }
+            }
+            LayerType::Tiles(TileLayer::Infinite(tile_layer)) => {
+                let chunk_size = (ChunkData::WIDTH as i32, ChunkData::HEIGHT as i32);
+                for ((chunk_x, chunk_y), chunk) in tile_layer.chunks() {
+                    for y in 0..chunk_size.1 {
+                        for x in 0..chunk_size.0 {
+                            if let Some(tile) = chunk.get_tile(x, y) {
+                                let grid_pos =
+                                    (chunk_x * chunk_size.0 + x, chunk_y * chunk_size.1 + y);
+                                f(layer, offset, grid_pos, tile);
+                            }
+                        }
+                    }
+                }
+            }
+            LayerType::Group(group_layer) => {
+                for child in group_layer.layers() {
+                    Self::for_each_tile(&child, offset, f);
+                }
+            }
+            LayerType::Objects(_) | LayerType::Image(_) => (),
+        }
+    }
+
+    // Walks wall objects, tile layers with per-tile collision shapes, finite or infinite,
+    // and group layers. `offset` is in map pixels.
+    fn collect_layer_colliders(
+        map: &Map,
+        layer: &tiled::Layer,
+        offset: Vector2,
+        colliders: &mut Vec<MapCollider>,
+    ) {
+        let layer_offset = offset + Vector2::new(layer.offset_x, layer.offset_y);
+        match layer.layer_type() {
+            LayerType::Objects(object_layer) => {
+                for object in object_layer.objects() {
+                    if Self::is_wall(layer, &object) {
+                        colliders.extend(Self::object_collider(&object, layer_offset));
+                    }
+                }
+            }
+            LayerType::Tiles(_) => {
+                Self::for_each_tile(layer, offset, &mut |_, offset, (x, y), tile| {
+                    let Some(tile) = tile.get_tile() else {
+                        return;
+                    };
+                    let Some(collision) = &tile.collision else {
+                        return;
+                    };
+                    let tile_origin = offset
+                        + Vector2::new(
+                            (x * map.tile_width as i32) as f32,
+                            (y * map.tile_height as i32) as f32,
+                        );
+                    for object in collision.object_data() {
+                        colliders.extend(Self::object_collider(object, tile_origin));
+                    }
+                });
+            }
+            LayerType::Group(group_layer) => {
+                for child in group_layer.layers() {
+                    Self::collect_layer_colliders(map, &child, layer_offset, colliders);
+                }
+            }
+            LayerType::Image(_) => (),
+        }
+    }
+
+    // Objects in the Walls layer block movement, others only with a `collision` bool property.
+    // The property can also be set on a whole layer, or false to let an object be walked through.
+    fn is_wall(layer: &tiled::Layer, object: &ObjectData) -> bool {
+        match object
+            .properties
+            .get("collision")
+            .or_else(|| layer.properties.get("collision"))
+        {
+            Some(PropertyValue::BoolValue(collision)) => *collision,
+            _ => layer.name == "Walls",
+        }
+    }
+
+    // Converts a Tiled object to a collider. Tiled rotates objects clockwise around their
+    // top left corner, so rotated shapes are turned into polygons around that point.
+    fn object_collider(object: &ObjectData, offset: Vector2) -> Option<MapCollider> {
+        let origin = (offset + Vector2::new(object.x, object.y)) * Self::SCALE;
+        let rotation = object.rotation.to_radians();
+        let to_world = |point: Vector2| point.rotated(rotation) * Self::SCALE;
+        let shape = match &object.shape {
+            ObjectShape::Rect { width, height } => {
+                let half_extents = Vector2::new(*width, *height) / 2.0;
+                if rotation == 0.0 {
+                    return Some(MapCollider {
+                        pos: origin + half_extents * Self::SCALE,
+                        shape: ShapeArgs::Cuboid {
+                            half_extents: half_extents * Self::SCALE,
+                        },
+                    });
+                }
+                ShapeArgs::Polygon {
+                    points: vec![
+                        to_world(Vector2::new(0.0, 0.0)),
+                        to_world(Vector2::new(*width, 0.0)),
+                        to_world(Vector2::new(*width, *height)),
+                        to_world(Vector2::new(0.0, *height)),
+                    ],
+                }
+            }
+            ObjectShape::Ellipse { width, height } => {
+                let radii = Vector2::new(*width, *height) / 2.0;
+                if rotation == 0.0 && width == height {
+                    return Some(MapCollider {
+                        pos: origin + radii * Self::SCALE,
+                        shape: ShapeArgs::Ball {
+                            radius: radii.x * Self::SCALE,
+                        },
+                    });
+                }
+                ShapeArgs::Polygon {
+                    points: (0..Self::ELLIPSE_SEGMENTS)
+                        .map(|i| {
+                            let angle =
+                                i as f32 / Self::ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                            to_world(Vector2::new(
+                                radii.x + radii.x * angle.cos(),
+                                radii.y + radii.y * angle.sin(),
+                            ))
+                        })
+                        .collect(),
+                }
+            }
+            ObjectShape::Polygon { points } => ShapeArgs::Polygon {
+                points: points
+                    .iter()
+                    .map(|(x, y)| to_world(Vector2::new(*x, *y)))
+                    .collect(),
+            },
+            ObjectShape::Polyline { points } => ShapeArgs::Polyline {
+                points: points
+                    .iter()
+                    .map(|(x, y)| to_world(Vector2::new(*x, *y)))
+                    .collect(),
+            },
+            _ => return None,
+        };
+        Some(MapCollider { pos: origin, shape })
+    }
+
+    // Loads the tiles of the chunks around `center` and drops the ones that went out of range
+    pub fn stream(&mut self, center: Vector2) {
+        let (loaded, unloaded) = self.window.update(center);
+        for chunk in unloaded {
+            self.tile_chunks.remove(&chunk);
+        }
+        for chunk in loaded {
+            let mut layers = vec![];
+            for layer in self.map.layers() {
+                self.load_layer_tiles(chunk, &layer, Vector2::zero(), 1.0, &mut layers);
+            }
+            self.tile_chunks.insert(chunk, layers);
+        }
+    }
+
+    // Collects the tiles of a chunk, one list per tile layer. Layers are walked in the same
+    // order as when rendering, so the lists line up with the layers drawing them.
+    fn load_layer_tiles(
+        &self,
+        chunk: ChunkPos,
+        layer: &tiled::Layer,
+        offset: Vector2,
+        opacity: f32,
+        layers: &mut Vec<Vec<TileDraw>>,
+    ) {
+        if !layer.visible {
+            return;
+        }
+        let offset = offset + Vector2::new(layer.offset_x, layer.offset_y);
+        let opacity = opacity * layer.opacity;
+        match layer.layer_type() {
+            LayerType::Tiles(tile_layer) => {
+                // Every tile belongs to the chunk its grid cell's top left corner is in
+                let tile_size =
+                    Vector2::new(self.map.tile_width as f32, self.map.tile_height as f32);
+                let rect = chunk_rect(chunk);
+                let first = |start: f32, offset: f32, size: f32| {
+                    ((start / Self::SCALE - offset) / size).floor() as i32
+                };
+                let last = |end: f32, offset: f32, size: f32| {
+                    ((end / Self::SCALE - offset) / size).ceil() as i32
+                };
+                let mut tiles = vec![];
+                for y in first(rect.y, offset.y, tile_size.y)
+                    ..=last(rect.y + rect.height, offset.y, tile_size.y)
+                {
+                    for x in first(rect.x, offset.x, tile_size.x)
+                        ..=last(rect.x + rect.width, offset.x, tile_size.x)
+                    {
+                        let cell_origin = (offset
+                            + Vector2::new(x as f32 * tile_size.x, y as f32 * tile_size.y))
+                            * Self::SCALE;
+                        if chunk_at(cell_origin) != chunk {
+                            continue;
+                        }
+                        if let Some(tile) = tile_layer.get_tile(x, y) {
+                            tiles.extend(self.tile_draw(&tile, (x, y), offset, opacity));
+                        }
+                    }
+                }
+                layers.push(tiles);
+            }
+            LayerType::Group(group_layer) => {
+                for child in group_layer.layers() {
+                    self.load_layer_tiles(chunk, &child, offset, opacity, layers);
+                }
+            }
+            LayerType::Objects(_) | LayerType::Image(_) => (),
         }
     }
 
@@ -23,45 +485,262 @@ impl GameMap {
         target: &mut RenderTexture2D,
     ) {
         let mut d = d.begin_texture_mode(thread, target);
-        let scale = 0.1;
-        let texture = assets.get_texture("tiles/tilelist.png");
         let camera_world_rect = camera.get_visible_rect(Vector2::new(
             d.get_screen_width() as f32,
             d.get_screen_height() as f32,
         ));
-
-        let tileset = self.map.tilesets().first().unwrap();
+        let mut tile_layer = 0;
         for layer in self.map.layers() {
-            let tile_layer = layer.as_tile_layer().unwrap();
-            for y in 0..tile_layer.width().unwrap() {
-                for x in 0..tile_layer.height().unwrap() {
-                    if let Some(tile_id) = tile_layer.get_tile(x as i32, y as i32) {
-                        let tileset_index = tile_id.id();
-                        let source_rect = Rectangle::new(
-                            (tileset_index % tileset.columns) as f32 * 64.0,
-                            (tileset_index / tileset.columns) as f32 * 64.0,
-                            tileset.tile_width as f32,
-                            tileset.tile_height as f32,
-                        );
-                        let dest_rect = Rectangle::new(
-                            x as f32 * tileset.tile_width as f32 * scale,
-                            y as f32 * tileset.tile_height as f32 * scale,
-                            tileset.tile_width as f32 * scale * 1.001,
-                            tileset.tile_height as f32 * scale * 1.001,
-                        );
-                        if camera_world_rect.check_collision_recs(&dest_rect) {
-                            d.draw_texture_pro(
-                                texture,
-                                source_rect,
-                                camera.to_screen_rect(&dest_rect),
-                                Vector2::zero(),
-                                0.0,
-                                Color::WHITE,
-                            );
+            self.render_layer(
+                &mut d,
+                &layer,
+                camera,
+                &camera_world_rect,
+                assets,
+                Vector2::zero(),
+                1.0,
+                &mut tile_layer,
+            );
+        }
+    }
+
+    // Offset (map pixels) and opacity accumulate down through group layers.
+    // Tile layers draw their tiles from the loaded chunks, `tile_layer` counts them.
+    #[allow(clippy::too_many_arguments)]
+    fn render_layer(
+        &self,
+        d: &mut impl RaylibDraw,
+        layer: &tiled::Layer,
+        camera: &Camera2D,
+        camera_world_rect: &Rectangle,
+        assets: &Assets,
+        offset: Vector2,
+        opacity: f32,
+        tile_layer: &mut usize,
+    ) {
+        if !layer.visible {
+            return;
+        }
+        let offset = offset + Vector2::new(layer.offset_x, layer.offset_y);
+        let opacity = opacity * layer.opacity;
+        let tint = Color::WHITE.fade(opacity);
+        match layer.layer_type() {
+            LayerType::Tiles(_) => {
+                for layers in self.tile_chunks.values() {
+                    for tile in &layers[*tile_layer] {
+                        if camera_world_rect.check_collision_recs(&tile.dest_rect) {
+                            Self::draw_tile(d, tile, camera, assets);
                         }
                     }
                 }
+                *tile_layer += 1;
+            }
+            LayerType::Image(image_layer) => {
+                if let Some(image) = &image_layer.image {
+                    let texture = assets.get_texture(&Self::asset_name(&image.source));
+                    let dest_rect = Rectangle::new(
+                        offset.x * Self::SCALE,
+                        offset.y * Self::SCALE,
+                        image.width as f32 * Self::SCALE,
+                        image.height as f32 * Self::SCALE,
+                    );
+                    if camera_world_rect.check_collision_recs(&dest_rect) {
+                        d.draw_texture_pro(
+                            texture,
+                            Rectangle::new(0.0, 0.0, image.width as f32, image.height as f32),
+                            camera.to_screen_rect(&dest_rect),
+                            Vector2::zero(),
+                            0.0,
+                            tint,
+                        );
+                    }
+                }
+            }
+            LayerType::Group(group_layer) => {
+                for child in group_layer.layers() {
+                    self.render_layer(
+                        d,
+                        &child,
+                        camera,
+                        camera_world_rect,
+                        assets,
+                        offset,
+                        opacity,
+                        tile_layer,
+                    );
+                }
             }
+            LayerType::Objects(_) => (),
+        }
+    }
+
+    // Works out where and how a tile is drawn, done once when its chunk loads
+    fn tile_draw(
+        &self,
+        tile: &LayerTile,
+        grid_pos: (i32, i32),
+        offset: Vector2,
+        opacity: f32,
+    ) -> Option<TileDraw> {
+        let tileset = tile.get_tileset();
+        // Tilesets are either one image sliced into a grid, or a collection of single images
+        let (texture, mut source_rect) = match &tileset.image {
+            Some(image) => {
+                let column = tile.id() % tileset.columns.max(1);
+                let row = tile.id() / tileset.columns.max(1);
+                (
+                    Self::asset_name(&image.source),
+                    Rectangle::new(
+                        (tileset.margin + column * (tileset.tile_width + tileset.spacing)) as f32,
+                        (tileset.margin + row * (tileset.tile_height + tileset.spacing)) as f32,
+                        tileset.tile_width as f32,
+                        tileset.tile_height as f32,
+                    ),
+                )
+            }
+            None => {
+                let image = tile.get_tile().and_then(|t| t.image.clone())?;
+                (
+                    Self::asset_name(&image.source),
+                    Rectangle::new(0.0, 0.0, image.width as f32, image.height as f32),
+                )
+            }
+        };
+
+        // Tiles larger than the map grid are anchored to the bottom left of their cell
+        let tile_size = Vector2::new(source_rect.width, source_rect.height) * Self::SCALE;
+        let dest_rect = Rectangle::new(
+            (offset.x + (grid_pos.0 * self.map.tile_width as i32) as f32) * Self::SCALE,
+            (offset.y + ((grid_pos.1 + 1) * self.map.tile_height as i32) as f32) * Self::SCALE
+                - tile_size.y,
+            tile_size.x * 1.001,
+            tile_size.y * 1.001,
+        );
+
+        // Tiled applies the diagonal flip first, which is a 90 degree turn plus a flip.
+        // Raylib flips the source before rotating, so the flags are swapped around to match.
+        let (flip_x, flip_y, rotation) = if tile.flip_d {
+            (tile.flip_v, !tile.flip_h, 90.0)
+        } else {
+            (tile.flip_h, tile.flip_v, 0.0)
+        };
+        if flip_x {
+            source_rect.width = -source_rect.width;
         }
+        if flip_y {
+            source_rect.height = -source_rect.height;
+        }
+        Some(TileDraw {
+            texture,
+            source_rect,
+            dest_rect,
+            rotation,
+            tint: Color::WHITE.fade(opacity),
+        })
+    }
+
+    fn draw_tile(d: &mut impl RaylibDraw, tile: &TileDraw, camera: &Camera2D, assets: &Assets) {
+        // Rotating around the center of the tile keeps it inside its cell
+        let mut screen_rect = camera.to_screen_rect(&tile.dest_rect);
+        let origin = Vector2::new(screen_rect.width / 2.0, screen_rect.height / 2.0);
+        screen_rect.x += origin.x;
+        screen_rect.y += origin.y;
+        d.draw_texture_pro(
+            assets.get_texture(&tile.texture),
+            tile.source_rect,
+            screen_rect,
+            origin,
+            tile.rotation,
+            tile.tint,
+        );
+    }
+
+    // Asset names are relative to the assets folder, while Tiled paths are relative to the map
+    fn asset_name(path: &Path) -> String {
+        let path = path.to_string_lossy().replace('\\', "/");
+        match path.rfind("assets/") {
+            Some(index) => path[index + "assets/".len()..].to_string(),
+            None => path,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Player;
+
+    #[test]
+    fn generated_maps_load_without_touching_the_disk() {
+        let seed = 4242;
+        let map = GameMap::generate(seed).unwrap();
+        assert_eq!((map.map.width, map.map.height), GameMap::GENERATED_SIZE);
+        assert!(!map.ground().ground_at(map.spawn_point()).swimmable);
+        assert!(!Path::new(&GameMap::generated_path(seed)).exists());
+    }
+
+    // Infinite map with a wall tile left of the origin, a wall object, and two marker objects of
+    // which only one asks to collide
+    fn infinite_map_tmx() -> String {
+        let mut cells = vec!["0"; 16 * 16];
+        cells[16 + 2] = "1";
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="4" tilewidth="64" tileheight="64" infinite="1" nextlayerid="4" nextobjectid="5">
+ <tileset firstgid="1" name="walls" tilewidth="64" tileheight="64" tilecount="1" columns="1">
+  <image source="walls.png" width="64" height="64"/>
+  <tile id="0">
+   <objectgroup draworder="index">
+    <object id="1" x="0" y="0" width="64" height="64"/>
+   </objectgroup>
+  </tile>
+ </tileset>
+ <layer id="1" name="Ground" width="4" height="4">
+  <data encoding="csv">
+   <chunk x="-16" y="0" width="16" height="16">
+{}
+   </chunk>
+  </data>
+ </layer>
+ <objectgroup id="2" name="Walls">
+  <object id="2" x="0" y="0" width="32" height="32"/>
+ </objectgroup>
+ <objectgroup id="3" name="Markers">
+  <object id="3" name="spawn" x="200" y="200" width="10" height="10"/>
+  <object id="4" name="crate" x="100" y="100" width="10" height="10">
+   <properties>
+    <property name="collision" type="bool" value="true"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>"#,
+            cells.join(",")
+        )
+    }
+
+    #[test]
+    fn only_walls_and_colliding_objects_block_movement() {
+        let map =
+            GameMap::from_map(GameMap::load_tmx("maps/test.tmx", &infinite_map_tmx()).unwrap());
+        let mut centers = map
+            .colliders()
+            .iter()
+            .map(|collider| (collider.pos.x, collider.pos.y))
+            .collect::<Vec<(f32, f32)>>();
+        centers.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let expected = [(-86.4, 9.6), (1.6, 1.6), (10.5, 10.5)];
+        assert_eq!(centers.len(), expected.len());
+        for (center, expected) in centers.iter().zip(expected) {
+            assert!((center.0 - expected.0).abs() < 1e-3 && (center.1 - expected.1).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn the_hand_made_map_has_a_pond_to_swim_in() {
+        let ground = GameMap::load_map("maps/map.tmx").ground();
+        let pond = ground.ground_at(Vector2::new(83.0, 86.0));
+        assert!(pond.swimmable);
+        assert_eq!(pond.footstep.as_deref(), Some("water"));
+        assert!(!ground.ground_at(Player::SPAWN_POS).swimmable);
     }
 }
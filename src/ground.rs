@@ -0,0 +1,137 @@
+use raylib::prelude::*;
+use std::collections::HashMap;
+use tiled::{Properties, PropertyValue};
+
+// How the ground under a character affects it. Set in Tiled with custom properties on a tile
+// layer, or on single tiles of the tileset, which override the layer's.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ground {
+    // Scales walking and sprinting speed, property `speed_multiplier`
+    pub speed_multiplier: f32,
+    // How quickly characters speed up and slow down, lower is more slippery. Property `friction`.
+    pub friction: f32,
+    // Deep water, characters swim through it and can't shoot. Property `swimmable`.
+    pub swimmable: bool,
+    // Sound played for footsteps on it, property `footstep`
+    pub footstep: Option<String>,
+}
+
+impl Default for Ground {
+    fn default() -> Self {
+        Ground {
+            speed_multiplier: 1.0,
+            friction: 1.0,
+            swimmable: false,
+            footstep: None,
+        }
+    }
+}
+
+impl Ground {
+    // Overrides the fields the properties set
+    pub fn apply_properties(&mut self, properties: &Properties) {
+        if let Some(PropertyValue::FloatValue(speed_multiplier)) =
+            properties.get("speed_multiplier")
+        {
+            self.speed_multiplier = *speed_multiplier;
+        }
+        if let Some(PropertyValue::FloatValue(friction)) = properties.get("friction") {
+            self.friction = *friction;
+        }
+        if let Some(PropertyValue::BoolValue(swimmable)) = properties.get("swimmable") {
+            self.swimmable = *swimmable;
+        }
+        if let Some(PropertyValue::StringValue(footstep)) = properties.get("footstep") {
+            self.footstep = Some(footstep.clone());
+        }
+    }
+}
+
+// A step a character took, played as a sound
+#[derive(Clone, Debug, PartialEq)]
+pub struct Footstep {
+    pub pos: Vector2,
+    // The `footstep` of the ground it was taken on
+    pub sound: String,
+}
+
+// The ground of every map tile, from the topmost layer with a tile there
+pub struct GroundMap {
+    // In world units
+    tile_size: Vector2,
+    tiles: HashMap<(i32, i32), Ground>,
+    // Off the map, or where no layer has a tile
+    default: Ground,
+}
+
+impl GroundMap {
+    pub fn new(tile_size: Vector2) -> Self {
+        GroundMap {
+            tile_size,
+            tiles: HashMap::new(),
+            default: Ground::default(),
+        }
+    }
+
+    pub fn set(&mut self, cell: (i32, i32), ground: Ground) {
+        self.tiles.insert(cell, ground);
+    }
+
+    pub fn ground_at(&self, pos: Vector2) -> &Ground {
+        let cell = (
+            (pos.x / self.tile_size.x).floor() as i32,
+            (pos.y / self.tile_size.y).floor() as i32,
+        );
+        self.tiles.get(&cell).unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_properties_override_the_layer() {
+        let layer = Properties::from([
+            (
+                "speed_multiplier".to_string(),
+                PropertyValue::FloatValue(0.5),
+            ),
+            ("swimmable".to_string(), PropertyValue::BoolValue(true)),
+            (
+                "footstep".to_string(),
+                PropertyValue::StringValue("water".to_string()),
+            ),
+        ]);
+        let tile = Properties::from([(
+            "speed_multiplier".to_string(),
+            PropertyValue::FloatValue(0.3),
+        )]);
+        let mut ground = Ground::default();
+        ground.apply_properties(&layer);
+        ground.apply_properties(&tile);
+        assert_eq!(ground.speed_multiplier, 0.3);
+        assert_eq!(ground.friction, 1.0);
+        assert!(ground.swimmable);
+        assert_eq!(ground.footstep.as_deref(), Some("water"));
+    }
+
+    #[test]
+    fn ground_is_looked_up_by_tile() {
+        let mut ground_map = GroundMap::new(Vector2::new(6.4, 6.4));
+        let water = Ground {
+            swimmable: true,
+            ..Default::default()
+        };
+        ground_map.set((1, 0), water.clone());
+        assert_eq!(ground_map.ground_at(Vector2::new(7.0, 3.0)), &water);
+        assert_eq!(
+            ground_map.ground_at(Vector2::new(5.0, 3.0)),
+            &Ground::default()
+        );
+        assert_eq!(
+            ground_map.ground_at(Vector2::new(-7.0, 3.0)),
+            &Ground::default()
+        );
+    }
+}
@@ -0,0 +1,689 @@
+use raylib::prelude::*;
+use std::collections::HashMap;
+
+use crate::{equipment::EquipmentSlot, ImprovedCamera};
+
+pub const CONTROLS_PATH: &str = "controls.cfg";
+const GAMEPAD: i32 = 0;
+const GAMEPAD_DEADZONE: f32 = 0.2;
+const GAMEPAD_AIM_DISTANCE: f32 = 10.0;
+
+// Everything the simulation needs to know about the player's intent for one frame.
+// Built from raylib when running with a window, or by hand when running headless.
+#[derive(Clone, Debug)]
+pub struct PlayerInput {
+    pub movement: Vector2,
+    pub sprinting: bool,
+    pub aim_pos: Vector2,
+    pub firing: bool,
+    pub reload: bool,
+    pub switch_fire_mode: bool,
+    pub quick_heal: bool,
+    // Weapon slot to draw, or to holster if it is already in hand
+    pub select_weapon: Option<EquipmentSlot>,
+    pub interact: bool,
+    pub toggle_inventory: bool,
+    pub spawn_dummy: Option<Vector2>,
+}
+
+impl Default for PlayerInput {
+    fn default() -> Self {
+        PlayerInput {
+            movement: Vector2::zero(),
+            sprinting: false,
+            aim_pos: Vector2::zero(),
+            firing: false,
+            reload: false,
+            switch_fire_mode: false,
+            quick_heal: false,
+            select_weapon: None,
+            interact: false,
+            toggle_inventory: false,
+            spawn_dummy: None,
+        }
+    }
+}
+
+impl PlayerInput {
+    pub fn from_raylib(
+        rl: &RaylibHandle,
+        camera: &Camera2D,
+        bindings: &InputBindings,
+        player_pos: Vector2,
+    ) -> Self {
+        let mut movement = Vector2::zero();
+        if bindings.is_down(rl, Action::MoveUp) {
+            movement.y -= 1.0;
+        }
+        if bindings.is_down(rl, Action::MoveDown) {
+            movement.y += 1.0;
+        }
+        if bindings.is_down(rl, Action::MoveLeft) {
+            movement.x -= 1.0;
+        }
+        if bindings.is_down(rl, Action::MoveRight) {
+            movement.x += 1.0;
+        }
+        let mut aim_pos = camera.to_world(rl.get_mouse_position());
+        if rl.is_gamepad_available(GAMEPAD) {
+            let left_stick = Self::gamepad_stick(
+                rl,
+                GamepadAxis::GAMEPAD_AXIS_LEFT_X,
+                GamepadAxis::GAMEPAD_AXIS_LEFT_Y,
+            );
+            let right_stick = Self::gamepad_stick(
+                rl,
+                GamepadAxis::GAMEPAD_AXIS_RIGHT_X,
+                GamepadAxis::GAMEPAD_AXIS_RIGHT_Y,
+            );
+            movement += left_stick;
+            if right_stick != Vector2::zero() {
+                aim_pos = player_pos + right_stick.normalized() * GAMEPAD_AIM_DISTANCE;
+            }
+        }
+        PlayerInput {
+            movement,
+            sprinting: bindings.is_down(rl, Action::Sprint),
+            aim_pos,
+            firing: bindings.is_down(rl, Action::Fire),
+            reload: bindings.is_pressed(rl, Action::Reload),
+            switch_fire_mode: bindings.is_pressed(rl, Action::SwitchFireMode),
+            quick_heal: bindings.is_pressed(rl, Action::QuickHeal),
+            select_weapon: if bindings.is_pressed(rl, Action::PrimaryWeapon) {
+                Some(EquipmentSlot::Primary)
+            } else if bindings.is_pressed(rl, Action::SecondaryWeapon) {
+                Some(EquipmentSlot::Secondary)
+            } else {
+                None
+            },
+            interact: bindings.is_pressed(rl, Action::Interact),
+            toggle_inventory: bindings.is_pressed(rl, Action::ToggleInventory),
+            spawn_dummy: if bindings.is_pressed(rl, Action::SpawnDummy) {
+                Some(aim_pos)
+            } else {
+                None
+            },
+        }
+    }
+
+    // Keeps only the aim, used while a menu is capturing input
+    pub fn aim_only(self) -> Self {
+        PlayerInput {
+            aim_pos: self.aim_pos,
+            ..Default::default()
+        }
+    }
+
+    // Returns the stick direction, or zero while it rests inside the deadzone
+    fn gamepad_stick(rl: &RaylibHandle, axis_x: GamepadAxis, axis_y: GamepadAxis) -> Vector2 {
+        let stick = Vector2::new(
+            rl.get_gamepad_axis_movement(GAMEPAD, axis_x),
+            rl.get_gamepad_axis_movement(GAMEPAD, axis_y),
+        );
+        if stick.length() < GAMEPAD_DEADZONE {
+            Vector2::zero()
+        } else {
+            stick
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Fire,
+    Reload,
+    SwitchFireMode,
+    QuickHeal,
+    PrimaryWeapon,
+    SecondaryWeapon,
+    Interact,
+    ToggleInventory,
+    RotateItem,
+    SplitStack,
+    SpawnDummy,
+    ToggleDebug,
+    FastForwardTime,
+    SkipTime,
+    SetTime,
+    ToggleControlsMenu,
+    QuickSave,
+    QuickLoad,
+    CycleSaveSlot,
+}
+
+impl Action {
+    pub const ALL: [Action; 24] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Sprint,
+        Action::Fire,
+        Action::Reload,
+        Action::SwitchFireMode,
+        Action::QuickHeal,
+        Action::PrimaryWeapon,
+        Action::SecondaryWeapon,
+        Action::Interact,
+        Action::ToggleInventory,
+        Action::RotateItem,
+        Action::SplitStack,
+        Action::SpawnDummy,
+        Action::ToggleDebug,
+        Action::FastForwardTime,
+        Action::SkipTime,
+        Action::SetTime,
+        Action::ToggleControlsMenu,
+        Action::QuickSave,
+        Action::QuickLoad,
+        Action::CycleSaveSlot,
+    ];
+
+    // Name used in the controls config file
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Sprint => "sprint",
+            Action::Fire => "fire",
+            Action::Reload => "reload",
+            Action::SwitchFireMode => "switch_fire_mode",
+            Action::QuickHeal => "quick_heal",
+            Action::PrimaryWeapon => "primary_weapon",
+            Action::SecondaryWeapon => "secondary_weapon",
+            Action::Interact => "interact",
+            Action::ToggleInventory => "toggle_inventory",
+            Action::RotateItem => "rotate_item",
+            Action::SplitStack => "split_stack",
+            Action::SpawnDummy => "spawn_dummy",
+            Action::ToggleDebug => "toggle_debug",
+            Action::FastForwardTime => "fast_forward_time",
+            Action::SkipTime => "skip_time",
+            Action::SetTime => "set_time",
+            Action::ToggleControlsMenu => "toggle_controls_menu",
+            Action::QuickSave => "quick_save",
+            Action::QuickLoad => "quick_load",
+            Action::CycleSaveSlot => "cycle_save_slot",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Sprint => "Sprint",
+            Action::Fire => "Fire",
+            Action::Reload => "Reload",
+            Action::SwitchFireMode => "Fire mode",
+            Action::QuickHeal => "Quick heal",
+            Action::PrimaryWeapon => "Primary weapon",
+            Action::SecondaryWeapon => "Secondary weapon",
+            Action::Interact => "Interact",
+            Action::ToggleInventory => "Inventory",
+            Action::RotateItem => "Rotate item",
+            Action::SplitStack => "Split stack",
+            Action::SpawnDummy => "Spawn dummy",
+            Action::ToggleDebug => "Debug info",
+            Action::FastForwardTime => "Fast forward time (debug)",
+            Action::SkipTime => "Skip time of day (debug)",
+            Action::SetTime => "Set time of day (debug)",
+            Action::ToggleControlsMenu => "Controls menu",
+            Action::QuickSave => "Save game",
+            Action::QuickLoad => "Load game",
+            Action::CycleSaveSlot => "Next save slot",
+        }
+    }
+
+    fn from_config_name(name: &str) -> Option<Action> {
+        Self::ALL
+            .iter()
+            .find(|action| action.config_name() == name)
+            .copied()
+    }
+}
+
+const MOUSE_BUTTONS: [MouseButton; 3] = [
+    MouseButton::MOUSE_BUTTON_LEFT,
+    MouseButton::MOUSE_BUTTON_RIGHT,
+    MouseButton::MOUSE_BUTTON_MIDDLE,
+];
+
+const GAMEPAD_BUTTONS: [GamepadButton; 17] = [
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_1,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_2,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2,
+    GamepadButton::GAMEPAD_BUTTON_MIDDLE_LEFT,
+    GamepadButton::GAMEPAD_BUTTON_MIDDLE,
+    GamepadButton::GAMEPAD_BUTTON_MIDDLE_RIGHT,
+    GamepadButton::GAMEPAD_BUTTON_LEFT_THUMB,
+    GamepadButton::GAMEPAD_BUTTON_RIGHT_THUMB,
+];
+
+// Highest raylib keycode (KEY_KB_MENU)
+const MAX_KEY_CODE: i32 = 348;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+    Key(KeyboardKey),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    pub fn is_down(&self, rl: &RaylibHandle) -> bool {
+        match self {
+            Binding::Key(key) => rl.is_key_down(*key),
+            Binding::Mouse(button) => rl.is_mouse_button_down(*button),
+            Binding::Gamepad(button) => rl.is_gamepad_button_down(GAMEPAD, *button),
+        }
+    }
+
+    pub fn is_pressed(&self, rl: &RaylibHandle) -> bool {
+        match self {
+            Binding::Key(key) => rl.is_key_pressed(*key),
+            Binding::Mouse(button) => rl.is_mouse_button_pressed(*button),
+            Binding::Gamepad(button) => rl.is_gamepad_button_pressed(GAMEPAD, *button),
+        }
+    }
+
+    // Returns whichever key or button was pressed this frame, used by the rebinding screen
+    pub fn pressed_this_frame(rl: &mut RaylibHandle) -> Option<Binding> {
+        if let Some(key) = rl.get_key_pressed() {
+            return Some(Binding::Key(key));
+        }
+        if let Some(button) = MOUSE_BUTTONS
+            .iter()
+            .find(|button| rl.is_mouse_button_pressed(**button))
+        {
+            return Some(Binding::Mouse(*button));
+        }
+        GAMEPAD_BUTTONS
+            .iter()
+            .find(|button| rl.is_gamepad_button_pressed(GAMEPAD, **button))
+            .map(|button| Binding::Gamepad(*button))
+    }
+
+    // Bindings are stored by their raylib names, e.g. KEY_W or MOUSE_BUTTON_LEFT
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("{:?}", button),
+            Binding::Gamepad(button) => format!("{:?}", button),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Binding> {
+        if let Some(button) = MOUSE_BUTTONS
+            .iter()
+            .find(|button| format!("{:?}", button) == name)
+        {
+            return Some(Binding::Mouse(*button));
+        }
+        if let Some(button) = GAMEPAD_BUTTONS
+            .iter()
+            .find(|button| format!("{:?}", button) == name)
+        {
+            return Some(Binding::Gamepad(*button));
+        }
+        (0..=MAX_KEY_CODE)
+            .filter_map(key_from_i32)
+            .find(|key| format!("{:?}", key) == name)
+            .map(Binding::Key)
+    }
+}
+
+// Returned by `rebind` with the action the key or button is already bound to
+#[derive(Debug, PartialEq)]
+pub struct BindingConflict(pub Action);
+
+pub struct InputBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Binding::*;
+        let bindings = HashMap::from([
+            (
+                Action::MoveUp,
+                vec![Key(KeyboardKey::KEY_W), Key(KeyboardKey::KEY_UP)],
+            ),
+            (
+                Action::MoveDown,
+                vec![Key(KeyboardKey::KEY_S), Key(KeyboardKey::KEY_DOWN)],
+            ),
+            (
+                Action::MoveLeft,
+                vec![Key(KeyboardKey::KEY_A), Key(KeyboardKey::KEY_LEFT)],
+            ),
+            (
+                Action::MoveRight,
+                vec![Key(KeyboardKey::KEY_D), Key(KeyboardKey::KEY_RIGHT)],
+            ),
+            (
+                Action::Sprint,
+                vec![
+                    Key(KeyboardKey::KEY_LEFT_SHIFT),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_LEFT_THUMB),
+                ],
+            ),
+            (
+                Action::Fire,
+                vec![
+                    Mouse(MouseButton::MOUSE_BUTTON_LEFT),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2),
+                ],
+            ),
+            (
+                Action::Reload,
+                vec![
+                    Key(KeyboardKey::KEY_R),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT),
+                ],
+            ),
+            (
+                Action::SwitchFireMode,
+                vec![
+                    Key(KeyboardKey::KEY_B),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN),
+                ],
+            ),
+            (
+                Action::QuickHeal,
+                vec![
+                    Key(KeyboardKey::KEY_H),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT),
+                ],
+            ),
+            (
+                Action::PrimaryWeapon,
+                vec![
+                    Key(KeyboardKey::KEY_ONE),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT),
+                ],
+            ),
+            (
+                Action::SecondaryWeapon,
+                vec![
+                    Key(KeyboardKey::KEY_TWO),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT),
+                ],
+            ),
+            (
+                Action::Interact,
+                vec![
+                    Key(KeyboardKey::KEY_E),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN),
+                ],
+            ),
+            (
+                Action::ToggleInventory,
+                vec![
+                    Key(KeyboardKey::KEY_I),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP),
+                ],
+            ),
+            (
+                Action::RotateItem,
+                vec![
+                    Key(KeyboardKey::KEY_T),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1),
+                ],
+            ),
+            (
+                Action::SplitStack,
+                vec![Mouse(MouseButton::MOUSE_BUTTON_MIDDLE)],
+            ),
+            (Action::SpawnDummy, vec![Key(KeyboardKey::KEY_G)]),
+            (Action::ToggleDebug, vec![Key(KeyboardKey::KEY_F1)]),
+            (Action::FastForwardTime, vec![Key(KeyboardKey::KEY_F3)]),
+            (Action::SkipTime, vec![Key(KeyboardKey::KEY_F4)]),
+            (Action::SetTime, vec![Key(KeyboardKey::KEY_F7)]),
+            (
+                Action::ToggleControlsMenu,
+                vec![
+                    Key(KeyboardKey::KEY_F2),
+                    Gamepad(GamepadButton::GAMEPAD_BUTTON_MIDDLE_RIGHT),
+                ],
+            ),
+            (Action::QuickSave, vec![Key(KeyboardKey::KEY_F5)]),
+            (Action::QuickLoad, vec![Key(KeyboardKey::KEY_F9)]),
+            (Action::CycleSaveSlot, vec![Key(KeyboardKey::KEY_F6)]),
+        ]);
+        InputBindings { bindings }
+    }
+}
+
+impl InputBindings {
+    pub fn is_down(&self, rl: &RaylibHandle, action: Action) -> bool {
+        self.get(action).iter().any(|binding| binding.is_down(rl))
+    }
+
+    pub fn is_pressed(&self, rl: &RaylibHandle, action: Action) -> bool {
+        self.get(action)
+            .iter()
+            .any(|binding| binding.is_pressed(rl))
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // The action other than `action` that already uses the binding
+    fn bound_elsewhere(&self, action: Action, binding: Binding) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .find(|other| **other != action && self.get(**other).contains(&binding))
+            .copied()
+    }
+
+    // Replaces the primary binding of an action, keeping any secondary ones. A secondary
+    // binding made primary swaps places with the old primary one.
+    // Keys and buttons already bound to another action are rejected.
+    pub fn rebind(&mut self, action: Action, binding: Binding) -> Result<(), BindingConflict> {
+        if let Some(other) = self.bound_elsewhere(action, binding) {
+            return Err(BindingConflict(other));
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        match bindings.iter().position(|b| *b == binding) {
+            Some(i) => bindings.swap(0, i),
+            None if bindings.is_empty() => bindings.push(binding),
+            None => bindings[0] = binding,
+        }
+        Ok(())
+    }
+
+    // Parses lines of `action = BINDING, BINDING`. Unknown actions and bindings are skipped.
+    pub fn parse(config: &str) -> Self {
+        let mut input_bindings = InputBindings::default();
+        for line in config.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((action_name, binding_names)) = line.split_once('=') else {
+                eprintln!("Ignoring malformed controls line: {}", line);
+                continue;
+            };
+            let Some(action) = Action::from_config_name(action_name.trim()) else {
+                eprintln!("Ignoring unknown action: {}", action_name.trim());
+                continue;
+            };
+            let bindings = binding_names
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .filter_map(|name| {
+                    let binding = Binding::from_name(name);
+                    if binding.is_none() {
+                        eprintln!("Ignoring unknown binding: {}", name);
+                    }
+                    binding
+                })
+                .collect();
+            input_bindings.bindings.insert(action, bindings);
+        }
+        // Without a binding for the controls menu there would be no way to fix the others
+        if input_bindings.get(Action::ToggleControlsMenu).is_empty() {
+            eprintln!("Controls menu has no binding, using the default");
+            input_bindings.bindings.insert(
+                Action::ToggleControlsMenu,
+                Self::default().get(Action::ToggleControlsMenu).to_vec(),
+            );
+        }
+        input_bindings
+    }
+
+    pub fn to_config(&self) -> String {
+        Action::ALL
+            .iter()
+            .map(|action| {
+                let names = self
+                    .get(*action)
+                    .iter()
+                    .map(|binding| binding.name())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                format!("{} = {}\n", action.config_name(), names)
+            })
+            .collect()
+    }
+
+    // Falls back to the default bindings if the file does not exist yet
+    pub fn load(path: &str) -> Self {
+        match std::fs::read_to_string(path) {
+            Ok(config) => Self::parse(&config),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_config())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trips() {
+        let mut bindings = InputBindings::default();
+        bindings
+            .rebind(Action::SpawnDummy, Binding::Key(KeyboardKey::KEY_J))
+            .unwrap();
+        let parsed = InputBindings::parse(&bindings.to_config());
+        assert_eq!(parsed.to_config(), bindings.to_config());
+        assert_eq!(
+            parsed.get(Action::SpawnDummy),
+            &[Binding::Key(KeyboardKey::KEY_J)]
+        );
+        assert_eq!(
+            parsed.get(Action::Fire),
+            &[
+                Binding::Mouse(MouseButton::MOUSE_BUTTON_LEFT),
+                Binding::Gamepad(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2)
+            ]
+        );
+
+        let path = std::env::temp_dir().join("survival_game_controls_test.cfg");
+        let path = path.to_str().unwrap();
+        bindings.save(path).unwrap();
+        assert_eq!(InputBindings::load(path).to_config(), bindings.to_config());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn default_bindings_are_not_shared_between_actions() {
+        let bindings = InputBindings::default();
+        for action in Action::ALL {
+            for binding in bindings.get(action) {
+                assert_eq!(bindings.bound_elsewhere(action, *binding), None);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_skips_unknown_lines() {
+        let bindings = InputBindings::parse(
+            "# comment\nfire = KEY_SPACE, NOT_A_KEY\nnot_an_action = KEY_W\nmalformed",
+        );
+        assert_eq!(
+            bindings.get(Action::Fire),
+            &[Binding::Key(KeyboardKey::KEY_SPACE)]
+        );
+        assert_eq!(
+            bindings.get(Action::MoveUp),
+            InputBindings::default().get(Action::MoveUp)
+        );
+    }
+
+    #[test]
+    fn rebind_rejects_keys_bound_to_another_action() {
+        let mut bindings = InputBindings::default();
+        assert_eq!(
+            bindings.rebind(Action::Sprint, Binding::Key(KeyboardKey::KEY_W)),
+            Err(BindingConflict(Action::MoveUp))
+        );
+        assert_eq!(
+            bindings.get(Action::Sprint)[0],
+            Binding::Key(KeyboardKey::KEY_LEFT_SHIFT)
+        );
+        // Binding an action to what it already uses is fine
+        assert_eq!(
+            bindings.rebind(Action::MoveUp, Binding::Key(KeyboardKey::KEY_UP)),
+            Ok(())
+        );
+        assert_eq!(
+            bindings.get(Action::MoveUp)[0],
+            Binding::Key(KeyboardKey::KEY_UP)
+        );
+    }
+
+    #[test]
+    fn rebinding_to_a_secondary_binding_swaps_them() {
+        let mut bindings = InputBindings::default();
+        let (w, up) = (
+            Binding::Key(KeyboardKey::KEY_W),
+            Binding::Key(KeyboardKey::KEY_UP),
+        );
+        assert_eq!(bindings.get(Action::MoveUp), &[w, up]);
+        bindings.rebind(Action::MoveUp, up).unwrap();
+        assert_eq!(bindings.get(Action::MoveUp), &[up, w]);
+        // A new binding only replaces the primary one
+        let k = Binding::Key(KeyboardKey::KEY_K);
+        bindings.rebind(Action::MoveUp, k).unwrap();
+        assert_eq!(bindings.get(Action::MoveUp), &[k, w]);
+    }
+
+    #[test]
+    fn controls_menu_always_keeps_a_binding() {
+        let bindings = InputBindings::parse("toggle_controls_menu =\nfire =");
+        assert!(bindings.get(Action::Fire).is_empty());
+        assert_eq!(
+            bindings.get(Action::ToggleControlsMenu),
+            InputBindings::default().get(Action::ToggleControlsMenu)
+        );
+    }
+}
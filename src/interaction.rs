@@ -0,0 +1,173 @@
+use raylib::prelude::*;
+
+use crate::{
+    collision_world::CollisionWorld,
+    container::WorldContainer,
+    input::{Action, InputBindings, PlayerInput},
+    inventory::{InventoryFull, LootTarget},
+    Corpse, GameWorld, Player,
+};
+
+// Something in the world the player is close enough to interact with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interactable {
+    GroundItem(usize),
+    Container(usize),
+    Corpse(usize),
+}
+
+// Tracks what the player can currently interact with and shows the result of interacting
+pub struct Interaction {
+    pub target: Option<Interactable>,
+    message: Option<(String, f32)>,
+}
+
+impl Interaction {
+    // Seconds a message such as "Inventory full" stays on screen
+    const MESSAGE_TIME: f32 = 2.0;
+    const FONT_SIZE: i32 = 30;
+
+    pub fn new() -> Self {
+        Interaction {
+            target: None,
+            message: None,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        input: &PlayerInput,
+        dt: f32,
+        player: &mut Player,
+        game_world: &mut GameWorld,
+        collision_world: &mut CollisionWorld,
+    ) {
+        if let Some((_, timer)) = &mut self.message {
+            *timer -= dt;
+            if *timer <= 0.0 {
+                self.message = None;
+            }
+        }
+        let player_pos = player.collider.get_pos(collision_world);
+        // Walking away from an opened container or closing the inventory stops looting it
+        if let Some(looting) = player.inventory.looting {
+            let in_reach = match looting {
+                LootTarget::Crate(i) => game_world.containers.get(i).is_some_and(|container| {
+                    container.pos.distance_to(player_pos) <= WorldContainer::OPEN_RADIUS
+                }),
+                LootTarget::Corpse(i) => game_world.corpses.get(i).is_some_and(|corpse| {
+                    corpse.pos.distance_to(player_pos) <= Corpse::SEARCH_RADIUS
+                }),
+            };
+            if !in_reach || !player.inventory_open {
+                player.inventory.looting = None;
+            }
+        }
+        self.target = Self::find_target(player_pos, game_world);
+        if input.interact && !player.inventory_open {
+            if let Some(target) = self.target {
+                self.interact(target, player, game_world);
+                self.target = Self::find_target(player_pos, game_world);
+            }
+        }
+    }
+
+    // The closest interactable within reach of the player
+    fn find_target(player_pos: Vector2, game_world: &GameWorld) -> Option<Interactable> {
+        let ground_items = game_world
+            .ground_items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                (
+                    Interactable::GroundItem(i),
+                    item.pos().distance_to(player_pos),
+                )
+            })
+            .filter(|(_, distance)| *distance <= crate::GroundItem::PICKUP_RADIUS);
+        let containers = game_world
+            .containers
+            .iter()
+            .enumerate()
+            .map(|(i, container)| {
+                (
+                    Interactable::Container(i),
+                    container.pos.distance_to(player_pos),
+                )
+            })
+            .filter(|(_, distance)| *distance <= WorldContainer::OPEN_RADIUS);
+        let corpses = game_world
+            .corpses
+            .iter()
+            .enumerate()
+            .map(|(i, corpse)| (Interactable::Corpse(i), corpse.pos.distance_to(player_pos)))
+            .filter(|(_, distance)| *distance <= Corpse::SEARCH_RADIUS);
+        ground_items
+            .chain(containers)
+            .chain(corpses)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(target, _)| target)
+    }
+
+    fn interact(&mut self, target: Interactable, player: &mut Player, game_world: &mut GameWorld) {
+        match target {
+            Interactable::GroundItem(i) => {
+                let ground_item = game_world.ground_items.remove(i);
+                let item = ground_item.item().clone();
+                // Whatever did not fit stays on the ground
+                if let Err(InventoryFull(rest)) = player.inventory.try_insert(item) {
+                    game_world
+                        .ground_items
+                        .insert(i, rest.to_ground_item(ground_item.pos()));
+                    self.message = Some(("Inventory full".to_string(), Self::MESSAGE_TIME));
+                }
+            }
+            // Opens the container next to the inventory
+            Interactable::Container(i) => {
+                player.inventory.looting = Some(LootTarget::Crate(i));
+                player.inventory_open = true;
+            }
+            Interactable::Corpse(i) => {
+                player.inventory.looting = Some(LootTarget::Corpse(i));
+                player.inventory_open = true;
+            }
+        }
+    }
+
+    pub fn render(
+        &self,
+        d: &mut RaylibDrawHandle,
+        game_world: &GameWorld,
+        bindings: &InputBindings,
+    ) {
+        let screen_size = Vector2::new(d.get_screen_width() as f32, d.get_screen_height() as f32);
+        let text = if let Some((message, _)) = &self.message {
+            message.clone()
+        } else if let Some(target) = self.target {
+            let key_name = bindings
+                .get(Action::Interact)
+                .first()
+                .map(|binding| binding.name())
+                .unwrap_or_default();
+            match target {
+                Interactable::GroundItem(i) => format!(
+                    "Press {} to pick up {}",
+                    key_name,
+                    game_world.ground_items[i].item().item.get_display_name()
+                ),
+                Interactable::Container(_) => format!("Press {} to open crate", key_name),
+                Interactable::Corpse(_) => format!("Press {} to search body", key_name),
+            }
+        } else {
+            return;
+        };
+        let text_width = measure_text(&text, Self::FONT_SIZE);
+        d.draw_text(
+            &text,
+            (screen_size.x / 2.0) as i32 - text_width / 2,
+            (screen_size.y * 0.75) as i32,
+            Self::FONT_SIZE,
+            Color::WHITE,
+        );
+    }
+}
@@ -1,14 +1,34 @@
 use raylib::prelude::*;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
-use crate::{assets::Assets, lighting_renderer::LightingRenderer, GameWorld, ImprovedCamera, Player};
+use crate::{
+    assets::Assets,
+    consumable::ConsumableEffect,
+    container::Container,
+    equipment::{Equipment, EquipmentSlot},
+    input::{Action, InputBindings},
+    lighting_renderer::LightingRenderer,
+    weapon::{Caliber, ReloadSource},
+    GameWorld, ImprovedCamera,
+};
 
 pub struct GroundItem {
-    item: Item,
+    item: InventoryItem,
     pos: Vector2,
 }
 
 impl GroundItem {
+    // Distance from the player at which the item can be picked up
+    pub const PICKUP_RADIUS: f32 = 3.0;
+
+    pub fn item(&self) -> &InventoryItem {
+        &self.item
+    }
+
+    pub fn pos(&self) -> Vector2 {
+        self.pos
+    }
+
     pub fn render(
         &self,
         d: &mut RaylibDrawHandle,
@@ -18,8 +38,8 @@ impl GroundItem {
         thread: &RaylibThread,
     ) {
         let screen_pos = camera.to_screen(self.pos);
-        let texture = assets.get_texture(&self.item.get_asset_name());
-        let size = self.item.get_inventory_size();
+        let texture = assets.get_texture(&self.item.item.get_asset_name());
+        let size = self.item.item.get_inventory_size();
         let size = (
             size.0 as f32 * 1.0 * camera.zoom,
             size.1 as f32 * 1.0 * camera.zoom,
@@ -46,18 +66,47 @@ impl GroundItem {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InventoryItem {
     pub rotated: bool,
     pub size: (u32, u32),
     pub item: Item,
+    // Items in the stack, always 1 for items that don't stack
+    #[serde(default = "default_count")]
+    pub count: u32,
+    // Rounds loaded in a firearm or magazine
+    #[serde(default)]
+    pub rounds: Option<u32>,
+    // Uses left in a consumable, for the top item of a stack
+    #[serde(default)]
+    pub charges: Option<u32>,
+    // From 1 when new down to 0 when worn out
+    #[serde(default)]
+    pub durability: Option<f32>,
+    // Grid carried inside a backpack or rig
+    #[serde(default)]
+    pub container: Option<Container>,
 }
 
-#[derive(Clone, Debug)]
+fn default_count() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Item {
     Rifle,
     Pistol,
     MedKit,
+    Bandage,
+    CannedFood,
+    WaterBottle,
+    EnergyDrink,
+    Magazine(Caliber),
+    Ammo(Caliber),
+    Backpack,
+    Rig,
+    Helmet,
+    BodyArmor,
 }
 impl Item {
     pub fn to_inventory_item(&self, rotated: bool) -> InventoryItem {
@@ -65,11 +114,69 @@ impl Item {
             rotated,
             size: self.get_inventory_size(),
             item: self.clone(),
+            count: self.get_starting_count(),
+            rounds: self.get_starting_rounds(),
+            charges: self.consumable_stats().map(|stats| stats.charges),
+            durability: self.firearm_stats().map(|_| 1.0),
+            container: self.get_container_size().map(Container::new),
+        }
+    }
+
+    // Size of the grid carried by the item, for backpacks and rigs
+    pub fn get_container_size(&self) -> Option<(i32, i32)> {
+        match self {
+            Item::Backpack => Some((6, 5)),
+            Item::Rig => Some((4, 2)),
+            _ => None,
+        }
+    }
+
+    // Items are found fully loaded
+    pub fn get_starting_rounds(&self) -> Option<u32> {
+        match self {
+            Item::Magazine(caliber) => Some(caliber.magazine_size()),
+            _ => self.firearm_stats().map(|stats| stats.magazine_size),
+        }
+    }
+
+    // Loose rounds are found by the box
+    pub fn get_starting_count(&self) -> u32 {
+        match self {
+            Item::Ammo(caliber) => caliber.ammo_box_size(),
+            _ => 1,
+        }
+    }
+
+    pub fn get_max_stack(&self) -> u32 {
+        match self {
+            Item::Ammo(caliber) => caliber.ammo_box_size(),
+            Item::Bandage => 5,
+            _ => 1,
         }
     }
 
     pub fn to_ground_item(&self, pos: Vector2) -> GroundItem {
-        GroundItem { pos, item: self.clone() }
+        self.to_inventory_item(false).to_ground_item(pos)
+    }
+
+    pub fn get_display_name(&self) -> String {
+        match self {
+            Item::Rifle => "Rifle".to_string(),
+            Item::Pistol => "Pistol".to_string(),
+            Item::MedKit => "Medkit".to_string(),
+            Item::Bandage => "Bandage".to_string(),
+            Item::CannedFood => "Canned food".to_string(),
+            Item::WaterBottle => "Water bottle".to_string(),
+            Item::EnergyDrink => "Energy drink".to_string(),
+            Item::Magazine(Caliber::Rifle) => "Rifle magazine".to_string(),
+            Item::Magazine(Caliber::Pistol) => "Pistol magazine".to_string(),
+            Item::Ammo(Caliber::Rifle) => "Rifle ammo".to_string(),
+            Item::Ammo(Caliber::Pistol) => "Pistol ammo".to_string(),
+            Item::Backpack => "Backpack".to_string(),
+            Item::Rig => "Rig".to_string(),
+            Item::Helmet => "Helmet".to_string(),
+            Item::BodyArmor => "Body armor".to_string(),
+        }
     }
 
     pub fn get_inventory_size(&self) -> (u32, u32) {
@@ -77,6 +184,16 @@ impl Item {
             Item::Rifle => (4, 2),
             Item::Pistol => (2, 1),
             Item::MedKit => (3, 2),
+            Item::Bandage => (1, 1),
+            Item::CannedFood => (1, 1),
+            Item::WaterBottle => (1, 2),
+            Item::EnergyDrink => (1, 1),
+            Item::Magazine(_) => (1, 2),
+            Item::Ammo(_) => (1, 1),
+            Item::Backpack => (3, 3),
+            Item::Rig => (3, 2),
+            Item::Helmet => (2, 2),
+            Item::BodyArmor => (3, 3),
         }
     }
 
@@ -85,22 +202,107 @@ impl Item {
             Item::Rifle => "417.png".to_string(),
             Item::Pistol => "pistol.png".to_string(),
             Item::MedKit => "medkit.png".to_string(),
+            Item::Bandage => "bandage.png".to_string(),
+            Item::CannedFood => "canned_food.png".to_string(),
+            Item::WaterBottle => "water_bottle.png".to_string(),
+            Item::EnergyDrink => "energy_drink.png".to_string(),
+            Item::Magazine(Caliber::Rifle) => "rifle_magazine.png".to_string(),
+            Item::Magazine(Caliber::Pistol) => "pistol_magazine.png".to_string(),
+            Item::Ammo(Caliber::Rifle) => "rifle_ammo.png".to_string(),
+            Item::Ammo(Caliber::Pistol) => "pistol_ammo.png".to_string(),
+            Item::Backpack => "backpack.png".to_string(),
+            Item::Rig => "rig.png".to_string(),
+            Item::Helmet => "helmet.png".to_string(),
+            Item::BodyArmor => "body_armor.png".to_string(),
         }
     }
 }
+
+// Returned by `try_insert` with whatever part of the item did not fit
+#[derive(Debug)]
+pub struct InventoryFull(pub InventoryItem);
+
+// One of the grids shown in the inventory screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GridId {
+    Pockets,
+    Rig,
+    Backpack,
+    // The world container opened next to the inventory
+    Loot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemLocation {
+    pub grid: GridId,
+    pub pos: (i32, i32),
+}
+
+// World container being looted, by its index in the game world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LootTarget {
+    Crate(usize),
+    Corpse(usize),
+}
+
 pub struct Inventory {
-    pub items: HashMap<(i32, i32), InventoryItem>,
-    pub selected_item: Option<((i32, i32), InventoryItem, Vector2)>,
+    pub pockets: Container,
+    pub equipment: Equipment,
+    pub selected_item: Option<(ItemLocation, InventoryItem, Vector2)>,
+    // Weapon slot held in hand
+    pub active_weapon: Option<EquipmentSlot>,
+    // Consumable right clicked in the grid, picked up by the player on their next update
+    pub use_requested: Option<ItemLocation>,
+    pub looting: Option<LootTarget>,
 }
 
 impl InventoryItem {
-    fn get_rotation_offset(&self, scale: f32) -> f32 {
-        (self.size.0 as f32 * Inventory::GRID_SPACING - 1.0) / (self.size.0 / self.size.1) as f32
-            * scale
-            - 2.5
+    pub fn to_ground_item(self, pos: Vector2) -> GroundItem {
+        GroundItem { item: self, pos }
+    }
+
+    // Whether `other` can be merged into this stack
+    pub fn can_stack_with(&self, other: &InventoryItem) -> bool {
+        self.item == other.item
+            && self.item.get_max_stack() > 1
+            && self.charges == other.charges
+            && self.durability == other.durability
+    }
+
+    // Rounds this item can give to a reload, loose rounds in a stack or rounds in a magazine
+    pub fn available_rounds(&self) -> u32 {
+        match self.item {
+            Item::Ammo(_) => self.count,
+            _ => self.rounds.unwrap_or(0),
+        }
+    }
+
+    // Text drawn over the item in the grid
+    pub fn get_label(&self) -> Option<String> {
+        if self.item.get_max_stack() > 1 {
+            Some(self.count.to_string())
+        } else if let Some(charges) = self.charges {
+            Some(charges.to_string())
+        } else {
+            self.rounds.map(|rounds| rounds.to_string())
+        }
+    }
+
+    // Cells taken up in the grid, accounting for rotation
+    pub fn grid_size(&self) -> (i32, i32) {
+        if self.rotated {
+            (self.size.1 as i32, self.size.0 as i32)
+        } else {
+            (self.size.0 as i32, self.size.1 as i32)
+        }
+    }
+
+    // Items rotate around their top left corner, so they are shifted right by their height
+    pub fn get_rotation_offset(&self, scale: f32) -> f32 {
+        (self.size.1 as f32 * Container::GRID_SPACING - 1.0) * scale
     }
 
-    fn rotate_back(&self, rect: &Rectangle, scale: f32) -> Rectangle {
+    pub fn rotate_back(&self, rect: &Rectangle, scale: f32) -> Rectangle {
         Rectangle {
             x: rect.x - self.get_rotation_offset(scale),
             y: rect.y,
@@ -111,71 +313,459 @@ impl InventoryItem {
 }
 
 impl Inventory {
-    pub const GRID_SPACING: f32 = 17.0;
-    pub const SIZE: (i32, i32) = (8, 4);
+    pub const POCKETS_SIZE: (i32, i32) = (8, 4);
+    // Grids searched when picking up items, healing and reloading
+    const PLAYER_GRIDS: [GridId; 3] = [GridId::Pockets, GridId::Rig, GridId::Backpack];
+    const MAX_SCALE: f32 = 7.0;
+    // Space between grids and between paper doll slots, before scaling
+    const GRID_GAP: f32 = 8.0;
+    const EQUIPMENT_SLOT_SIZE: f32 = 24.0;
+    // Paper doll of three columns and rows of slots, the character drawn in the middle one
+    const PAPER_DOLL_SIZE: f32 = Self::EQUIPMENT_SLOT_SIZE * 3.0 + Self::GRID_GAP * 2.0;
+
+    pub fn new() -> Self {
+        Inventory {
+            pockets: Container::new(Self::POCKETS_SIZE),
+            equipment: Equipment::default(),
+            selected_item: None,
+            active_weapon: None,
+            use_requested: None,
+            looting: None,
+        }
+    }
+
+    // One of the player's own grids. The loot grid lives in the world.
+    pub fn container(&self, grid: GridId) -> Option<&Container> {
+        match grid {
+            GridId::Pockets => Some(&self.pockets),
+            GridId::Rig => self.equipment.rig.as_ref()?.container.as_ref(),
+            GridId::Backpack => self.equipment.backpack.as_ref()?.container.as_ref(),
+            GridId::Loot => None,
+        }
+    }
+
+    pub fn container_mut(&mut self, grid: GridId) -> Option<&mut Container> {
+        match grid {
+            GridId::Pockets => Some(&mut self.pockets),
+            GridId::Rig => self.equipment.rig.as_mut()?.container.as_mut(),
+            GridId::Backpack => self.equipment.backpack.as_mut()?.container.as_mut(),
+            GridId::Loot => None,
+        }
+    }
+
+    // Same as `container`, but also finds the opened loot container
+    fn grid<'a>(&'a self, grid: GridId, loot: Option<&'a Container>) -> Option<&'a Container> {
+        match grid {
+            GridId::Loot => loot,
+            _ => self.container(grid),
+        }
+    }
+
+    fn grid_mut<'a>(
+        &'a mut self,
+        grid: GridId,
+        loot: Option<&'a mut Container>,
+    ) -> Option<&'a mut Container> {
+        match grid {
+            GridId::Loot => loot,
+            _ => self.container_mut(grid),
+        }
+    }
 
-    pub fn get_item_rect(
+    pub fn get(&self, location: ItemLocation) -> Option<&InventoryItem> {
+        self.container(location.grid)?.items.get(&location.pos)
+    }
+
+    pub fn get_mut(&mut self, location: ItemLocation) -> Option<&mut InventoryItem> {
+        self.container_mut(location.grid)?
+            .items
+            .get_mut(&location.pos)
+    }
+
+    pub fn active_weapon_item(&self) -> Option<&InventoryItem> {
+        self.equipment.get(self.active_weapon?)
+    }
+
+    // Backpacks and rigs can't be carried inside another backpack or rig
+    fn can_hold(grid: GridId, item: &InventoryItem) -> bool {
+        !matches!(grid, GridId::Rig | GridId::Backpack) || item.item.get_container_size().is_none()
+    }
+
+    // Fits the item into the pockets, then the rig, then the backpack
+    pub fn try_insert(&mut self, mut item: InventoryItem) -> Result<ItemLocation, InventoryFull> {
+        for grid in Self::PLAYER_GRIDS {
+            if !Self::can_hold(grid, &item) {
+                continue;
+            }
+            let Some(container) = self.container_mut(grid) else {
+                continue;
+            };
+            match container.try_insert(item) {
+                Ok(pos) => return Ok(ItemLocation { grid, pos }),
+                Err(InventoryFull(rest)) => item = rest,
+            }
+        }
+        Err(InventoryFull(item))
+    }
+
+    // The healing item used by the quick heal key, picking the one closest to the top left
+    // of the first grid that has one `useful` accepts
+    pub fn find_healing_item(
         &self,
-        grid_pos: &(i32, i32),
-        scale: f32,
-        inventory_top_left: Vector2,
-    ) -> Rectangle {
-        let item = &self.items[grid_pos];
-        let rotation_offset = if item.rotated {
-            item.get_rotation_offset(scale)
+        useful: impl Fn(&ConsumableEffect) -> bool,
+    ) -> Option<ItemLocation> {
+        Self::PLAYER_GRIDS.iter().find_map(|grid| {
+            self.container(*grid)?
+                .items
+                .iter()
+                .filter(|(_, item)| {
+                    item.item
+                        .consumable_stats()
+                        .is_some_and(|stats| stats.effect.is_healing() && useful(&stats.effect))
+                })
+                .map(|(grid_pos, _)| *grid_pos)
+                .min_by_key(|grid_pos| (grid_pos.1, grid_pos.0))
+                .map(|pos| ItemLocation { grid: *grid, pos })
+        })
+    }
+
+    pub fn use_charge(&mut self, location: ItemLocation) {
+        if let Some(container) = self.container_mut(location.grid) {
+            container.use_charge(location.pos);
+        }
+    }
+
+    fn rounds_in_grids(&self, item: &Item) -> Vec<(ItemLocation, u32)> {
+        let mut found = vec![];
+        for grid in Self::PLAYER_GRIDS {
+            if let Some(container) = self.container(grid) {
+                found.extend(
+                    container
+                        .rounds_of(item)
+                        .map(|(pos, rounds)| (ItemLocation { grid, pos: *pos }, rounds)),
+                );
+            }
+        }
+        found
+    }
+
+    // The magazine with the most rounds, if it holds more than the firearm already has
+    fn fullest_magazine(&self, caliber: Caliber, rounds_in_weapon: u32) -> Option<ItemLocation> {
+        self.rounds_in_grids(&Item::Magazine(caliber))
+            .into_iter()
+            .max_by_key(|(_, rounds)| *rounds)
+            .filter(|(_, rounds)| *rounds > rounds_in_weapon)
+            .map(|(location, _)| location)
+    }
+
+    // Loaded magazines are preferred, otherwise loose rounds from ammo boxes are used.
+    // Magazines with no more rounds than the firearm already has are not worth swapping in.
+    pub fn get_reload_source(
+        &self,
+        caliber: Caliber,
+        rounds_in_weapon: u32,
+    ) -> Option<ReloadSource> {
+        if self.fullest_magazine(caliber, rounds_in_weapon).is_some() {
+            Some(ReloadSource::Magazine)
+        } else if !self.rounds_in_grids(&Item::Ammo(caliber)).is_empty() {
+            Some(ReloadSource::LooseRounds)
         } else {
-            0.0
+            None
+        }
+    }
+
+    // Takes rounds out of the inventory to fill a firearm, returning its new round count.
+    // A magazine swap puts the old magazine, with whatever was left in it, back in the grid.
+    pub fn take_rounds_for_reload(
+        &mut self,
+        caliber: Caliber,
+        rounds_in_weapon: u32,
+        magazine_size: u32,
+    ) -> u32 {
+        if let Some(location) = self.fullest_magazine(caliber, rounds_in_weapon) {
+            let magazine = self.get_mut(location).unwrap();
+            let loaded = magazine.rounds.unwrap_or(0);
+            magazine.rounds = Some(rounds_in_weapon);
+            return loaded;
+        }
+
+        let mut loaded = rounds_in_weapon;
+        for (location, _) in self.rounds_in_grids(&Item::Ammo(caliber)) {
+            if loaded >= magazine_size {
+                break;
+            }
+            let ammo_stack = self.get_mut(location).unwrap();
+            let taken = ammo_stack.count.min(magazine_size - loaded);
+            loaded += taken;
+            ammo_stack.count -= taken;
+            if ammo_stack.count == 0 {
+                self.container_mut(location.grid)
+                    .unwrap()
+                    .items
+                    .remove(&location.pos);
+            }
+        }
+        loaded
+    }
+
+    // Moves an item within or between grids. Returns whether any item changed place.
+    pub fn transfer(
+        &mut self,
+        from: ItemLocation,
+        to: ItemLocation,
+        rotated: bool,
+        mut loot: Option<&mut Container>,
+    ) -> bool {
+        if from.grid == to.grid {
+            return self.grid_mut(from.grid, loot).is_some_and(|container| {
+                !container.move_item(from.pos, to.pos, rotated).is_empty()
+            });
+        }
+
+        let Some(source) = self.grid_mut(from.grid, loot.as_deref_mut()) else {
+            return false;
         };
-        let (x, y) = (
-            inventory_top_left.x
-                + (grid_pos.0 as f32 * Self::GRID_SPACING * scale)
-                + (4.0 * scale)
-                + rotation_offset,
-            inventory_top_left.y + (grid_pos.1 as f32 * Self::GRID_SPACING * scale) + (4.0 * scale),
-        );
-        Rectangle {
-            x,
-            y,
-            width: (item.size.0 as f32 * Self::GRID_SPACING - 1.0) * scale + 0.1,
-            height: (item.size.1 as f32 * Self::GRID_SPACING - 1.0) * scale,
+        let Some(mut item) = source.items.remove(&from.pos) else {
+            return false;
+        };
+        let original_rotation = item.rotated;
+        item.rotated = rotated;
+        let placed = match self.grid_mut(to.grid, loot.as_deref_mut()) {
+            Some(target) if Self::can_hold(to.grid, &item) => target.place(to.pos, item),
+            _ => Err(InventoryFull(item)),
+        };
+        match placed {
+            Ok(()) => true,
+            Err(InventoryFull(mut rest)) => {
+                rest.rotated = original_rotation;
+                self.grid_mut(from.grid, loot)
+                    .unwrap()
+                    .items
+                    .insert(from.pos, rest);
+                false
+            }
         }
     }
 
-    pub fn get_hovered_rect(
-        &self,
-        scale: f32,
-        inventory_top_left: Vector2,
-        mouse_pos: Vector2,
-    ) -> Option<(&(i32, i32), &InventoryItem)> {
-        for (grid_pos, item) in &self.items {
-            let mut rect = self.get_item_rect(grid_pos, scale, inventory_top_left);
-            if item.rotated {
-                rect = item.rotate_back(&rect, scale);
+    // Moves an item out of a grid into its equipment slot. Whatever was in the slot goes
+    // into the inventory, and nothing changes if there is no room for it.
+    pub fn equip(&mut self, from: ItemLocation, mut loot: Option<&mut Container>) -> bool {
+        let Some(slot) = self
+            .grid(from.grid, loot.as_deref())
+            .and_then(|container| container.items.get(&from.pos))
+            .and_then(|item| item.item.get_equipment_slot())
+        else {
+            return false;
+        };
+        let Some(mut item) = self
+            .grid_mut(from.grid, loot.as_deref_mut())
+            .and_then(|container| container.items.remove(&from.pos))
+        else {
+            return false;
+        };
+        let original_rotation = item.rotated;
+        item.rotated = false;
+        let previous = self.equipment.slot_mut(slot).replace(item);
+        if let Some(previous) = previous {
+            if let Err(InventoryFull(previous)) = self.try_insert(previous) {
+                let mut item = self.equipment.slot_mut(slot).replace(previous).unwrap();
+                item.rotated = original_rotation;
+                self.grid_mut(from.grid, loot)
+                    .unwrap()
+                    .items
+                    .insert(from.pos, item);
+                return false;
+            }
+        }
+        if slot.is_weapon() && self.active_weapon.is_none() {
+            self.active_weapon = Some(slot);
+        }
+        true
+    }
+
+    // Moves an equipped item, with everything in it, into the inventory
+    pub fn unequip(&mut self, slot: EquipmentSlot) -> bool {
+        let Some(item) = self.equipment.slot_mut(slot).take() else {
+            return false;
+        };
+        match self.try_insert(item) {
+            Ok(_) => {
+                if self.active_weapon == Some(slot) {
+                    self.active_weapon = None;
+                }
+                true
             }
-            if rect.check_collision_point_rec(mouse_pos) {
-                return Some((grid_pos, item));
+            Err(InventoryFull(item)) => {
+                *self.equipment.slot_mut(slot) = Some(item);
+                false
+            }
+        }
+    }
+
+    // Everything carried, packed into a single grid of at least `size`. Pocket items keep
+    // their place and equipment goes wherever it fits, backpacks and rigs along with their
+    // contents. The grid grows downwards for anything that doesn't fit.
+    pub fn into_container(self, size: (i32, i32)) -> Container {
+        let mut container = Container::new(size);
+        container.items = self.pockets.items;
+        let mut equipment = self.equipment;
+        for slot in EquipmentSlot::ALL {
+            let Some(mut item) = equipment.slot_mut(slot).take() else {
+                continue;
+            };
+            while let Err(InventoryFull(rest)) = container.try_insert(item) {
+                let (width, height) = rest.grid_size();
+                container.size = (container.size.0.max(width), container.size.1 + height);
+                item = rest;
             }
         }
-        None
+        container
     }
 
-    pub fn render(&mut self, d: &mut RaylibDrawHandle, player: &Player, assets: &Assets, game_world: &mut GameWorld, player_pos: Vector2) {
-        if player.inventory_open {
+    // Scale and top left corner of every shown grid, laid out side by side to the right of
+    // the paper doll
+    fn get_layout(
+        &self,
+        loot: Option<&Container>,
+        screen_size: Vector2,
+    ) -> (f32, Vec<(GridId, Vector2)>) {
+        let grids = [GridId::Pockets, GridId::Rig, GridId::Backpack, GridId::Loot]
+            .into_iter()
+            .filter_map(|grid| {
+                let container = self.grid(grid, loot)?;
+                Some((grid, Container::get_pixel_size(container.size)))
+            })
+            .collect::<Vec<(GridId, Vector2)>>();
+        let width = Self::PAPER_DOLL_SIZE
+            + grids.iter().map(|(_, size)| size.x).sum::<f32>()
+            + Self::GRID_GAP * grids.len() as f32;
+        let height = grids
+            .iter()
+            .map(|(_, size)| size.y)
+            .fold(Self::PAPER_DOLL_SIZE, f32::max);
+        let scale = Self::MAX_SCALE
+            .min(screen_size.x * 0.9 / width)
+            .min(screen_size.y * 0.9 / height);
+
+        let mut top_left = Vector2::new(
+            (screen_size.x - width * scale) / 2.0
+                + (Self::PAPER_DOLL_SIZE + Self::GRID_GAP) * scale,
+            (screen_size.y - height * scale) / 2.0,
+        );
+        let layout = grids
+            .into_iter()
+            .map(|(grid, size)| {
+                let grid_top_left = top_left;
+                top_left.x += (size.x + Self::GRID_GAP) * scale;
+                (grid, grid_top_left)
+            })
+            .collect();
+        (scale, layout)
+    }
+
+    fn get_top_left(layout: &[(GridId, Vector2)], grid: GridId) -> Option<Vector2> {
+        layout
+            .iter()
+            .find(|(other, _)| *other == grid)
+            .map(|(_, top_left)| *top_left)
+    }
+
+    // The paper doll sits to the left of the pockets, top aligned with them
+    fn get_paper_doll_top_left(layout: &[(GridId, Vector2)], scale: f32) -> Option<Vector2> {
+        let pockets_top_left = Self::get_top_left(layout, GridId::Pockets)?;
+        Some(Vector2::new(
+            pockets_top_left.x - (Self::PAPER_DOLL_SIZE + Self::GRID_GAP) * scale,
+            pockets_top_left.y,
+        ))
+    }
+
+    fn get_equipment_slot_rect(
+        slot: EquipmentSlot,
+        scale: f32,
+        doll_top_left: Vector2,
+    ) -> Rectangle {
+        let (column, row) = slot.get_doll_cell();
+        let cell_size = (Self::EQUIPMENT_SLOT_SIZE + Self::GRID_GAP) * scale;
+        Rectangle::new(
+            doll_top_left.x + column as f32 * cell_size,
+            doll_top_left.y + row as f32 * cell_size,
+            Self::EQUIPMENT_SLOT_SIZE * scale,
+            Self::EQUIPMENT_SLOT_SIZE * scale,
+        )
+    }
+
+    fn get_hovered_slot(
+        layout: &[(GridId, Vector2)],
+        scale: f32,
+        mouse_pos: Vector2,
+    ) -> Option<EquipmentSlot> {
+        let doll_top_left = Self::get_paper_doll_top_left(layout, scale)?;
+        EquipmentSlot::ALL.into_iter().find(|slot| {
+            Self::get_equipment_slot_rect(*slot, scale, doll_top_left)
+                .check_collision_point_rec(mouse_pos)
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        open: bool,
+        assets: &Assets,
+        game_world: &mut GameWorld,
+        player_pos: Vector2,
+        bindings: &InputBindings,
+    ) {
+        if open {
             let screen_size =
                 Vector2::new(d.get_screen_width() as f32, d.get_screen_height() as f32);
-            let texture = assets.get_texture("inventory.png");
-            let texture_size = Vector2::new(texture.width() as f32, texture.height() as f32);
-            let scale = 7.0;
-            let inventory_top_left = Vector2::new(screen_size.x / 2.0, screen_size.y / 2.0)
-                - Vector2::new(texture_size.x / 2.0 * scale, texture_size.y / 2.0 * scale);
+            let GameWorld {
+                ground_items,
+                containers,
+                corpses,
+                ..
+            } = game_world;
+            let mut loot = match self.looting {
+                Some(LootTarget::Crate(i)) => {
+                    containers.get_mut(i).map(|crate_| &mut crate_.container)
+                }
+                Some(LootTarget::Corpse(i)) => corpses.get_mut(i).map(|corpse| &mut corpse.loot),
+                None => None,
+            };
+            let (scale, layout) = self.get_layout(loot.as_deref(), screen_size);
 
             self.draw_background(d, screen_size);
-            self.draw_inventory_slots(d, texture, texture_size, scale, inventory_top_left);
+            for (grid, top_left) in &layout {
+                if *grid == GridId::Pockets {
+                    self.draw_inventory_slots(
+                        d,
+                        assets.get_texture("inventory.png"),
+                        scale,
+                        *top_left,
+                    );
+                } else if let Some(container) = self.grid(*grid, loot.as_deref()) {
+                    container.draw_slots(d, scale, *top_left);
+                }
+            }
+            self.draw_paper_doll(d, assets, scale, &layout);
 
-            self.draw_items(d, assets, scale, inventory_top_left);
-            self.draw_selected_item(d, scale, inventory_top_left);
+            for (grid, top_left) in &layout {
+                if let Some(container) = self.grid(*grid, loot.as_deref()) {
+                    container.draw_items(d, assets, scale, *top_left);
+                }
+            }
+            self.draw_selected_item(d, scale, &layout, loot.as_deref());
 
-            let offset = self.handle_item_selection(d, scale, inventory_top_left, assets, game_world, player_pos);
+            let offset = self.handle_item_selection(
+                d,
+                scale,
+                &layout,
+                loot.as_deref_mut(),
+                assets,
+                ground_items,
+                player_pos,
+                bindings,
+            );
             if let Some(item) = &mut self.selected_item {
                 item.2 = offset;
             }
@@ -196,10 +786,10 @@ impl Inventory {
         &self,
         d: &mut RaylibDrawHandle,
         texture: &Texture2D,
-        texture_size: Vector2,
         scale: f32,
         inventory_top_left: Vector2,
     ) {
+        let texture_size = Vector2::new(texture.width() as f32, texture.height() as f32);
         d.draw_texture_pro(
             texture,
             Rectangle {
@@ -220,29 +810,66 @@ impl Inventory {
         );
     }
 
-    fn draw_items(
+    fn draw_paper_doll(
         &self,
         d: &mut RaylibDrawHandle,
         assets: &Assets,
         scale: f32,
-        inventory_top_left: Vector2,
+        layout: &[(GridId, Vector2)],
     ) {
-        for (grid_pos, item) in &self.items {
-            let texture = assets.get_texture(&item.item.get_asset_name());
-            let dest_rect = self.get_item_rect(grid_pos, scale, inventory_top_left);
-            d.draw_texture_pro(
-                texture,
-                Rectangle {
-                    x: 0.0,
-                    y: 0.0,
-                    width: texture.width() as f32,
-                    height: texture.height() as f32,
-                },
-                dest_rect,
-                Vector2::new(0.0, 0.0),
-                if item.rotated { 90.0 } else { 0.0 },
-                Color::new(255, 255, 255, 255),
-            );
+        let Some(doll_top_left) = Self::get_paper_doll_top_left(layout, scale) else {
+            return;
+        };
+        let character_size = Self::EQUIPMENT_SLOT_SIZE * 3.0 * scale;
+        self.equipment.draw_character(
+            d,
+            assets,
+            self.active_weapon,
+            Rectangle::new(
+                doll_top_left.x + Self::PAPER_DOLL_SIZE * scale / 2.0 - character_size / 2.0,
+                doll_top_left.y + Self::PAPER_DOLL_SIZE * scale / 2.0 - character_size / 2.0,
+                character_size,
+                character_size,
+            ),
+            Vector2::zero(),
+            0.0,
+        );
+        for slot in EquipmentSlot::ALL {
+            let rect = Self::get_equipment_slot_rect(slot, scale, doll_top_left);
+            d.draw_rectangle_rec(rect, Color::new(40, 40, 40, 235));
+            let border_color = if self.active_weapon == Some(slot) {
+                Color::new(255, 220, 120, 200)
+            } else {
+                Color::new(80, 80, 80, 235)
+            };
+            d.draw_rectangle_lines_ex(rect, scale / 2.0, border_color);
+            if let Some(item) = self.equipment.get(slot) {
+                // Fit the item inside the slot, keeping its proportions
+                let texture = assets.get_texture(&item.item.get_asset_name());
+                let texture_size = Vector2::new(texture.width() as f32, texture.height() as f32);
+                let fit = (rect.width - scale * 2.0) / texture_size.x.max(texture_size.y);
+                d.draw_texture_pro(
+                    texture,
+                    Rectangle::new(0.0, 0.0, texture_size.x, texture_size.y),
+                    Rectangle::new(
+                        rect.x + rect.width / 2.0 - texture_size.x * fit / 2.0,
+                        rect.y + rect.height / 2.0 - texture_size.y * fit / 2.0,
+                        texture_size.x * fit,
+                        texture_size.y * fit,
+                    ),
+                    Vector2::zero(),
+                    0.0,
+                    Color::WHITE,
+                );
+            } else {
+                d.draw_text(
+                    slot.get_display_name(),
+                    (rect.x + scale) as i32,
+                    (rect.y + scale) as i32,
+                    (4.0 * scale) as i32,
+                    Color::GRAY,
+                );
+            }
         }
     }
 
@@ -250,11 +877,20 @@ impl Inventory {
         &self,
         d: &mut RaylibDrawHandle,
         scale: f32,
-        inventory_top_left: Vector2,
+        layout: &[(GridId, Vector2)],
+        loot: Option<&Container>,
     ) {
-        if let Some(item) = &self.selected_item {
-            let mut dest_rect = self.get_item_rect(&item.0, scale, inventory_top_left);
-            let item = self.items.get(&item.0).unwrap();
+        let Some((location, _, _)) = &self.selected_item else {
+            return;
+        };
+        let (Some(container), Some(top_left)) = (
+            self.grid(location.grid, loot),
+            Self::get_top_left(layout, location.grid),
+        ) else {
+            return;
+        };
+        if let Some(item) = container.items.get(&location.pos) {
+            let mut dest_rect = container.get_item_rect(&location.pos, scale, top_left);
             let rotation = if item.rotated { 90.0 } else { 0.0 };
             dest_rect.width += 0.01; // correcting for rounding
             d.draw_rectangle_pro(
@@ -266,17 +902,22 @@ impl Inventory {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_item_selection(
         &mut self,
         d: &mut RaylibDrawHandle,
         scale: f32,
-        inventory_top_left: Vector2,
+        layout: &[(GridId, Vector2)],
+        mut loot: Option<&mut Container>,
         assets: &Assets,
-        game_world: &mut GameWorld,
+        ground_items: &mut Vec<GroundItem>,
         player_pos: Vector2,
+        bindings: &InputBindings,
     ) -> Vector2 {
         let mut new_mouse_offset = Vector2::zero();
-        if d.is_key_pressed(KeyboardKey::KEY_R) {
+        let mouse_pos = d.get_mouse_position();
+        let right_clicked = d.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT);
+        if bindings.is_pressed(d, Action::RotateItem) {
             if let Some(item) = &mut self.selected_item {
                 if item.1.size.0 != item.1.size.1 {
                     let selected_item = &mut item.1;
@@ -285,78 +926,133 @@ impl Inventory {
             }
         }
         if self.selected_item.is_none() {
-            if let Some((grid_pos, item)) =
-                self.get_hovered_rect(scale, inventory_top_left, d.get_mouse_position())
-            {
-                let mut rect = self.get_item_rect(grid_pos, scale, inventory_top_left);
+            // Left clicking a weapon slot takes that weapon in hand, right clicking any slot
+            // puts its item back in the inventory
+            if let Some(slot) = Self::get_hovered_slot(layout, scale, mouse_pos) {
+                if right_clicked {
+                    self.unequip(slot);
+                } else if d.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT)
+                    && slot.is_weapon()
+                    && self.equipment.get(slot).is_some()
+                {
+                    self.active_weapon = Some(slot);
+                }
+                return new_mouse_offset;
+            }
+            let hovered = layout.iter().find_map(|(grid, top_left)| {
+                let container = self.grid(*grid, loot.as_deref())?;
+                let (grid_pos, item) = container.get_hovered_rect(scale, *top_left, mouse_pos)?;
+                let mut rect = container.get_item_rect(grid_pos, scale, *top_left);
                 if item.rotated {
                     rect = item.rotate_back(&rect, scale);
-                    rect.width += 0.01; // correcting for rounding
                     rect.x += 0.01;
-                } else {
-                    rect.width += 0.01; // correcting for rounding
                 }
+                rect.width += 0.01; // correcting for rounding
+                let location = ItemLocation {
+                    grid: *grid,
+                    pos: *grid_pos,
+                };
+                Some((location, item.clone(), rect))
+            });
+            if let Some((location, item, rect)) = hovered {
                 d.draw_rectangle_rec(rect, Color::new(255, 255, 255, 80));
+                // Only items carried by the player can be used
+                let carried = location.grid != GridId::Loot;
                 if d.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
-                    self.selected_item = Some((*grid_pos, item.clone(), Vector2::zero()));
+                    self.selected_item = Some((location, item, Vector2::zero()));
+                } else if right_clicked && carried && item.item.consumable_stats().is_some() {
+                    self.use_requested = Some(location);
+                } else if right_clicked && item.item.get_equipment_slot().is_some() {
+                    self.equip(location, loot);
+                } else if bindings.is_pressed(d, Action::SplitStack) {
+                    if let Some(container) = self.grid_mut(location.grid, loot) {
+                        container.split_stack(location.pos);
+                    }
                 }
             }
         } else if !d.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
-            self.place_selected_item(scale, game_world, player_pos);
-        } else if let Some((grid_pos, selected_item, mouse_offset)) = &self.selected_item {
+            self.place_selected_item(scale, layout, loot, ground_items, player_pos, mouse_pos);
+        } else if let Some((location, selected_item, mouse_offset)) = &self.selected_item {
             new_mouse_offset += *mouse_offset + d.get_mouse_delta();
-            self.draw_moving_item(
-                d,
-                grid_pos,
-                selected_item,
-                scale,
-                inventory_top_left,
-                new_mouse_offset,
-                assets,
-            );
+            if let (Some(container), Some(top_left)) = (
+                self.grid(location.grid, loot.as_deref()),
+                Self::get_top_left(layout, location.grid),
+            ) {
+                Self::draw_moving_item(
+                    d,
+                    container,
+                    location.pos,
+                    selected_item,
+                    scale,
+                    top_left,
+                    new_mouse_offset,
+                    assets,
+                );
+            }
         }
         new_mouse_offset
     }
 
-    fn place_selected_item(&mut self, scale: f32, game_world: &mut GameWorld, player_pos: Vector2) {
-        if let Some((grid_pos, selected_item, offset)) = &self.selected_item {
-            let new_grid = (
-                (grid_pos.0 as f32 + (offset.x / Self::GRID_SPACING / scale)).round() as i32,
-                (grid_pos.1 as f32 + (offset.y / Self::GRID_SPACING / scale)).round() as i32,
-            );
-            let item_size = if selected_item.rotated {
-                (selected_item.size.1, selected_item.size.0)
-            } else {
-                (selected_item.size.0, selected_item.size.1)
-            };
-            if (new_grid.0 + item_size.0 as i32 - 1) < Self::SIZE.0
-                && (new_grid.1 + item_size.1 as i32 - 1) < Self::SIZE.1
-                && new_grid.0 >= 0
-                && new_grid.1 >= 0
-            {
-                self.items.remove(grid_pos);
-                self.items.insert(new_grid, selected_item.clone());
-            } else {
-                game_world.ground_items.push(selected_item.item.clone().to_ground_item(player_pos));
-                self.items.remove(grid_pos);
+    // Drops the dragged item into the grid or equipment slot under the mouse, or onto the ground
+    // when the mouse is outside all of them. Placements covering other items are rejected and
+    // the item snaps back.
+    fn place_selected_item(
+        &mut self,
+        scale: f32,
+        layout: &[(GridId, Vector2)],
+        mut loot: Option<&mut Container>,
+        ground_items: &mut Vec<GroundItem>,
+        player_pos: Vector2,
+        mouse_pos: Vector2,
+    ) {
+        let Some((from, selected_item, offset)) = self.selected_item.take() else {
+            return;
+        };
+        let Some(from_top_left) = Self::get_top_left(layout, from.grid) else {
+            return;
+        };
+        if let Some(slot) = Self::get_hovered_slot(layout, scale, mouse_pos) {
+            if selected_item.item.get_equipment_slot() == Some(slot) {
+                self.equip(from, loot);
             }
+            return;
+        }
+        let dragged_pos = Container::get_cell_pos(from.pos, scale, from_top_left) + offset;
+        let target = layout.iter().find(|(grid, top_left)| {
+            self.grid(*grid, loot.as_deref()).is_some_and(|container| {
+                Container::get_screen_rect(container.size, scale, *top_left)
+                    .check_collision_point_rec(mouse_pos)
+            })
+        });
+        if let Some((grid, top_left)) = target {
+            let to = ItemLocation {
+                grid: *grid,
+                pos: Container::get_cell_at(dragged_pos, scale, *top_left),
+            };
+            self.transfer(from, to, selected_item.rotated, loot);
+        } else if let Some(item) = self
+            .grid_mut(from.grid, loot)
+            .and_then(|container| container.items.remove(&from.pos))
+        {
+            ground_items.push(item.to_ground_item(player_pos));
         }
-        self.selected_item = None;
     }
 
     #[allow(clippy::too_many_arguments)]
     fn draw_moving_item(
-        &self,
         d: &mut RaylibDrawHandle,
-        grid_pos: &(i32, i32),
+        container: &Container,
+        grid_pos: (i32, i32),
         selected_item: &InventoryItem,
         scale: f32,
-        inventory_top_left: Vector2,
+        top_left: Vector2,
         new_mouse_offset: Vector2,
         assets: &Assets,
     ) {
-        let rect = self.get_item_rect(grid_pos, scale, inventory_top_left);
-        let item = self.items.get(grid_pos).unwrap();
+        let Some(item) = container.items.get(&grid_pos) else {
+            return;
+        };
+        let rect = container.get_item_rect(&grid_pos, scale, top_left);
         let rotation_offset = if selected_item.rotated != item.rotated {
             if selected_item.rotated {
                 selected_item.get_rotation_offset(scale)
@@ -388,3 +1084,275 @@ impl Inventory {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pockets(grid_pos: (i32, i32)) -> ItemLocation {
+        ItemLocation {
+            grid: GridId::Pockets,
+            pos: grid_pos,
+        }
+    }
+
+    #[test]
+    fn try_insert_spills_into_equipped_containers() {
+        let mut inventory = Inventory::new();
+        inventory.equipment.rig = Some(Item::Rig.to_inventory_item(false));
+        for _ in 0..4 {
+            inventory
+                .try_insert(Item::Rifle.to_inventory_item(false))
+                .unwrap();
+        }
+        let location = inventory
+            .try_insert(Item::Magazine(Caliber::Rifle).to_inventory_item(false))
+            .unwrap();
+        assert_eq!(location.grid, GridId::Rig);
+        assert!(inventory
+            .try_insert(Item::Rifle.to_inventory_item(false))
+            .is_err());
+    }
+
+    #[test]
+    fn transfer_moves_between_grids() {
+        let mut inventory = Inventory::new();
+        inventory.equipment.backpack = Some(Item::Backpack.to_inventory_item(false));
+        inventory
+            .pockets
+            .items
+            .insert((0, 0), Item::Pistol.to_inventory_item(false));
+
+        let to = ItemLocation {
+            grid: GridId::Backpack,
+            pos: (1, 1),
+        };
+        assert!(inventory.transfer(pockets((0, 0)), to, true, None));
+        assert!(inventory.pockets.items.is_empty());
+        assert!(inventory.get(to).unwrap().rotated);
+    }
+
+    #[test]
+    fn transfer_into_loot_checks_bounds() {
+        let mut inventory = Inventory::new();
+        let mut loot = Container::new((4, 4));
+        inventory
+            .pockets
+            .items
+            .insert((0, 0), Item::Pistol.to_inventory_item(false));
+
+        let to = ItemLocation {
+            grid: GridId::Loot,
+            pos: (3, 0),
+        };
+        assert!(!inventory.transfer(pockets((0, 0)), to, false, Some(&mut loot)));
+        assert!(inventory.pockets.items.contains_key(&(0, 0)));
+        assert!(inventory.transfer(pockets((0, 0)), to, true, Some(&mut loot)));
+        assert_eq!(loot.items[&(3, 0)].item, Item::Pistol);
+    }
+
+    #[test]
+    fn containers_keep_their_items_when_unequipped() {
+        let mut inventory = Inventory::new();
+        inventory
+            .pockets
+            .items
+            .insert((0, 0), Item::Backpack.to_inventory_item(false));
+        assert!(inventory.equip(pockets((0, 0)), None));
+        let location = inventory
+            .try_insert(Item::Rifle.to_inventory_item(false))
+            .unwrap();
+        assert_eq!(location.grid, GridId::Pockets);
+        inventory
+            .container_mut(GridId::Backpack)
+            .unwrap()
+            .try_insert(Item::Bandage.to_inventory_item(false))
+            .unwrap();
+
+        assert!(inventory.unequip(EquipmentSlot::Backpack));
+        assert!(inventory.equipment.backpack.is_none());
+        let backpack = inventory
+            .pockets
+            .items
+            .values()
+            .find(|item| item.item == Item::Backpack)
+            .unwrap();
+        assert_eq!(backpack.container.as_ref().unwrap().items.len(), 1);
+    }
+
+    #[test]
+    fn equip_swaps_and_takes_weapon_in_hand() {
+        let mut inventory = Inventory::new();
+        inventory
+            .pockets
+            .items
+            .insert((0, 0), Item::Rifle.to_inventory_item(false));
+        inventory
+            .pockets
+            .items
+            .insert((0, 2), Item::Rifle.to_inventory_item(false));
+        inventory.pockets.items.get_mut(&(0, 2)).unwrap().rounds = Some(3);
+
+        assert!(inventory.equip(pockets((0, 0)), None));
+        assert_eq!(inventory.active_weapon, Some(EquipmentSlot::Primary));
+        assert!(inventory.equip(pockets((0, 2)), None));
+        assert_eq!(inventory.active_weapon_item().unwrap().rounds, Some(3));
+        assert_eq!(inventory.pockets.items.len(), 1);
+
+        assert!(inventory.unequip(EquipmentSlot::Primary));
+        assert_eq!(inventory.active_weapon, None);
+        assert!(!inventory.equip(pockets((4, 4)), None));
+    }
+
+    #[test]
+    fn into_container_keeps_pockets_and_equipment() {
+        let mut inventory = Inventory::new();
+        for _ in 0..4 {
+            inventory
+                .try_insert(Item::Rifle.to_inventory_item(false))
+                .unwrap();
+        }
+        inventory.equipment.rig = Some(Item::Rig.to_inventory_item(false));
+        inventory.equipment.backpack = Some(Item::Backpack.to_inventory_item(false));
+        inventory.equipment.body_armor = Some(Item::BodyArmor.to_inventory_item(false));
+        inventory
+            .try_insert(Item::Bandage.to_inventory_item(false))
+            .unwrap();
+
+        let container = inventory.into_container((8, 10));
+        assert_eq!(container.items.len(), 7);
+        assert_eq!(container.items[&(4, 2)].item, Item::Rifle);
+        let rig = container
+            .items
+            .values()
+            .find(|item| item.item == Item::Rig)
+            .unwrap();
+        assert_eq!(rig.container.as_ref().unwrap().items.len(), 1);
+    }
+
+    #[test]
+    fn reload_swaps_in_a_fuller_magazine() {
+        let mut inventory = Inventory::new();
+        let mut magazine = Item::Magazine(Caliber::Rifle).to_inventory_item(false);
+        magazine.rounds = Some(15);
+        let location = inventory.try_insert(magazine).unwrap();
+        inventory
+            .try_insert(Item::Ammo(Caliber::Rifle).to_inventory_item(false))
+            .unwrap();
+
+        assert_eq!(
+            inventory.get_reload_source(Caliber::Rifle, 5),
+            Some(ReloadSource::Magazine)
+        );
+        assert_eq!(inventory.take_rounds_for_reload(Caliber::Rifle, 5, 20), 15);
+        assert_eq!(inventory.get(location).unwrap().rounds, Some(5));
+    }
+
+    #[test]
+    fn reload_tops_up_from_loose_rounds_over_an_emptier_magazine() {
+        let mut inventory = Inventory::new();
+        let mut magazine = Item::Magazine(Caliber::Rifle).to_inventory_item(false);
+        magazine.rounds = Some(10);
+        let location = inventory.try_insert(magazine).unwrap();
+
+        // Swapping would lose rounds and there is nothing else to load
+        assert_eq!(inventory.get_reload_source(Caliber::Rifle, 10), None);
+        assert_eq!(inventory.take_rounds_for_reload(Caliber::Rifle, 10, 20), 10);
+        assert_eq!(inventory.get(location).unwrap().rounds, Some(10));
+
+        let mut ammo = Item::Ammo(Caliber::Rifle).to_inventory_item(false);
+        ammo.count = 4;
+        inventory.try_insert(ammo).unwrap();
+        assert_eq!(
+            inventory.get_reload_source(Caliber::Rifle, 12),
+            Some(ReloadSource::LooseRounds)
+        );
+        assert_eq!(inventory.take_rounds_for_reload(Caliber::Rifle, 12, 20), 16);
+        assert_eq!(inventory.get(location).unwrap().rounds, Some(10));
+        assert!(inventory
+            .rounds_in_grids(&Item::Ammo(Caliber::Rifle))
+            .is_empty());
+    }
+
+    #[test]
+    fn containers_do_not_go_inside_other_containers() {
+        let mut inventory = Inventory::new();
+        inventory.equipment.backpack = Some(Item::Backpack.to_inventory_item(false));
+        inventory
+            .pockets
+            .items
+            .insert((0, 0), Item::Rig.to_inventory_item(false));
+        let to = ItemLocation {
+            grid: GridId::Backpack,
+            pos: (0, 0),
+        };
+        assert!(!inventory.transfer(pockets((0, 0)), to, false, None));
+        assert_eq!(inventory.pockets.items[&(0, 0)].item, Item::Rig);
+
+        // With full pockets there is nowhere left for another container
+        inventory.pockets.items.clear();
+        for _ in 0..4 {
+            inventory
+                .try_insert(Item::Rifle.to_inventory_item(false))
+                .unwrap();
+        }
+        assert!(inventory
+            .try_insert(Item::Rig.to_inventory_item(false))
+            .is_err());
+
+        // Swapping backpacks can't put the old one in the new one
+        let mut loot = Container::new((8, 8));
+        loot.items
+            .insert((0, 0), Item::Backpack.to_inventory_item(false));
+        inventory
+            .container_mut(GridId::Backpack)
+            .unwrap()
+            .try_insert(Item::Bandage.to_inventory_item(false))
+            .unwrap();
+        let from = ItemLocation {
+            grid: GridId::Loot,
+            pos: (0, 0),
+        };
+        assert!(!inventory.equip(from, Some(&mut loot)));
+        assert!(loot.items.contains_key(&(0, 0)));
+        let backpack = inventory.equipment.backpack.as_ref().unwrap();
+        assert_eq!(backpack.container.as_ref().unwrap().items.len(), 1);
+    }
+
+    #[test]
+    fn into_container_keeps_everything_from_a_full_inventory() {
+        let full_inventory = || {
+            let mut inventory = Inventory::new();
+            for _ in 0..4 {
+                inventory
+                    .try_insert(Item::Rifle.to_inventory_item(false))
+                    .unwrap();
+            }
+            let equipment = [
+                Item::Helmet,
+                Item::BodyArmor,
+                Item::Rifle,
+                Item::Backpack,
+                Item::Rig,
+                Item::Pistol,
+            ];
+            for (slot, item) in EquipmentSlot::ALL.into_iter().zip(equipment) {
+                *inventory.equipment.slot_mut(slot) = Some(item.to_inventory_item(false));
+            }
+            inventory
+        };
+
+        let container = full_inventory().into_container(crate::Corpse::LOOT_SIZE);
+        assert_eq!(container.items.len(), 10);
+        assert_eq!(container.size, crate::Corpse::LOOT_SIZE);
+
+        // A grid only the size of the pockets grows to fit the equipment
+        let container = full_inventory().into_container(Inventory::POCKETS_SIZE);
+        assert_eq!(container.items.len(), 10);
+        assert_eq!(container.size.0, Inventory::POCKETS_SIZE.0);
+        assert!(container.size.1 > Inventory::POCKETS_SIZE.1);
+        for (grid_pos, item) in &container.items {
+            assert!(container.can_place(*grid_pos, item.grid_size(), &[*grid_pos]));
+        }
+    }
+}
@@ -1,8 +1,15 @@
 #![allow(dead_code)]
 use raylib::prelude::*;
 use std::collections::HashMap;
+use std::fmt;
 
-use crate::ImprovedCamera;
+use crate::{lighting_renderer::LightingRenderer, ImprovedCamera};
+
+// Most lights that can exist at once, each one takes a column of the light buffer texture
+pub const MAX_LIGHTS: usize = 1024;
+// Texels per light in the light buffer: position, radius and type, then color, then rotation
+// and angle
+pub const LIGHT_DATA_ROWS: usize = 3;
 
 pub const AMBIENT_LIGHT_NIGHT: Light = Light::Ambient {
     color: Vector4::new(0.7, 0.7, 1.0, 0.25),
@@ -135,51 +142,70 @@ impl Light {
 }
 
 // Used to store the shader uniform locations. Each i32 is a loc.
-struct ShaderUniforms {
-    position: i32,
-    color: i32,
+pub struct ShaderUniforms {
     amount: i32,
-    radius: i32,
-    light_type: i32,
     screen_size: i32,
-    rotation: i32,
-    angle: i32,
+    pub lights_data: i32,
+    pub visibility_mask: i32,
+    pub shadow_mask: i32,
+}
+
+impl ShaderUniforms {
+    // Setting the shader locations
+    pub fn new(shader: &mut Shader) -> ShaderUniforms {
+        ShaderUniforms {
+            amount: shader.get_shader_location("lightsAmount"),
+            screen_size: shader.get_shader_location("screenSize"),
+            lights_data: shader.get_shader_location("lightsData"),
+            visibility_mask: shader.get_shader_location("visibilityMask"),
+            shadow_mask: shader.get_shader_location("shadowMask"),
+        }
+    }
 }
 
+#[derive(Debug)]
+pub enum LightError {
+    TooManyLights,
+}
+
+impl fmt::Display for LightError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LightError::TooManyLights => {
+                write!(f, "can't spawn more than {} lights", MAX_LIGHTS)
+            }
+        }
+    }
+}
+
+// Owns the lights. Holds no GPU state so it can be used by the headless simulation.
 pub struct LightEngine {
     lights: HashMap<u32, Light>,
     light_id: u32,
-    shader_uniforms: ShaderUniforms,
 }
 
 pub struct LightHandle(u32);
 
+impl LightHandle {
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
+
 impl LightEngine {
-    // Setting the shader locations
-    pub fn new(shader: &mut Shader) -> LightEngine {
+    pub fn new() -> LightEngine {
         LightEngine {
             lights: HashMap::new(),
             light_id: 0,
-            shader_uniforms: ShaderUniforms {
-                position: shader.get_shader_location("lightsPosition"),
-                color: shader.get_shader_location("lightsColor"),
-                amount: shader.get_shader_location("lightsAmount"),
-                radius: shader.get_shader_location("lightsRadius"),
-                light_type: shader.get_shader_location("lightsType"),
-                rotation: shader.get_shader_location("lightsRotation"),
-                angle: shader.get_shader_location("lightsAngle"),
-                screen_size: shader.get_shader_location("screenSize"),
-            },
         }
     }
-    pub fn spawn_light(&mut self, light: Light) -> Result<LightHandle, ()> {
-        if self.light_id < 400 {
-            self.lights.insert(self.light_id, light);
-            self.light_id += 1;
-            Ok(LightHandle(self.light_id - 1))
-        } else {
-            Err(())
+    pub fn spawn_light(&mut self, light: Light) -> Result<LightHandle, LightError> {
+        if self.lights.len() >= MAX_LIGHTS {
+            return Err(LightError::TooManyLights);
         }
+        self.lights.insert(self.light_id, light);
+        self.light_id += 1;
+        Ok(LightHandle(self.light_id - 1))
     }
 
     pub fn remove_light(&mut self, light_handle: &LightHandle) {
@@ -197,99 +223,136 @@ impl LightEngine {
         self.lights.len()
     }
 
-    // Updating the shader with new uniform values
+    pub fn lights(&self) -> impl Iterator<Item = (u32, &Light)> {
+        self.lights.iter().map(|(id, light)| (*id, light))
+    }
+
+    // Re-inserts a light under a known id, used when loading a save
+    pub fn restore_light(&mut self, id: u32, light: Light) -> LightHandle {
+        self.lights.insert(id, light);
+        self.light_id = self.light_id.max(id + 1);
+        LightHandle(id)
+    }
+
+    // Lights that can light up part of the screen. Ambient lights light up everything.
+    pub fn visible_lights(
+        &self,
+        camera: &Camera2D,
+        screen_size: Vector2,
+    ) -> impl Iterator<Item = &Light> {
+        let screen_rect = Rectangle::new(0.0, 0.0, screen_size.x, screen_size.y);
+        self.lights.values().filter(move |light| match light {
+            Light::Ambient { .. } => true,
+            _ => screen_rect.check_collision_circle_rec(
+                Self::screen_pos(light, camera),
+                light.radius() * camera.zoom,
+            ),
+        })
+    }
+
+    fn screen_pos(light: &Light, camera: &Camera2D) -> Vector2 {
+        (light.pos() + camera.offset) * camera.zoom
+    }
+
+    // Packs the lights on screen into the light buffer texture and updates the shader with it
     pub fn update_shader_values(
         &self,
-        shader: &mut Shader,
+        lighting_renderer: &mut LightingRenderer,
         camera: &Camera2D,
         screen_size: Vector2,
     ) {
-        shader.set_shader_value_v(
-            self.shader_uniforms.position,
-            self.lights
-                .iter()
-                .map(|light| (light.1.pos() + camera.offset) * camera.zoom)
-                .collect::<Vec<Vector2>>()
-                .as_slice(),
-        );
-        shader.set_shader_value_v(
-            self.shader_uniforms.color,
-            self.lights
-                .iter()
-                .map(|light| light.1.color())
-                .collect::<Vec<Vector4>>()
-                .as_slice(),
-        );
-        shader.set_shader_value(self.shader_uniforms.amount, self.lights.len() as i32);
-        shader.set_shader_value_v(
-            self.shader_uniforms.radius,
-            self.lights
-                .iter()
-                .map(|light| light.1.radius() * camera.zoom)
-                .collect::<Vec<f32>>()
-                .as_slice(),
-        );
-        shader.set_shader_value_v(
-            self.shader_uniforms.light_type,
-            self.lights
-                .iter()
-                .map(|light| light.1.light_type())
-                .collect::<Vec<i32>>()
-                .as_slice(),
-        );
-        shader.set_shader_value_v(
-            self.shader_uniforms.rotation,
-            self.lights
-                .iter()
-                .map(|light| light.1.rotation())
-                .collect::<Vec<f32>>()
-                .as_slice(),
-        );
-        shader.set_shader_value_v(
-            self.shader_uniforms.angle,
-            self.lights
-                .iter()
-                .map(|light| light.1.angle())
-                .collect::<Vec<f32>>()
-                .as_slice(),
-        );
-        shader.set_shader_value(self.shader_uniforms.screen_size, screen_size);
+        let mut data = vec![0.0; MAX_LIGHTS * LIGHT_DATA_ROWS * 4];
+        let mut amount = 0;
+        for (i, light) in self.visible_lights(camera, screen_size).enumerate() {
+            let pos = Self::screen_pos(light, camera);
+            let color = light.color();
+            let texels = [
+                [
+                    pos.x,
+                    pos.y,
+                    light.radius() * camera.zoom,
+                    light.light_type() as f32,
+                ],
+                [color.x, color.y, color.z, color.w],
+                [light.rotation(), light.angle(), 0.0, 0.0],
+            ];
+            for (row, texel) in texels.iter().enumerate() {
+                let start = (row * MAX_LIGHTS + i) * 4;
+                data[start..start + 4].copy_from_slice(texel);
+            }
+            amount += 1;
+        }
+        lighting_renderer.update_light_buffer(&data);
+        let shader = &mut lighting_renderer.shader;
+        let shader_uniforms = &lighting_renderer.shader_uniforms;
+        shader.set_shader_value(shader_uniforms.amount, amount);
+        shader.set_shader_value(shader_uniforms.screen_size, screen_size);
     }
 
     pub fn handle_spawning_light(&mut self, rl: &mut RaylibHandle, camera: &Camera2D) {
         let pos = camera.to_world(rl.get_mouse_position());
         let light_radius = Light::default_radial().radius();
-        if rl.is_key_pressed(KeyboardKey::KEY_ONE) {
-            self.spawn_light(Light::Radial {
-                pos,
-                color: Color::WHITE.into(),
-                radius: light_radius,
-            })
-            .unwrap();
-        }
-        if rl.is_key_pressed(KeyboardKey::KEY_TWO) {
-            self.spawn_light(Light::Radial {
-                pos,
-                color: Color::RED.into(),
-                radius: light_radius,
-            })
-            .unwrap();
-        }
-        if rl.is_key_pressed(KeyboardKey::KEY_THREE) {
-            self.spawn_light(Light::Radial {
-                pos,
-                color: Color::BLUE.into(),
-                radius: light_radius,
-            })
-            .unwrap();
-        }
-        if rl.is_key_pressed(KeyboardKey::KEY_FOUR) {
-            self.spawn_light(Light::Radial {
-                pos,
-                color: Color::YELLOW.into(),
-                radius: light_radius,
-            })
-            .unwrap();
+        let color = if rl.is_key_pressed(KeyboardKey::KEY_ONE) {
+            Color::WHITE
+        } else if rl.is_key_pressed(KeyboardKey::KEY_TWO) {
+            Color::RED
+        } else if rl.is_key_pressed(KeyboardKey::KEY_THREE) {
+            Color::BLUE
+        } else if rl.is_key_pressed(KeyboardKey::KEY_FOUR) {
+            Color::YELLOW
+        } else {
+            return;
+        };
+        let light = Light::Radial {
+            pos,
+            color: color.into(),
+            radius: light_radius,
+        };
+        if let Err(err) = self.spawn_light(light) {
+            eprintln!("Failed to spawn light: {}", err);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawning_past_the_limit_fails() {
+        let mut light_engine = LightEngine::new();
+        let handles = (0..MAX_LIGHTS)
+            .map(|_| light_engine.spawn_light(Light::default_radial()).unwrap())
+            .collect::<Vec<LightHandle>>();
+        assert!(matches!(
+            light_engine.spawn_light(Light::default_radial()),
+            Err(LightError::TooManyLights)
+        ));
+        // Removed lights free up room for new ones
+        light_engine.remove_light(&handles[0]);
+        assert!(light_engine.spawn_light(Light::default_radial()).is_ok());
+    }
+
+    #[test]
+    fn only_lights_on_screen_are_visible() {
+        let mut light_engine = LightEngine::new();
+        let camera = Camera2D {
+            offset: Vector2::zero(),
+            target: Vector2::zero(),
+            rotation: 0.0,
+            zoom: 10.0,
+        };
+        let screen_size = Vector2::new(800.0, 600.0);
+        let light = |x: f32| Light::Radial {
+            pos: Vector2::new(x, 30.0),
+            color: Color::WHITE.into(),
+            radius: 5.0,
+        };
+        light_engine.spawn_light(light(40.0)).unwrap();
+        // Centre off screen but light reaching onto it
+        light_engine.spawn_light(light(83.0)).unwrap();
+        light_engine.spawn_light(light(200.0)).unwrap();
+        light_engine.spawn_light(Light::default_ambient()).unwrap();
+        assert_eq!(light_engine.visible_lights(&camera, screen_size).count(), 3);
+    }
+}
@@ -1,18 +1,36 @@
 use raylib::prelude::*;
 
+use crate::{
+    collision_world::CollisionWorld,
+    draw_collider::draw_shape,
+    lighting::{ShaderUniforms, LIGHT_DATA_ROWS, MAX_LIGHTS},
+    visibility::Visibility,
+    ImprovedCamera,
+};
+
 pub struct LightingRenderer {
     pub shader: Shader,
+    pub shader_uniforms: ShaderUniforms,
     pub target: RenderTexture2D,
+    // Float texture holding the lights on screen, one column per light. Unlike uniform arrays
+    // it isn't limited by how many uniforms the GPU has.
+    light_buffer: Texture2D,
+    // White where the player can see, the lighting shader darkens everything else
+    visibility_target: RenderTexture2D,
+    // White where walls block light
+    shadow_target: RenderTexture2D,
 }
 
 impl LightingRenderer {
     pub fn new(rl: &mut RaylibHandle, thread: &RaylibThread) -> Self {
+        let mut shader =
+            rl.load_shader_from_memory(thread, None, Some(include_str!("../shaders/lighting.fs")));
+        let mut light_data =
+            Image::gen_image_color(MAX_LIGHTS as i32, LIGHT_DATA_ROWS as i32, Color::BLACK);
+        light_data.set_format(PixelFormat::PIXELFORMAT_UNCOMPRESSED_R32G32B32A32);
         LightingRenderer {
-            shader: rl.load_shader_from_memory(
-                thread,
-                None,
-                Some(include_str!("../shaders/lighting.fs")),
-            ),
+            shader_uniforms: ShaderUniforms::new(&mut shader),
+            shader,
             target: rl
                 .load_render_texture(
                     thread,
@@ -20,6 +38,21 @@ impl LightingRenderer {
                     rl.get_screen_height() as u32,
                 )
                 .unwrap(),
+            light_buffer: rl.load_texture_from_image(thread, &light_data).unwrap(),
+            visibility_target: rl
+                .load_render_texture(
+                    thread,
+                    rl.get_screen_width() as u32,
+                    rl.get_screen_height() as u32,
+                )
+                .unwrap(),
+            shadow_target: rl
+                .load_render_texture(
+                    thread,
+                    rl.get_screen_width() as u32,
+                    rl.get_screen_height() as u32,
+                )
+                .unwrap(),
         }
     }
 
@@ -30,6 +63,12 @@ impl LightingRenderer {
             self.target = rl
                 .load_render_texture(thread, screen_size.x as u32, screen_size.y as u32)
                 .unwrap();
+            self.visibility_target = rl
+                .load_render_texture(thread, screen_size.x as u32, screen_size.y as u32)
+                .unwrap();
+            self.shadow_target = rl
+                .load_render_texture(thread, screen_size.x as u32, screen_size.y as u32)
+                .unwrap();
         }
     }
 
@@ -38,4 +77,75 @@ impl LightingRenderer {
         d.begin_texture_mode(thread, &mut self.target)
             .clear_background(Color::BLACK);
     }
+
+    // Uploads the packed light data, `LIGHT_DATA_ROWS` rows of `MAX_LIGHTS` RGBA texels.
+    // Like the masks, the texture has to be handed to the shader every frame.
+    pub fn update_light_buffer(&mut self, data: &[f32]) {
+        let bytes = data
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect::<Vec<u8>>();
+        self.light_buffer.update_texture(&bytes);
+        self.shader
+            .set_shader_value_texture(self.shader_uniforms.lights_data, &self.light_buffer);
+    }
+
+    // Draws what the player can see into the visibility mask and hands it to the shader.
+    // Texture uniforms are unbound after every draw, so this is needed each frame.
+    pub fn update_visibility_mask(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        thread: &RaylibThread,
+        camera: &Camera2D,
+        visibility: &Visibility,
+    ) {
+        let mut d = d.begin_texture_mode(thread, &mut self.visibility_target);
+        d.clear_background(Color::BLACK);
+        visibility.render(&mut d, camera, Color::WHITE);
+        drop(d);
+        self.shader.set_shader_value_texture(
+            self.shader_uniforms.visibility_mask,
+            self.visibility_target.texture(),
+        );
+    }
+
+    // Draws the walls on screen into the shadow mask the shader traces light through.
+    // Like the visibility mask, it has to be handed to the shader every frame.
+    pub fn update_shadow_mask(
+        &mut self,
+        d: &mut RaylibDrawHandle,
+        thread: &RaylibThread,
+        camera: &Camera2D,
+        collision_world: &CollisionWorld,
+    ) {
+        d.begin_texture_mode(thread, &mut self.shadow_target)
+            .clear_background(Color::BLACK);
+        let camera_world_rect = camera.get_visible_rect(Vector2::new(
+            d.get_screen_width() as f32,
+            d.get_screen_height() as f32,
+        ));
+        for (_, collider) in collision_world.rapier.collider_set.iter() {
+            let aabb = collider.compute_aabb();
+            let on_screen = camera_world_rect.check_collision_recs(&Rectangle::new(
+                aabb.mins.x,
+                aabb.mins.y,
+                aabb.maxs.x - aabb.mins.x,
+                aabb.maxs.y - aabb.mins.y,
+            ));
+            if on_screen && collision_world.blocks_sight(collider) {
+                draw_shape(
+                    (*collider.position(), collider.shape()),
+                    Color::WHITE,
+                    d,
+                    camera,
+                    thread,
+                    &mut self.shadow_target,
+                );
+            }
+        }
+        self.shader.set_shader_value_texture(
+            self.shader_uniforms.shadow_mask,
+            self.shadow_target.texture(),
+        );
+    }
 }
@@ -1,34 +1,79 @@
-use std::collections::HashMap;
-
 use crate::player::*;
 use crate::rapier_world::*;
 use crate::traits::*;
 use assets::Assets;
+use clock::WorldClock;
 use collision_world::*;
+use container::{Container, WorldContainer};
+use controls_menu::ControlsMenu;
 use debug::DebugInfo;
+use equipment::EquipmentSlot;
 use game_map::GameMap;
+use input::Action;
+use input::{InputBindings, PlayerInput, CONTROLS_PATH};
 use inventory::*;
-use lighting::Light;
-use lighting::LightEngine;
 use lighting_renderer::LightingRenderer;
 use raylib::prelude::*;
+use save::{SaveFile, SAVE_SLOTS};
+use simulation::Simulation;
+use sounds::FootstepSounds;
+use std::str::FromStr;
+use weapon::Caliber;
 use world::*;
 
+mod ai;
 mod assets;
+mod chunks;
+mod clock;
 mod collision_world;
+mod consumable;
+mod container;
+mod controls_menu;
+mod damage;
 mod debug;
 mod draw_collider;
+mod equipment;
 mod game_map;
+mod ground;
+mod input;
+mod interaction;
 mod inventory;
 mod lighting;
 mod lighting_renderer;
+mod nav;
+mod needs;
 mod player;
 mod rapier_world;
+mod save;
+mod simulation;
+mod sounds;
+mod terrain;
 mod traits;
+mod visibility;
+mod weapon;
 mod world;
 mod world_collider;
 
+const HEADLESS_TIME_STEP: f32 = 1.0 / 60.0;
+
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let has_flag = |name: &str| args.iter().any(|arg| arg == name);
+    // `--seed N` plays on a map generated from the seed, a bare `--seed` picks a random one
+    let seed = has_flag("--seed").then(|| arg_value(&args, "--seed").unwrap_or_else(rand::random));
+    // `--export-map` also writes the generated map out, so it can be opened in Tiled
+    if let Some(seed) = seed.filter(|_| has_flag("--export-map")) {
+        match GameMap::export_generated(seed) {
+            Ok(path) => println!("Exported map to {}", path),
+            Err(err) => eprintln!("Failed to export map: {}", err),
+        }
+    }
+    if has_flag("--headless") {
+        let ticks = arg_value(&args, "--ticks").unwrap_or(600);
+        run_headless(ticks, seed);
+        return;
+    }
+
     let (mut rl, thread) = raylib::init()
         .size(1080, 720)
         .resizable()
@@ -40,71 +85,138 @@ fn main() {
         zoom: 50.0,
         ..Default::default()
     };
-    let mut collision_world = CollisionWorld::default();
     let mut lighting_renderer = LightingRenderer::new(&mut rl, &thread);
-    let mut light_engine = LightEngine::new(&mut lighting_renderer.shader);
-    let mut game_world = GameWorld::new();
+    let mut sim = Simulation::new(GameWorld::new());
     let mut debugger = DebugInfo::new();
-    let mut player = Player::new(&mut collision_world, &mut light_engine);
+    let mut bindings = InputBindings::load(CONTROLS_PATH);
+    let mut controls_menu = ControlsMenu::new();
+    let mut save_slot = 1;
     let assets = Assets::new(&mut rl, &thread);
-    let mut debug_colliders = vec![];
-    light_engine
-        .spawn_light(Light::Ambient {
-            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
-        })
-        .unwrap();
+    let audio = RaylibAudio::init_audio_device()
+        .map_err(|err| eprintln!("Failed to open the audio device: {}", err))
+        .ok();
+    let footstep_sounds = FootstepSounds::load(audio.as_ref());
 
-    let map = GameMap::load_map("maps/map.tmx");
-    let mut inventory = Inventory {
-        items: HashMap::new(),
-        selected_item: None,
-    };
+    let mut map = load_map(seed);
+    let inventory = &mut sim.player.inventory;
+    inventory.equipment.primary = Some(Item::Rifle.to_inventory_item(false));
+    inventory.equipment.secondary = Some(Item::Pistol.to_inventory_item(false));
+    inventory.active_weapon = Some(EquipmentSlot::Primary);
     inventory
+        .pockets
         .items
-        .insert((0, 0), Item::Rifle.to_inventory_item(false));
-    inventory
-        .items
-        .insert((4, 0), Item::Pistol.to_inventory_item(true));
-    inventory
-        .items
-        .insert((6, 0), Item::MedKit.to_inventory_item(false));
+        .insert((5, 0), Item::MedKit.to_inventory_item(false));
+    for (grid_pos, item) in [
+        ((0, 2), Item::Magazine(Caliber::Rifle)),
+        ((1, 2), Item::Magazine(Caliber::Rifle)),
+        ((2, 2), Item::Magazine(Caliber::Pistol)),
+        ((3, 2), Item::Ammo(Caliber::Rifle)),
+        ((3, 3), Item::Ammo(Caliber::Pistol)),
+        ((4, 2), Item::Bandage),
+        ((4, 3), Item::Bandage),
+    ] {
+        inventory
+            .pockets
+            .items
+            .insert(grid_pos, item.to_inventory_item(false));
+    }
+    let mut loot = Container::new((6, 4));
+    for (grid_pos, item) in [
+        ((0, 0), Item::Backpack),
+        ((3, 0), Item::Rig),
+        ((3, 2), Item::Ammo(Caliber::Rifle)),
+        ((4, 2), Item::Magazine(Caliber::Pistol)),
+        ((0, 3), Item::CannedFood),
+        ((1, 3), Item::EnergyDrink),
+        ((5, 2), Item::WaterBottle),
+    ] {
+        loot.items.insert(grid_pos, item.to_inventory_item(false));
+    }
+    sim.game_world
+        .containers
+        .push(WorldContainer::new(Vector2::new(5.0, 0.0), loot));
+    let mut armor_crate = Container::new((6, 4));
+    for (grid_pos, item) in [((0, 0), Item::BodyArmor), ((3, 0), Item::Helmet)] {
+        armor_crate
+            .items
+            .insert(grid_pos, item.to_inventory_item(false));
+    }
+    sim.game_world
+        .containers
+        .push(WorldContainer::new(Vector2::new(5.0, 5.0), armor_crate));
 
-    game_world
+    sim.game_world
         .ground_items
         .push(Item::MedKit.to_ground_item(Vector2::new(0.0, 0.0)));
 
-    spawn_debug_colldier_world(&mut debug_colliders, &mut collision_world);
+    if seed.is_some() {
+        sim.player
+            .collider
+            .set_pos(map.spawn_point(), &mut sim.collision_world);
+    }
+    sim.load_map(&map);
 
     while !rl.window_should_close() {
         /*
          * Update
          */
         let mouse_pos = rl.get_mouse_position();
-        debugger.update(&mut rl);
-        //player.apply_collision_damage(&mut collision_world, &mut game_world.bullets);
-        game_world.handle_corpses(&rl);
-        player.handle_controls(&rl, &camera, &mut collision_world);
-        player.handle_shooting(
-            &mut rl,
-            &mut collision_world,
-            &mut game_world.bullets,
-            camera.to_world(mouse_pos),
+        controls_menu.update(&mut rl, &mut bindings);
+        debugger.update(&mut rl, &bindings);
+        let mut input = PlayerInput::from_raylib(
+            &rl,
+            &camera,
+            &bindings,
+            sim.player.collider.get_pos(&sim.collision_world),
+        );
+        let typed_time = debugger.prompt_time(&mut rl, &bindings);
+        if controls_menu.open || debugger.is_prompting() {
+            input = input.aim_only();
+        }
+        // Debug commands for the time of day
+        sim.clock.speed = if debugger.debug && bindings.is_down(&rl, Action::FastForwardTime) {
+            WorldClock::FAST_FORWARD_SPEED
+        } else {
+            1.0
+        };
+        if debugger.debug && bindings.is_pressed(&rl, Action::SkipTime) {
+            sim.clock.skip_to_next_keyframe();
+        }
+        if let Some(time) = typed_time {
+            sim.clock.set_time(time);
+        }
+        sim.update(rl.get_frame_time(), &input);
+        footstep_sounds.play(
+            &sim.game_world.footsteps,
+            sim.player.collider.get_pos(&sim.collision_world),
         );
-        player.update_player_light(&mut light_engine, &mut collision_world);
+        if bindings.is_pressed(&rl, Action::CycleSaveSlot) {
+            save_slot = save_slot % SAVE_SLOTS + 1;
+        }
+        if bindings.is_pressed(&rl, Action::QuickSave) {
+            if let Err(err) = SaveFile::capture(&sim).write(save_slot) {
+                eprintln!("Failed to save slot {}: {}", save_slot, err);
+            }
+        }
+        if bindings.is_pressed(&rl, Action::QuickLoad) {
+            match SaveFile::read(save_slot).and_then(|save| save.restore(&map)) {
+                Ok(restored) => sim = restored,
+                Err(err) => eprintln!("Failed to load slot {}: {}", save_slot, err),
+            }
+        }
         camera.handle_camera_controls(&rl);
         camera.track(
-            player.collider.get_center_of_mass(&collision_world),
+            sim.player.collider.get_center_of_mass(&sim.collision_world),
             Vector2::new(rl.get_screen_width() as f32, rl.get_screen_height() as f32),
         );
-        player.handle_spawning_dunmmies(
-            &rl,
-            &camera,
-            &mut collision_world,
-            &mut game_world,
-            &mut light_engine,
-        );
-        game_world.handle_bullet_physics(&rl, &mut collision_world);
-        collision_world.step(&rl, &mut player, &mut game_world, &mut light_engine);
+        let camera_rect = camera.get_visible_rect(Vector2::new(
+            rl.get_screen_width() as f32,
+            rl.get_screen_height() as f32,
+        ));
+        map.stream(Vector2::new(
+            camera_rect.x + camera_rect.width / 2.0,
+            camera_rect.y + camera_rect.height / 2.0,
+        ));
 
         /*
          * Drawing
@@ -114,8 +226,8 @@ fn main() {
         lighting_renderer.update_target(&mut rl, &thread);
         let mut d = rl.begin_drawing(&thread);
         lighting_renderer.clear_target(&mut d, &thread);
-        light_engine.update_shader_values(
-            &mut lighting_renderer.shader,
+        sim.light_engine.update_shader_values(
+            &mut lighting_renderer,
             &camera,
             Vector2::new(d.get_screen_width() as f32, d.get_screen_height() as f32),
         );
@@ -127,28 +239,31 @@ fn main() {
             &thread,
             &mut lighting_renderer.target,
         );
-        for debug_collider in &debug_colliders {
-            debug_collider.draw(
-                &collision_world,
-                &camera,
-                &mut d,
-                &thread,
-                &mut lighting_renderer.target,
-            );
+        if debugger.debug {
+            sim.nav_grid.render(&mut d, &camera);
+            for wall in sim.chunks.walls() {
+                wall.draw(
+                    &sim.collision_world,
+                    &camera,
+                    &mut d,
+                    &thread,
+                    &mut lighting_renderer.target,
+                );
+            }
         }
-        game_world.render_entities(
+        sim.game_world.render_entities(
             &mut d,
             &thread,
             &mut lighting_renderer,
-            &mut collision_world,
+            &mut sim.collision_world,
             &camera,
             &assets,
-            &player,
+            &sim.visibility,
         );
-        player.render(
+        sim.player.render(
             &mut d,
             &camera,
-            &mut collision_world,
+            &mut sim.collision_world,
             &assets,
             &thread,
             &mut lighting_renderer.target,
@@ -158,21 +273,132 @@ fn main() {
         debugger.add(format!("Game FPS: {}", d.get_fps()));
         debugger.add(format!(
             "Num Colliders: {}",
-            collision_world.rapier.rigid_body_set.len()
+            sim.collision_world.rapier.rigid_body_set.len()
+        ));
+        debugger.add(format!(
+            "Lights: {} on screen / {}",
+            sim.light_engine
+                .visible_lights(
+                    &camera,
+                    Vector2::new(d.get_screen_width() as f32, d.get_screen_height() as f32),
+                )
+                .count(),
+            sim.light_engine.spawned_lights()
+        ));
+        debugger.add(format!(
+            "Time: {}{}",
+            sim.clock.display(),
+            if sim.clock.is_night() { " (night)" } else { "" }
+        ));
+        debugger.add(format!("Health: {:?} ", sim.player.health));
+        debugger.add(format!("Bleeding: {:.1}/s", sim.player.bleeding));
+        let needs = &sim.player.needs;
+        debugger.add(format!(
+            "Hunger: {:.2} Thirst: {:.2} Stamina: {:.2} Fatigue: {:.2}",
+            needs.hunger, needs.thirst, needs.stamina, needs.fatigue
+        ));
+        debugger.add(format!(
+            "Ground: {}{}",
+            sim.player.ground.footstep.as_deref().unwrap_or("none"),
+            if sim.player.is_swimming() {
+                " (swimming)"
+            } else {
+                ""
+            }
         ));
-        debugger.add(format!("Health: {:?} ", player.health));
         debugger.add(format!(
             "Mouse_pos: ({:?}, {:?})",
             camera.to_world(mouse_pos).x,
             camera.to_world(mouse_pos).y,
         ));
-        debugger.add(format!("Corpses: {:?}", game_world.corpses.len(),));
+        debugger.add(format!("Corpses: {:?}", sim.game_world.corpses.len(),));
+        debugger.add(format!("Save slot: {}", save_slot));
+        if let Some(weapon) = &sim.player.weapon {
+            debugger.add(format!(
+                "{:?} ({:?}): {}/{}{}",
+                weapon.item,
+                weapon.fire_mode,
+                weapon.rounds_in_magazine,
+                weapon.stats.magazine_size,
+                if weapon.is_reloading() {
+                    " (reloading)"
+                } else {
+                    ""
+                },
+            ));
+        }
+        if let Some(consumption) = &sim.player.consumption {
+            debugger.add(format!(
+                "Using {:?}: {:.0}%",
+                consumption.item,
+                consumption.progress() * 100.0
+            ));
+        }
+        lighting_renderer.update_visibility_mask(&mut d, &thread, &camera, &sim.visibility);
+        lighting_renderer.update_shadow_mask(&mut d, &thread, &camera, &sim.collision_world);
         let mut sh = d.begin_shader_mode(&lighting_renderer.shader);
         sh.draw_texture(&mut lighting_renderer.target, 0, 0, Color::WHITE);
         drop(sh);
         // UI
-        inventory.render(&mut d, &player, &assets, &mut game_world, player.collider.get_pos(&collision_world));
-        debugger.add(format!("{:?}", inventory.selected_item));
+        if !sim.player.inventory_open {
+            sim.interaction.render(&mut d, &sim.game_world, &bindings);
+        }
+        let player_pos = sim.player.collider.get_pos(&sim.collision_world);
+        sim.player.inventory.render(
+            &mut d,
+            sim.player.inventory_open,
+            &assets,
+            &mut sim.game_world,
+            player_pos,
+            &bindings,
+        );
+        debugger.add(format!("{:?}", sim.player.inventory.selected_item));
         debugger.draw(&mut d);
+        controls_menu.render(&mut d, &bindings);
+    }
+}
+
+// Runs the simulation without opening a window, stepping with a fixed dt
+fn run_headless(ticks: u32, seed: Option<u64>) {
+    let mut sim = Simulation::new(GameWorld::with_seed(0));
+    let map = load_map(seed);
+    if seed.is_some() {
+        sim.player
+            .collider
+            .set_pos(map.spawn_point(), &mut sim.collision_world);
+    }
+    sim.load_map(&map);
+    let input = PlayerInput::default();
+    for _ in 0..ticks {
+        sim.update(HEADLESS_TIME_STEP, &input);
+    }
+    let player_pos = sim.player.collider.get_pos(&sim.collision_world);
+    println!(
+        "Simulated {} ticks: player at ({}, {}), health {}, {} dummies, {} bullets",
+        ticks,
+        player_pos.x,
+        player_pos.y,
+        sim.player.health,
+        sim.game_world.dummies.len(),
+        sim.game_world.bullets.len(),
+    );
+}
+
+// Parsed value following a named argument, such as `--ticks 600`
+fn arg_value<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .and_then(|value| value.parse().ok())
+}
+
+// The hand made map, or one generated from the seed
+fn load_map(seed: Option<u64>) -> GameMap {
+    match seed {
+        Some(seed) => GameMap::generate(seed).unwrap_or_else(|err| {
+            eprintln!("Failed to generate map with seed {}: {}", seed, err);
+            std::process::exit(1)
+        }),
+        None => GameMap::load_map("maps/map.tmx"),
     }
 }
@@ -0,0 +1,365 @@
+use rapier2d::parry::query::intersection_test;
+use rapier2d::prelude::{Ball, Collider, Isometry};
+use raylib::prelude::*;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::{collision_world::CollisionWorld, ImprovedCamera};
+
+type Cell = (i32, i32);
+
+// Where characters can walk, sampled on a grid over the map. Baked from static colliders and
+// unwalkable map tiles, and re-baked where static colliders are added or removed.
+pub struct NavGrid {
+    bounds: Rectangle,
+    width: i32,
+    height: i32,
+    blocked: Vec<bool>,
+    unwalkable_areas: Vec<Rectangle>,
+}
+
+impl NavGrid {
+    pub const CELL_SIZE: f32 = 0.5;
+    // Space kept between the centre of a walking character and walls, its radius
+    const CLEARANCE: f32 = 1.0;
+    // How many cells away a path may start or end when its ends are blocked
+    const MAX_SNAP_DISTANCE: i32 = 4;
+    // A* costs of straight and diagonal steps
+    const STRAIGHT_COST: u32 = 10;
+    const DIAGONAL_COST: u32 = 14;
+
+    pub fn new(bounds: Rectangle, unwalkable_areas: Vec<Rectangle>) -> Self {
+        let width = (bounds.width / Self::CELL_SIZE).ceil() as i32;
+        let height = (bounds.height / Self::CELL_SIZE).ceil() as i32;
+        NavGrid {
+            bounds,
+            width,
+            height,
+            blocked: vec![false; (width * height) as usize],
+            unwalkable_areas,
+        }
+    }
+
+    // Bakes the whole grid from the colliders currently in the world
+    pub fn bake(&mut self, collision_world: &mut CollisionWorld) {
+        collision_world.changed_static_areas.clear();
+        self.bake_area(self.bounds, collision_world);
+    }
+
+    // Re-bakes the areas where static colliders changed since the last update
+    pub fn update(&mut self, collision_world: &mut CollisionWorld) {
+        for area in std::mem::take(&mut collision_world.changed_static_areas) {
+            self.bake_area(area, collision_world);
+        }
+    }
+
+    fn bake_area(&mut self, area: Rectangle, collision_world: &CollisionWorld) {
+        let margin = Self::CLEARANCE + Self::CELL_SIZE;
+        let area = Rectangle::new(
+            area.x - margin,
+            area.y - margin,
+            area.width + margin * 2.0,
+            area.height + margin * 2.0,
+        );
+        let rapier = &collision_world.rapier;
+        let statics = rapier
+            .collider_set
+            .iter()
+            .map(|(_, collider)| collider)
+            .filter(|collider| {
+                collider
+                    .parent()
+                    .is_some_and(|parent| rapier.rigid_body_set[parent].is_fixed())
+            })
+            .filter(|collider| Self::collider_rect(collider).check_collision_recs(&area))
+            .collect::<Vec<&Collider>>();
+        let agent = Ball::new(Self::CLEARANCE);
+        let (min, max) = (
+            self.cell_at(Vector2::new(area.x, area.y)),
+            self.cell_at(Vector2::new(area.x + area.width, area.y + area.height)),
+        );
+        let (min, max) = (
+            (min.0.max(0), min.1.max(0)),
+            (max.0.min(self.width - 1), max.1.min(self.height - 1)),
+        );
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                let center = self.cell_center((x, y));
+                self.blocked[(y * self.width + x) as usize] = statics.iter().any(|collider| {
+                    intersection_test(
+                        &Isometry::translation(center.x, center.y),
+                        &agent,
+                        collider.position(),
+                        collider.shape(),
+                    )
+                    .unwrap_or(false)
+                });
+            }
+        }
+        // Unwalkable tiles block the cells with their centre inside them. Marked tile by tile
+        // rather than checked cell by cell, as generated maps can have thousands of them.
+        let unwalkable_areas = self
+            .unwalkable_areas
+            .iter()
+            .filter(|unwalkable| unwalkable.check_collision_recs(&area))
+            .copied()
+            .collect::<Vec<Rectangle>>();
+        for unwalkable in unwalkable_areas {
+            let first = |start: f32, origin: f32| ((start - origin) / Self::CELL_SIZE - 0.5).ceil();
+            let last = |end: f32, origin: f32| ((end - origin) / Self::CELL_SIZE - 0.5).floor();
+            let (x0, y0) = (
+                first(unwalkable.x, self.bounds.x) as i32,
+                first(unwalkable.y, self.bounds.y) as i32,
+            );
+            let (x1, y1) = (
+                last(unwalkable.x + unwalkable.width, self.bounds.x) as i32,
+                last(unwalkable.y + unwalkable.height, self.bounds.y) as i32,
+            );
+            for y in y0.max(min.1)..=y1.min(max.1) {
+                for x in x0.max(min.0)..=x1.min(max.0) {
+                    self.blocked[(y * self.width + x) as usize] = true;
+                }
+            }
+        }
+    }
+
+    fn collider_rect(collider: &Collider) -> Rectangle {
+        let aabb = collider.compute_aabb();
+        Rectangle::new(
+            aabb.mins.x,
+            aabb.mins.y,
+            aabb.maxs.x - aabb.mins.x,
+            aabb.maxs.y - aabb.mins.y,
+        )
+    }
+
+    fn cell_at(&self, pos: Vector2) -> Cell {
+        (
+            ((pos.x - self.bounds.x) / Self::CELL_SIZE).floor() as i32,
+            ((pos.y - self.bounds.y) / Self::CELL_SIZE).floor() as i32,
+        )
+    }
+
+    fn cell_center(&self, cell: Cell) -> Vector2 {
+        Vector2::new(
+            self.bounds.x + (cell.0 as f32 + 0.5) * Self::CELL_SIZE,
+            self.bounds.y + (cell.1 as f32 + 0.5) * Self::CELL_SIZE,
+        )
+    }
+
+    fn is_walkable(&self, cell: Cell) -> bool {
+        cell.0 >= 0
+            && cell.1 >= 0
+            && cell.0 < self.width
+            && cell.1 < self.height
+            && !self.blocked[(cell.1 * self.width + cell.0) as usize]
+    }
+
+    pub fn is_walkable_at(&self, pos: Vector2) -> bool {
+        self.is_walkable(self.cell_at(pos))
+    }
+
+    // Whether a character can walk in a straight line between the two points
+    pub fn is_clear_line(&self, from: Vector2, to: Vector2) -> bool {
+        let steps = (from.distance_to(to) / (Self::CELL_SIZE / 2.0)).ceil() as i32;
+        (0..=steps).all(|step| {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f32 / steps as f32
+            };
+            self.is_walkable_at(from.lerp(to, t))
+        })
+    }
+
+    // Closest walkable cell, for characters standing right against a wall
+    fn nearest_walkable(&self, cell: Cell) -> Option<Cell> {
+        (0..=Self::MAX_SNAP_DISTANCE).find_map(|distance| {
+            (-distance..=distance)
+                .flat_map(|dy| (-distance..=distance).map(move |dx| (dx, dy)))
+                .filter(|(dx, dy)| dx.abs().max(dy.abs()) == distance)
+                .map(|(dx, dy)| (cell.0 + dx, cell.1 + dy))
+                .filter(|cell| self.is_walkable(*cell))
+                .min_by_key(|other| (other.0 - cell.0).pow(2) + (other.1 - cell.1).pow(2))
+        })
+    }
+
+    // Waypoints from `start` to `goal` going around walls, or None if the goal can't be reached.
+    // The first waypoint is `start` and the last is `goal`, or the closest walkable point to it.
+    pub fn find_path(&self, start: Vector2, goal: Vector2) -> Option<Vec<Vector2>> {
+        let start_cell = self.nearest_walkable(self.cell_at(start))?;
+        let goal_cell = self.nearest_walkable(self.cell_at(goal))?;
+        let cells = self.search(start_cell, goal_cell)?;
+        let mut waypoints = vec![start];
+        waypoints.extend(
+            cells
+                .iter()
+                .skip(1)
+                .take(cells.len().saturating_sub(2))
+                .map(|cell| self.cell_center(*cell)),
+        );
+        // Stop short of goals inside walls rather than walking into them
+        waypoints.push(if self.is_walkable_at(goal) {
+            goal
+        } else {
+            self.cell_center(goal_cell)
+        });
+        Some(self.smooth(&waypoints))
+    }
+
+    // A* over the grid, moving diagonally only where neither side is blocked
+    fn search(&self, start: Cell, goal: Cell) -> Option<Vec<Cell>> {
+        let heuristic = |cell: Cell| {
+            let dx = (cell.0 - goal.0).unsigned_abs();
+            let dy = (cell.1 - goal.1).unsigned_abs();
+            Self::STRAIGHT_COST * dx.max(dy)
+                + (Self::DIAGONAL_COST - Self::STRAIGHT_COST) * dx.min(dy)
+        };
+        let mut open = BinaryHeap::from([Reverse((heuristic(start), start))]);
+        let mut costs = HashMap::from([(start, 0)]);
+        let mut came_from = HashMap::new();
+        while let Some(Reverse((_, cell))) = open.pop() {
+            if cell == goal {
+                let mut path = vec![cell];
+                while let Some(previous) = came_from.get(path.last().unwrap()) {
+                    path.push(*previous);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let cost = costs[&cell];
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let next = (cell.0 + dx, cell.1 + dy);
+                    if (dx, dy) == (0, 0) || !self.is_walkable(next) {
+                        continue;
+                    }
+                    let diagonal = dx != 0 && dy != 0;
+                    if diagonal
+                        && !(self.is_walkable((cell.0 + dx, cell.1))
+                            && self.is_walkable((cell.0, cell.1 + dy)))
+                    {
+                        continue;
+                    }
+                    let next_cost = cost
+                        + if diagonal {
+                            Self::DIAGONAL_COST
+                        } else {
+                            Self::STRAIGHT_COST
+                        };
+                    if costs.get(&next).is_some_and(|known| *known <= next_cost) {
+                        continue;
+                    }
+                    costs.insert(next, next_cost);
+                    came_from.insert(next, cell);
+                    open.push(Reverse((next_cost + heuristic(next), next)));
+                }
+            }
+        }
+        None
+    }
+
+    // Skips every waypoint that can be cut past in a straight line
+    fn smooth(&self, waypoints: &[Vector2]) -> Vec<Vector2> {
+        let mut smoothed = vec![waypoints[0]];
+        let mut i = 0;
+        while i < waypoints.len() - 1 {
+            i = (i + 2..waypoints.len())
+                .rev()
+                .find(|j| self.is_clear_line(waypoints[i], waypoints[*j]))
+                .unwrap_or(i + 1);
+            smoothed.push(waypoints[i]);
+        }
+        smoothed
+    }
+
+    // Shades blocked cells on screen, for debugging
+    pub fn render(&self, d: &mut impl RaylibDraw, camera: &Camera2D) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.is_walkable((x, y)) {
+                    continue;
+                }
+                let center = self.cell_center((x, y));
+                d.draw_rectangle_rec(
+                    camera.to_screen_rect(&Rectangle::new(
+                        center.x - Self::CELL_SIZE / 2.0,
+                        center.y - Self::CELL_SIZE / 2.0,
+                        Self::CELL_SIZE,
+                        Self::CELL_SIZE,
+                    )),
+                    Color::new(255, 0, 0, 40),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision_world::ShapeArgs;
+
+    fn path_is_clear(nav_grid: &NavGrid, path: &[Vector2]) -> bool {
+        path.windows(2)
+            .all(|segment| nav_grid.is_clear_line(segment[0], segment[1]))
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let mut collision_world = CollisionWorld::default();
+        // Wall from the top edge down to y = 7, leaving a gap below it
+        collision_world.spawn_static(
+            Vector2::new(10.0, 3.5),
+            ShapeArgs::Cuboid {
+                half_extents: Vector2::new(0.5, 3.5),
+            },
+        );
+        let mut nav_grid = NavGrid::new(Rectangle::new(0.0, 0.0, 20.0, 12.0), vec![]);
+        nav_grid.bake(&mut collision_world);
+
+        let start = Vector2::new(5.0, 3.0);
+        let goal = Vector2::new(15.0, 3.0);
+        assert!(!nav_grid.is_clear_line(start, goal));
+        let path = nav_grid.find_path(start, goal).unwrap();
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path_is_clear(&nav_grid, &path));
+        assert!(path.iter().any(|point| point.y > 7.0));
+        // Smoothing leaves only the corners around the end of the wall
+        assert!(path.len() < 8, "{:?}", path);
+    }
+
+    #[test]
+    fn removing_a_wall_rebakes_the_grid() {
+        let mut collision_world = CollisionWorld::default();
+        let wall = collision_world.spawn_static(
+            Vector2::new(10.0, 6.0),
+            ShapeArgs::Cuboid {
+                half_extents: Vector2::new(0.5, 6.0),
+            },
+        );
+        let mut nav_grid = NavGrid::new(Rectangle::new(0.0, 0.0, 20.0, 12.0), vec![]);
+        nav_grid.bake(&mut collision_world);
+        let start = Vector2::new(5.0, 6.0);
+        let goal = Vector2::new(15.0, 6.0);
+        assert_eq!(nav_grid.find_path(start, goal), None);
+
+        collision_world.delete_collider(wall);
+        nav_grid.update(&mut collision_world);
+        assert_eq!(nav_grid.find_path(start, goal), Some(vec![start, goal]));
+    }
+
+    #[test]
+    fn unwalkable_areas_block_paths() {
+        let mut collision_world = CollisionWorld::default();
+        let water = Rectangle::new(8.0, 0.0, 4.0, 12.0);
+        let mut nav_grid = NavGrid::new(Rectangle::new(0.0, 0.0, 20.0, 12.0), vec![water]);
+        nav_grid.bake(&mut collision_world);
+        assert!(!nav_grid.is_walkable_at(Vector2::new(10.0, 6.0)));
+        assert_eq!(
+            nav_grid.find_path(Vector2::new(5.0, 6.0), Vector2::new(15.0, 6.0)),
+            None
+        );
+    }
+}
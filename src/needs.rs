@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+
+// What a character did during the last update, sets how fast its needs change
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Activity {
+    #[default]
+    Resting,
+    Walking,
+    Sprinting,
+}
+
+// Survival needs of a character. Hunger, thirst and fatigue go from 0 when satisfied up to 1,
+// stamina from 1 when rested down to 0. Past their thresholds they slow the character down
+// and shake its aim, and fully starved or dehydrated characters lose health.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Needs {
+    pub hunger: f32,
+    pub thirst: f32,
+    pub stamina: f32,
+    pub fatigue: f32,
+    // Ran out of stamina, can't sprint until enough of it comes back
+    exhausted: bool,
+    #[serde(skip)]
+    activity: Activity,
+}
+
+impl Default for Needs {
+    fn default() -> Self {
+        Needs {
+            hunger: 0.0,
+            thirst: 0.0,
+            stamina: 1.0,
+            fatigue: 0.0,
+            exhausted: false,
+            activity: Activity::Resting,
+        }
+    }
+}
+
+impl Needs {
+    // Per second
+    const HUNGER_RATE: f32 = 1.0 / 2400.0;
+    const THIRST_RATE: f32 = 1.0 / 1500.0;
+    const FATIGUE_RATE: f32 = 1.0 / 3000.0;
+    // Sprinting tires the character out this many times faster
+    const SPRINT_FATIGUE_FACTOR: f32 = 3.0;
+    const FATIGUE_RECOVERY: f32 = 1.0 / 600.0;
+    const STAMINA_DRAIN: f32 = 1.0 / 6.0;
+    const STAMINA_RECOVERY: f32 = 1.0 / 8.0;
+    // Stamina comes back at this fraction of the rate while walking
+    const WALKING_RECOVERY_FACTOR: f32 = 0.5;
+    // Stamina needed to sprint again after running out
+    const EXHAUSTION_RECOVERY: f32 = 0.3;
+    // Levels past which a need starts applying penalties
+    const HUNGRY: f32 = 0.75;
+    const THIRSTY: f32 = 0.75;
+    const TIRED: f32 = 0.75;
+    const WINDED: f32 = 0.25;
+    // Health lost per second when fully starved or dehydrated
+    const STARVING_DAMAGE: f32 = 0.5;
+    const DEHYDRATED_DAMAGE: f32 = 1.0;
+
+    pub fn can_sprint(&self) -> bool {
+        !self.exhausted
+    }
+
+    // Drains stamina while sprinting and brings it back otherwise. Run for every character.
+    pub fn update_stamina(&mut self, dt: f32, activity: Activity) {
+        self.activity = activity;
+        // Tired characters catch their breath slower
+        let recovery = Self::STAMINA_RECOVERY * (1.0 - self.fatigue * 0.5);
+        self.stamina += match activity {
+            Activity::Sprinting => -Self::STAMINA_DRAIN,
+            Activity::Walking => recovery * Self::WALKING_RECOVERY_FACTOR,
+            Activity::Resting => recovery,
+        } * dt;
+        self.stamina = self.stamina.clamp(0.0, 1.0);
+        if self.stamina == 0.0 {
+            self.exhausted = true;
+        } else if self.stamina >= Self::EXHAUSTION_RECOVERY {
+            self.exhausted = false;
+        }
+    }
+
+    // Builds up hunger, thirst and fatigue, with fatigue wearing off at rest.
+    // Only run for the player, so dummies don't starve.
+    pub fn update_metabolism(&mut self, dt: f32) {
+        self.hunger = (self.hunger + Self::HUNGER_RATE * dt).min(1.0);
+        self.thirst = (self.thirst + Self::THIRST_RATE * dt).min(1.0);
+        let fatigue_rate = match self.activity {
+            Activity::Sprinting => Self::FATIGUE_RATE * Self::SPRINT_FATIGUE_FACTOR,
+            Activity::Walking => Self::FATIGUE_RATE,
+            Activity::Resting => -Self::FATIGUE_RECOVERY,
+        };
+        self.fatigue = (self.fatigue + fatigue_rate * dt).clamp(0.0, 1.0);
+    }
+
+    // Lowers hunger, thirst and fatigue, as eating and drinking does
+    pub fn nourish(&mut self, hunger: f32, thirst: f32, fatigue: f32) {
+        self.hunger = (self.hunger - hunger).clamp(0.0, 1.0);
+        self.thirst = (self.thirst - thirst).clamp(0.0, 1.0);
+        self.fatigue = (self.fatigue - fatigue).clamp(0.0, 1.0);
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        let mut multiplier = 1.0;
+        if self.hunger >= Self::HUNGRY {
+            multiplier *= 0.9;
+        }
+        if self.fatigue >= Self::TIRED {
+            multiplier *= 0.85;
+        }
+        multiplier
+    }
+
+    // Scales weapon spread, out of breath or tired characters can't hold their aim steady
+    pub fn spread_multiplier(&self) -> f32 {
+        let mut multiplier = 1.0;
+        if self.thirst >= Self::THIRSTY {
+            multiplier *= 1.25;
+        }
+        if self.fatigue >= Self::TIRED {
+            multiplier *= 1.25;
+        }
+        if self.stamina <= Self::WINDED {
+            multiplier *= 1.5;
+        }
+        multiplier
+    }
+
+    // Health lost per second
+    pub fn health_drain(&self) -> f32 {
+        let mut drain = 0.0;
+        if self.hunger >= 1.0 {
+            drain += Self::STARVING_DAMAGE;
+        }
+        if self.thirst >= 1.0 {
+            drain += Self::DEHYDRATED_DAMAGE;
+        }
+        drain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_out_of_stamina_stops_sprinting_until_it_recovers() {
+        let mut needs = Needs::default();
+        needs.update_stamina(7.0, Activity::Sprinting);
+        assert_eq!(needs.stamina, 0.0);
+        assert!(!needs.can_sprint());
+        assert!(needs.spread_multiplier() > 1.0);
+
+        needs.update_stamina(1.0, Activity::Resting);
+        assert!(!needs.can_sprint());
+        needs.update_stamina(2.0, Activity::Resting);
+        assert!(needs.can_sprint());
+    }
+
+    #[test]
+    fn needs_past_their_thresholds_apply_penalties() {
+        let mut needs = Needs::default();
+        assert_eq!(needs.speed_multiplier(), 1.0);
+        assert_eq!(needs.spread_multiplier(), 1.0);
+
+        needs.hunger = 0.8;
+        needs.fatigue = 0.8;
+        assert!(needs.speed_multiplier() < 0.8);
+        assert_eq!(needs.health_drain(), 0.0);
+
+        // Sitting around long enough starves and dehydrates the character
+        needs.update_metabolism(10000.0);
+        assert_eq!((needs.hunger, needs.thirst), (1.0, 1.0));
+        assert_eq!(needs.fatigue, 0.0);
+        assert!(needs.health_drain() > 0.0);
+
+        needs.nourish(0.5, 2.0, 0.0);
+        assert_eq!((needs.hunger, needs.thirst), (0.5, 0.0));
+        assert_eq!(needs.health_drain(), 0.0);
+    }
+}
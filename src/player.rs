@@ -1,7 +1,18 @@
+use crate::ai::Brain;
 use crate::collision_world::*;
+use crate::consumable::{Consumption, ConsumptionEvent};
+use crate::container::Container;
+use crate::damage::{Hit, Wound};
+use crate::equipment::EquipmentSlot;
+use crate::ground::{Footstep, Ground, GroundMap};
+use crate::input::PlayerInput;
+use crate::inventory::{Inventory, Item};
 use crate::lighting::Light;
 use crate::lighting::LightEngine;
+use crate::lighting::LightError;
 use crate::lighting::LightHandle;
+use crate::needs::{Activity, Needs};
+use crate::weapon::{Bullet, Caliber, Weapon, WeaponEvent};
 use crate::world_collider::*;
 use crate::Assets;
 use crate::GameWorld;
@@ -16,17 +27,27 @@ pub struct Corpse {
     pub animation_stage: i32,
     pub time_elapsed: f32,
     pub angle: f32,
+    // Everything the character carried when they died
+    pub loot: Container,
+    pub time_since_death: f32,
 }
 
 impl Corpse {
     const ANIMATION_FRAME_TIME: f32 = 0.1;
-    pub fn update_animation(&mut self, rl: &RaylibHandle) {
+    // Fits a full set of pockets with a rig and backpack below them
+    pub const LOOT_SIZE: (i32, i32) = (8, 10);
+    // Distance from the player at which the corpse can be searched
+    pub const SEARCH_RADIUS: f32 = 3.0;
+    // Seconds before corpses despawn, unless the game world is configured otherwise
+    pub const DEFAULT_DESPAWN_TIME: f32 = 300.0;
+
+    pub fn update_animation(&mut self, dt: f32) {
         if (1..4).contains(&self.animation_stage) {
             if self.time_elapsed > Self::ANIMATION_FRAME_TIME {
                 self.animation_stage += 1;
                 self.time_elapsed = 0.0;
             } else {
-                self.time_elapsed += rl.get_frame_time();
+                self.time_elapsed += dt;
             }
         }
     }
@@ -71,44 +92,99 @@ pub struct Player {
     pub collider: WorldColliderHandle,
     pub angle: f32,
     pub health: f32,
-    pub time_since_shot: f32,
     pub inventory_open: bool,
     pub player_light: LightHandle,
+    pub inventory: Inventory,
+    pub weapon: Option<Weapon>,
+    pub consumption: Option<Consumption>,
+    // Health lost per second from open wounds
+    pub bleeding: f32,
+    // Drives the character when it isn't controlled by the player
+    pub brain: Option<Brain>,
+    // What the character is standing on, updated as it moves
+    pub ground: Ground,
+    // Distance moved since the last footstep
+    pub stride: f32,
+    pub needs: Needs,
 }
 
 impl Player {
+    pub const MAX_HEALTH: f32 = 100.0;
+    pub const SPAWN_POS: Vector2 = Vector2 { x: 20.0, y: 20.0 };
+    // Health lost per second with every wound open
+    const MAX_BLEEDING: f32 = 5.0;
     const WALKING_SPEED: f32 = 4.5;
     const SPRINTING_SPEED: f32 = 8.5;
     const WALKING_ACCELERATION: f32 = 20.0;
+    const STRIDE_LENGTH: f32 = 2.0;
+    const SPRITE_SIZE: f32 = 64.0;
+    const SPRITE_SCALE: f32 = 0.1;
     //const WALKING_DEACCELERATION: f32 = 18.0;
 
-    pub fn new(collision_world: &mut CollisionWorld, light_engine: &mut LightEngine) -> Self {
-        let pos = Vector2::new(20.0, 20.0);
+    pub fn new(
+        collision_world: &mut CollisionWorld,
+        light_engine: &mut LightEngine,
+    ) -> Result<Self, LightError> {
+        let pos = Self::SPAWN_POS;
+        let player_light = Self::spawn_light(light_engine, pos)?;
+        Ok(Self::with_light(collision_world, pos, player_light))
+    }
+
+    // Spawns the light following the character, also used to wake dummies up in a loaded chunk
+    pub fn spawn_light(
+        light_engine: &mut LightEngine,
+        pos: Vector2,
+    ) -> Result<LightHandle, LightError> {
+        light_engine.spawn_light(Light::Radial {
+            pos,
+            color: Vector4::new(1.0, 1.0, 1.0, 0.0),
+            radius: 15.0,
+        })
+    }
+
+    // Spawns the player's collider around an existing light
+    pub fn with_light(
+        collision_world: &mut CollisionWorld,
+        pos: Vector2,
+        player_light: LightHandle,
+    ) -> Self {
         Player {
-            collider: collision_world.spawn_collider(
-                RigidBodyArgs {
-                    dynamic: true,
-                    pos,
-                    vel: Vector2::zero(),
-                    user_data: 0,
-                },
-                ColliderArgs::default(),
-                ShapeArgs::Ball { radius: 1.0 },
-            ),
-            health: 100.0,
+            collider: Self::spawn_body(collision_world, pos),
+            health: Self::MAX_HEALTH,
             angle: 0.0,
-            time_since_shot: 0.0,
             inventory_open: false,
-            player_light: light_engine
-                .spawn_light(Light::Radial {
-                    pos,
-                    color: Vector4::new(1.0, 1.0, 1.0, 0.0),
-                    radius: 15.0,
-                })
-                .unwrap(),
+            player_light,
+            inventory: Inventory::new(),
+            weapon: None,
+            consumption: None,
+            bleeding: 0.0,
+            brain: None,
+            ground: Ground::default(),
+            stride: 0.0,
+            needs: Needs::default(),
         }
     }
 
+    // Spawns the character's physics body, also used to wake dummies up in a loaded chunk
+    pub fn spawn_body(collision_world: &mut CollisionWorld, pos: Vector2) -> WorldColliderHandle {
+        collision_world.spawn_collider(
+            RigidBodyArgs {
+                dynamic: true,
+                pos,
+                vel: Vector2::zero(),
+                user_data: 0,
+            },
+            ColliderArgs::default(),
+            ShapeArgs::Ball { radius: 1.0 },
+        )
+    }
+
+    // Direction the character looks in, the sprite faces down at an angle of 0
+    pub fn facing(&self) -> Vector2 {
+        let angle = (self.angle + 90.0).to_radians();
+        Vector2::new(angle.cos(), angle.sin())
+    }
+
     pub fn aim_at(&mut self, world_pos: Vector2, collision_world: &mut CollisionWorld) {
         self.angle = self
             .collider
@@ -120,45 +196,62 @@ impl Player {
 
     pub fn handle_controls(
         &mut self,
-        rl: &RaylibHandle,
-        camera: &Camera2D,
+        input: &PlayerInput,
+        dt: f32,
         collision_world: &mut CollisionWorld,
+        ground_map: &GroundMap,
     ) {
+        let ground = ground_map.ground_at(self.collider.get_pos(collision_world));
+        if *ground != self.ground {
+            self.ground = ground.clone();
+        }
         let mut movement_vector = Vector2::new(0.0, 0.0);
         if !self.inventory_open {
-            if rl.is_key_down(KeyboardKey::KEY_W) {
-                movement_vector.y -= 1.0;
-            }
-            if rl.is_key_down(KeyboardKey::KEY_S) {
-                movement_vector.y += 1.0;
-            }
-            if rl.is_key_down(KeyboardKey::KEY_A) {
-                movement_vector.x -= 1.0;
-            }
-            if rl.is_key_down(KeyboardKey::KEY_D) {
-                movement_vector.x += 1.0;
-            }
-            self.aim_at(camera.to_world(rl.get_mouse_position()), collision_world);
+            movement_vector = input.movement;
+            self.aim_at(input.aim_pos, collision_world);
         }
-        if rl.is_key_pressed(KeyboardKey::KEY_I) {
+        if input.toggle_inventory {
             self.inventory_open = !self.inventory_open;
         }
-        self.handle_movement(rl, collision_world, &mut movement_vector);
+        if let Some(slot) = input.select_weapon {
+            self.select_weapon(slot);
+        }
+        // Can't sprint while swimming or out of stamina
+        let activity = if movement_vector == Vector2::zero() {
+            Activity::Resting
+        } else if input.sprinting && self.needs.can_sprint() && !self.is_swimming() {
+            Activity::Sprinting
+        } else {
+            Activity::Walking
+        };
+        self.needs.update_stamina(dt, activity);
+        self.handle_movement(
+            dt,
+            collision_world,
+            &mut movement_vector,
+            activity == Activity::Sprinting,
+        );
     }
 
+    // Speed is scaled by the ground, and friction sets how fast the character gets up to it
     pub fn handle_movement(
         &mut self,
-        rl: &RaylibHandle,
+        dt: f32,
         collision_world: &mut CollisionWorld,
         movement_vector: &mut Vector2,
+        sprinting: bool,
     ) {
-        let player_speed = Self::WALKING_ACCELERATION * self.collider.get_mass(collision_world);
-        let player_acceleration = player_speed * rl.get_frame_time();
-        let player_max_speed = match rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+        let player_speed = Self::WALKING_ACCELERATION
+            * self.ground.friction
+            * self.collider.get_mass(collision_world);
+        let player_acceleration = player_speed * dt;
+        let player_max_speed = match sprinting {
             false => Self::WALKING_SPEED,
             true => Self::SPRINTING_SPEED,
-        };
-        let player_drag = player_speed / player_max_speed * rl.get_frame_time();
+        } * self.inventory.equipment.speed_multiplier()
+            * self.ground.speed_multiplier
+            * self.needs.speed_multiplier();
+        let player_drag = player_speed / player_max_speed * dt;
         let drag_vector = -self.collider.get_linvel(collision_world);
         self.collider
             .apply_impulse(drag_vector * player_drag, collision_world);
@@ -167,6 +260,19 @@ impl Player {
             .apply_impulse(*movement_vector * player_acceleration, collision_world);
     }
 
+    // A footstep for every stride walked or stroke swum, sounding like the ground under it
+    pub fn take_step(&mut self, dt: f32, collision_world: &CollisionWorld) -> Option<Footstep> {
+        self.stride += self.collider.get_linvel(collision_world).length() * dt;
+        if self.stride < Self::STRIDE_LENGTH {
+            return None;
+        }
+        self.stride %= Self::STRIDE_LENGTH;
+        Some(Footstep {
+            pos: self.collider.get_pos(collision_world),
+            sound: self.ground.footstep.clone()?,
+        })
+    }
+
     pub fn update_player_light(
         &mut self,
         light_engine: &mut LightEngine,
@@ -178,67 +284,218 @@ impl Player {
             .set_color(Vector4::new(1.0, 1.0, 1.0, 0.15));
     }
 
+    // Takes the damage that gets through the armor. Getting hit interrupts using an item.
+    pub fn take_hit(&mut self, hit: Hit) -> Wound {
+        let wound = hit.resolve(&self.inventory.equipment);
+        self.health -= wound.damage;
+        self.bleeding = (self.bleeding + wound.bleeding).min(Self::MAX_BLEEDING);
+        self.consumption = None;
+        wound
+    }
+
+    // In deep water, where weapons can't be used
+    pub fn is_swimming(&self) -> bool {
+        self.ground.swimmable
+    }
+
+    pub fn handle_bleeding(&mut self, dt: f32) {
+        self.health -= self.bleeding * dt;
+    }
+
+    // Hunger, thirst and fatigue, along with the health lost to starving or dehydration
+    pub fn handle_needs(&mut self, dt: f32) {
+        self.needs.update_metabolism(dt);
+        self.health -= self.needs.health_drain() * dt;
+    }
+
+    // Starts using consumables requested from the inventory or the quick heal key,
+    // and applies their effect while and once the player has held still for long enough.
+    // Items that would do nothing, like a medkit at full health, are not used.
+    pub fn handle_consumables(&mut self, input: &PlayerInput, dt: f32) {
+        let requested = self.inventory.use_requested.take().or_else(|| {
+            if input.quick_heal {
+                self.inventory
+                    .find_healing_item(|effect| effect.has_effect(self))
+            } else {
+                None
+            }
+        });
+        if let Some(location) = requested {
+            let consumption = self
+                .inventory
+                .get(location)
+                .and_then(|item| Consumption::new(&item.item, location))
+                .filter(|consumption| consumption.stats.effect.has_effect(self));
+            if consumption.is_some() {
+                self.consumption = consumption;
+            }
+        }
+
+        let Some(consumption) = &mut self.consumption else {
+            return;
+        };
+        let item_missing = self
+            .inventory
+            .get(consumption.location)
+            .map_or(true, |item| item.item != consumption.item);
+        let moving = !self.inventory_open && input.movement != Vector2::zero();
+        if item_missing {
+            self.consumption = None;
+            return;
+        }
+        let progress = consumption.progress();
+        let event = consumption.update(dt, moving);
+        let gained = consumption.progress().min(1.0) - progress;
+        let effect = consumption.stats.effect;
+        let location = consumption.location;
+        if event != Some(ConsumptionEvent::Interrupted) {
+            effect.apply_gradually(self, gained);
+        }
+        match event {
+            // Stopping halfway through still uses up what was applied so far
+            Some(ConsumptionEvent::Interrupted) if effect.is_gradual() && progress > 0.0 => {
+                self.inventory.use_charge(location);
+                self.consumption = None;
+            }
+            Some(ConsumptionEvent::Interrupted) => self.consumption = None,
+            Some(ConsumptionEvent::Finished) => {
+                self.inventory.use_charge(location);
+                self.consumption = None;
+                effect.apply(self);
+            }
+            // A medkit stops once health is full
+            None if effect.is_gradual() && !effect.has_effect(self) => {
+                self.inventory.use_charge(location);
+                self.consumption = None;
+            }
+            None => {}
+        }
+    }
+
+    // Draws the weapon in the slot, or holsters it if it is already in hand
+    pub fn select_weapon(&mut self, slot: EquipmentSlot) {
+        if self.inventory.active_weapon == Some(slot) {
+            self.inventory.active_weapon = None;
+        } else if self.inventory.equipment.get(slot).is_some() {
+            self.inventory.active_weapon = Some(slot);
+        }
+    }
+
+    // Swaps the weapon in hand whenever a different weapon slot is active or its firearm changed
+    fn sync_weapon(&mut self) {
+        let active = self
+            .inventory
+            .active_weapon
+            .zip(self.inventory.active_weapon_item());
+        let in_hand = self
+            .weapon
+            .as_ref()
+            .map(|weapon| (weapon.slot, &weapon.item));
+        if active.map(|(slot, item)| (slot, &item.item)) != in_hand {
+            self.weapon = active.and_then(|(slot, item)| Weapon::new(item, slot));
+        }
+    }
+
     pub fn handle_shooting(
         &mut self,
-        rl: &mut RaylibHandle,
+        input: &PlayerInput,
+        dt: f32,
         collision_world: &mut CollisionWorld,
-        bullets: &mut Vec<WorldColliderHandle>,
-        aimed_at: Vector2,
+        bullets: &mut Vec<Bullet>,
+        rng: &mut impl Rng,
     ) {
-        let accuracy = 50.0
-            / (self.collider.get_linvel(collision_world).length() / Self::WALKING_SPEED * 2.0)
-                .max(1.0);
-        let bullet_speed = 160.0;
-        let max_angle = std::f32::consts::PI / 2.0 / accuracy;
-        let random_accuracy_angle = rand::thread_rng().gen_range(-max_angle..max_angle);
-        if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT)
-            && self.time_since_shot > 0.1
+        self.sync_weapon();
+        let Some(weapon) = &mut self.weapon else {
+            return;
+        };
+        if input.switch_fire_mode && !self.inventory_open {
+            weapon.cycle_fire_mode();
+        }
+        let trigger_held = input.firing
             && !self.inventory_open
-        {
-            self.time_since_shot = 0.0;
-            let d = (aimed_at - self.collider.get_pos(collision_world)).normalized();
-            let bullet_radius = 0.1;
-            bullets.push(collision_world.spawn_collider(
-                RigidBodyArgs {
-                    dynamic: true,
-                    pos: self.collider.get_pos(collision_world) + d * 2.0,
-                    vel: d.rotated(random_accuracy_angle) * bullet_speed,
-                    user_data: ColliderUserData::BULLET,
-                },
-                ColliderArgs {
-                    density: 1.5,
-                    restitution: 0.1,
-                    friction: 0.7,
-                    user_data: ColliderUserData::BULLET,
-                    sensor: false,
-                },
-                ShapeArgs::Ball {
-                    radius: bullet_radius,
-                },
-            ));
-        } else {
-            self.time_since_shot += rl.get_frame_time();
+            && self.consumption.is_none()
+            && !self.is_swimming();
+        // Pulling the trigger on an empty weapon reloads it
+        let wants_reload =
+            (input.reload && !self.inventory_open) || (trigger_held && weapon.is_empty());
+        if wants_reload && weapon.can_reload() {
+            let source = self
+                .inventory
+                .get_reload_source(weapon.stats.caliber, weapon.rounds_in_magazine);
+            if let Some(source) = source {
+                weapon.start_reload(source);
+            }
+        }
+        let event = weapon.update(dt, trigger_held);
+        if event == Some(WeaponEvent::Reloaded) {
+            weapon.rounds_in_magazine = self.inventory.take_rounds_for_reload(
+                weapon.stats.caliber,
+                weapon.rounds_in_magazine,
+                weapon.stats.magazine_size,
+            );
+        }
+        // Keep the rounds and wear on the inventory item so they stay with the firearm
+        if let Some(item) = self.inventory.equipment.get_mut(weapon.slot) {
+            item.rounds = Some(weapon.rounds_in_magazine);
+            item.durability = Some(weapon.durability);
+        }
+        if event != Some(WeaponEvent::Fired) {
+            return;
         }
+        let pos = self.collider.get_pos(collision_world);
+        let movement_factor =
+            self.collider.get_linvel(collision_world).length() / Self::WALKING_SPEED * 2.0;
+        let spread = weapon.current_spread(movement_factor) * self.needs.spread_multiplier();
+        let random_accuracy_angle = if spread > 0.0 {
+            rng.gen_range(-spread..spread)
+        } else {
+            0.0
+        };
+        let d = (input.aim_pos - pos).normalized();
+        bullets.push(Bullet {
+            collider: collision_world.spawn_bullet(
+                pos + d * 2.0,
+                d.rotated(random_accuracy_angle) * weapon.stats.muzzle_velocity,
+                weapon.stats.bullet_radius,
+            ),
+            damage: weapon.stats.damage,
+            penetration: weapon.stats.penetration,
+        });
+        self.collider
+            .apply_impulse(-d * weapon.stats.recoil, collision_world);
     }
 
     pub fn handle_spawning_dunmmies(
         &self,
-        rl: &RaylibHandle,
-        camera: &Camera2D,
+        input: &PlayerInput,
         collision_world: &mut CollisionWorld,
         game_world: &mut GameWorld,
         light_engine: &mut LightEngine,
     ) {
-        let mouse_pos = rl.get_mouse_position();
-        if rl.is_key_pressed(KeyboardKey::KEY_G) {
-            game_world.dummies.push({
-                let dummy = Player::new(collision_world, light_engine);
-                dummy
-                    .collider
-                    .set_pos(camera.to_world(mouse_pos), collision_world);
-                dummy
-            })
+        if let Some(spawn_pos) = input.spawn_dummy {
+            match Player::new_dummy(spawn_pos, collision_world, light_engine) {
+                Ok(dummy) => game_world.dummies.push(dummy),
+                Err(err) => eprintln!("Failed to spawn dummy: {}", err),
+            }
+        }
+    }
+
+    // A hostile character with a pistol, guarding the spot it spawned at
+    pub fn new_dummy(
+        pos: Vector2,
+        collision_world: &mut CollisionWorld,
+        light_engine: &mut LightEngine,
+    ) -> Result<Player, LightError> {
+        let mut dummy = Player::new(collision_world, light_engine)?;
+        dummy.collider.set_pos(pos, collision_world);
+        dummy.brain = Some(Brain::new(pos));
+        // Something to find on the body
+        dummy.inventory.equipment.secondary = Some(Item::Pistol.to_inventory_item(false));
+        dummy.inventory.active_weapon = Some(EquipmentSlot::Secondary);
+        for item in [Item::Magazine(Caliber::Pistol), Item::Bandage] {
+            let _ = dummy.inventory.try_insert(item.to_inventory_item(false));
         }
+        Ok(dummy)
     }
 
     pub fn render(
@@ -250,30 +507,17 @@ impl Player {
         thread: &RaylibThread,
         target: &mut RenderTexture2D,
     ) {
-        let player_texture = assets.get_texture("rifle.png");
         let player_pos = self.collider.get_pos(collision_world);
         let mut d = d.begin_texture_mode(thread, target);
-        let scale = 0.1;
-        d.draw_texture_pro(
-            player_texture,
-            Rectangle::new(
-                0.0,
-                0.0,
-                player_texture.width() as f32,
-                player_texture.height() as f32,
-            ),
-            camera.to_screen_rect(&Rectangle::new(
-                player_pos.x,
-                player_pos.y,
-                player_texture.width() as f32 * scale,
-                player_texture.width() as f32 * scale,
-            )),
-            Vector2::new(
-                player_texture.width() as f32 * scale / 2.0 * camera.zoom,
-                player_texture.height() as f32 * scale / 2.0 * camera.zoom,
-            ),
+        // Every character layer is drawn at the size of the character sprite
+        let size = Self::SPRITE_SIZE * Self::SPRITE_SCALE;
+        self.inventory.equipment.draw_character(
+            &mut d,
+            assets,
+            self.inventory.active_weapon,
+            camera.to_screen_rect(&Rectangle::new(player_pos.x, player_pos.y, size, size)),
+            Vector2::new(size / 2.0 * camera.zoom, size / 2.0 * camera.zoom),
             self.angle,
-            Color::WHITE,
         );
         let font_size = 1.0;
         d.draw_text(
@@ -281,16 +525,186 @@ impl Player {
             camera.to_screen_x(player_pos.x - font_size / 2.0) as i32,
             camera.to_screen_y(player_pos.y - font_size / 2.0) as i32,
             (1.0 * camera.zoom) as i32,
-            Color::WHITE,
+            // Red while bleeding
+            if self.bleeding > 0.0 {
+                Color::RED
+            } else {
+                Color::WHITE
+            },
         );
+        if let Some(progress) = self
+            .weapon
+            .as_ref()
+            .and_then(|weapon| weapon.reload_progress())
+        {
+            Self::draw_progress_ring(
+                &mut d,
+                camera,
+                player_pos,
+                progress,
+                Color::new(255, 255, 255, 150),
+            );
+        }
+        if let Some(consumption) = &self.consumption {
+            Self::draw_progress_ring(
+                &mut d,
+                camera,
+                player_pos,
+                consumption.progress(),
+                Color::new(120, 255, 120, 150),
+            );
+        }
     }
 
-    pub fn get_corpse(&self, collision_world: &mut CollisionWorld) -> Corpse {
+    fn draw_progress_ring(
+        d: &mut impl RaylibDraw,
+        camera: &Camera2D,
+        player_pos: Vector2,
+        progress: f32,
+        color: Color,
+    ) {
+        let radius = 2.5 * camera.zoom;
+        d.draw_ring(
+            camera.to_screen(player_pos),
+            radius - 0.2 * camera.zoom,
+            radius,
+            -90.0,
+            -90.0 + 360.0 * progress,
+            32,
+            color,
+        );
+    }
+
+    // Brings the character back at the spawn point, healthy and empty handed
+    pub fn respawn(&mut self, collision_world: &mut CollisionWorld) {
+        self.collider.set_pos(Self::SPAWN_POS, collision_world);
+        self.collider.set_linvel(Vector2::zero(), collision_world);
+        self.health = Self::MAX_HEALTH;
+        self.bleeding = 0.0;
+        self.needs = Needs::default();
+        self.inventory = Inventory::new();
+        self.weapon = None;
+        self.consumption = None;
+    }
+
+    // Leaves the inventory on the corpse so it can be looted
+    pub fn get_corpse(&mut self, collision_world: &mut CollisionWorld) -> Corpse {
+        let inventory = std::mem::replace(&mut self.inventory, Inventory::new());
         Corpse {
             pos: self.collider.get_pos(collision_world),
             animation_stage: 1,
             time_elapsed: 0.0,
             angle: self.angle,
+            loot: inventory.into_container(Corpse::LOOT_SIZE),
+            time_since_death: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // A single tile of ground, big enough to walk around on
+    fn covered_in(ground: Ground) -> GroundMap {
+        let mut ground_map = GroundMap::new(Vector2::new(1000.0, 1000.0));
+        ground_map.set((0, 0), ground);
+        ground_map
+    }
+
+    // How fast a character ends up walking across the ground
+    fn walking_speed(ground: Ground) -> f32 {
+        let mut collision_world = CollisionWorld::default();
+        let mut light_engine = LightEngine::new();
+        let mut game_world = GameWorld::with_seed(0);
+        let mut player = Player::new(&mut collision_world, &mut light_engine).unwrap();
+        let ground_map = covered_in(ground);
+        let input = PlayerInput {
+            movement: Vector2::new(1.0, 0.0),
+            aim_pos: Vector2::new(100.0, 20.0),
+            ..Default::default()
+        };
+        for _ in 0..180 {
+            player.handle_controls(&input, DT, &mut collision_world, &ground_map);
+            collision_world.step(DT, &mut player, &mut game_world, &mut light_engine);
+        }
+        player.collider.get_linvel(&collision_world).length()
+    }
+
+    #[test]
+    fn ground_scales_walking_speed() {
+        let normal = walking_speed(Ground::default());
+        let mud = walking_speed(Ground {
+            speed_multiplier: 0.5,
+            ..Default::default()
+        });
+        let road = walking_speed(Ground {
+            speed_multiplier: 1.1,
+            ..Default::default()
+        });
+        assert!((normal - Player::WALKING_SPEED).abs() < 0.1);
+        assert!((mud / normal - 0.5).abs() < 0.02);
+        assert!((road / normal - 1.1).abs() < 0.02);
+    }
+
+    #[test]
+    fn swimming_characters_cannot_shoot() {
+        let mut collision_world = CollisionWorld::default();
+        let mut light_engine = LightEngine::new();
+        let mut player = Player::new(&mut collision_world, &mut light_engine).unwrap();
+        player.inventory.equipment.primary = Some(Item::Rifle.to_inventory_item(false));
+        player.inventory.active_weapon = Some(EquipmentSlot::Primary);
+        let input = PlayerInput {
+            firing: true,
+            aim_pos: Vector2::new(40.0, 20.0),
+            ..Default::default()
+        };
+        let mut bullets = vec![];
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut shoot = |player: &mut Player, bullets: &mut Vec<Bullet>| {
+            for _ in 0..30 {
+                player.handle_shooting(&input, DT, &mut collision_world, bullets, &mut rng);
+            }
+        };
+
+        player.ground = Ground {
+            swimmable: true,
+            ..Default::default()
+        };
+        shoot(&mut player, &mut bullets);
+        assert!(bullets.is_empty());
+        assert!(!player.weapon.as_ref().unwrap().is_reloading());
+
+        player.ground = Ground::default();
+        shoot(&mut player, &mut bullets);
+        assert!(!bullets.is_empty());
+    }
+
+    #[test]
+    fn footsteps_sound_like_the_ground() {
+        let mut collision_world = CollisionWorld::default();
+        let mut light_engine = LightEngine::new();
+        let mut game_world = GameWorld::with_seed(0);
+        let mut player = Player::new(&mut collision_world, &mut light_engine).unwrap();
+        let ground_map = covered_in(Ground {
+            footstep: Some("grass".to_string()),
+            ..Default::default()
+        });
+        let input = PlayerInput {
+            movement: Vector2::new(1.0, 0.0),
+            ..Default::default()
+        };
+        let mut footsteps = vec![];
+        for _ in 0..120 {
+            player.handle_controls(&input, DT, &mut collision_world, &ground_map);
+            collision_world.step(DT, &mut player, &mut game_world, &mut light_engine);
+            footsteps.extend(player.take_step(DT, &collision_world));
         }
+        let walked = player.collider.get_pos(&collision_world).x - Player::SPAWN_POS.x;
+        assert!((footsteps.len() as f32 - walked / Player::STRIDE_LENGTH).abs() <= 1.0);
+        assert!(footsteps.iter().all(|footstep| footstep.sound == "grass"));
     }
 }
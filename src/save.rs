@@ -0,0 +1,1012 @@
+use rand_chacha::ChaCha12Rng;
+use raylib::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{
+    ai::Brain,
+    chunks::WorldChunks,
+    clock::WorldClock,
+    collision_world::CollisionWorld,
+    container::{Container, WorldContainer},
+    equipment::{Equipment, EquipmentSlot},
+    game_map::GameMap,
+    ground::GroundMap,
+    interaction::Interaction,
+    inventory::InventoryItem,
+    lighting::{Light, LightEngine, LightError, LightHandle},
+    nav::NavGrid,
+    needs::Needs,
+    simulation::Simulation,
+    visibility::Visibility,
+    weapon::Bullet,
+    world_collider::WorldColliderHandle,
+    Corpse, GameWorld, Player,
+};
+
+pub const SAVE_VERSION: u32 = 10;
+pub const SAVE_SLOTS: u32 = 3;
+const SAVE_DIR: &str = "saves";
+
+// Each entry upgrades the raw json of a save from version `i + 1` to `i + 2`.
+// When the format changes, bump SAVE_VERSION and push a migration here.
+const MIGRATIONS: &[fn(&mut Value)] = &[
+    migrate_v1_inventory_into_player,
+    migrate_v2_load_firearms,
+    migrate_v3_consumable_charges,
+    migrate_v4_item_instances,
+    migrate_v5_item_locations,
+    migrate_v6_corpse_loot,
+    migrate_v7_equipment_slots,
+    migrate_v8_bullet_damage,
+    migrate_v9_world_clock,
+];
+
+// Version 2 moved the inventory from the top level into every player and dummy
+fn migrate_v1_inventory_into_player(save: &mut Value) {
+    let inventory = match save["inventory"].take() {
+        Value::Null => Value::Array(vec![]),
+        inventory => inventory,
+    };
+    save["player"]["inventory"] = inventory;
+    if let Some(dummies) = save["dummies"].as_array_mut() {
+        for dummy in dummies {
+            dummy["inventory"] = Value::Array(vec![]);
+        }
+    }
+    if let Some(save) = save.as_object_mut() {
+        save.remove("inventory");
+    }
+}
+
+// Version 3 tracks the rounds in every firearm, older saves get them fully loaded
+fn migrate_v2_load_firearms(save: &mut Value) {
+    for_each_inventory_item(save, |inventory_item| {
+        if let Some(rounds) = frozen::firearm_magazine_size(&inventory_item["item"]) {
+            inventory_item["rounds"] = rounds.into();
+        }
+    });
+}
+
+// Version 4 tracks the charges left in consumables, older saves get them unused
+fn migrate_v3_consumable_charges(save: &mut Value) {
+    for_each_inventory_item(save, |inventory_item| {
+        if let Some(charges) = frozen::consumable_charges(&inventory_item["item"]) {
+            inventory_item["charges"] = charges.into();
+        }
+    });
+}
+
+// Version 5 keeps per-instance state on ground items and stacks loose rounds by count
+fn migrate_v4_item_instances(save: &mut Value) {
+    for_each_inventory_item(save, |inventory_item| {
+        if inventory_item["item"].get("Ammo").is_some() {
+            if let Some(rounds) = inventory_item["rounds"].take().as_u64() {
+                inventory_item["count"] = rounds.into();
+            }
+        }
+    });
+    if let Some(ground_items) = save["ground_items"].as_array_mut() {
+        for ground_item in ground_items {
+            if let Some(inventory_item) = frozen::v5_inventory_item(&ground_item["item"]) {
+                ground_item["item"] = inventory_item;
+            }
+        }
+    }
+}
+
+// Version 6 adds backpack and rig grids, so the equipped item also records its grid
+fn migrate_v5_item_locations(save: &mut Value) {
+    let locate_equipped = |player: &mut Value| {
+        let pos = player["equipped"].take();
+        if !pos.is_null() {
+            player["equipped"] = json!({ "grid": "Pockets", "pos": pos });
+        }
+    };
+    locate_equipped(&mut save["player"]);
+    if let Some(dummies) = save["dummies"].as_array_mut() {
+        for dummy in dummies {
+            locate_equipped(dummy);
+        }
+    }
+}
+
+// Version 7 keeps the dead character's items on their corpse, older corpses are empty
+fn migrate_v6_corpse_loot(save: &mut Value) {
+    if let Some(corpses) = save["corpses"].as_array_mut() {
+        for corpse in corpses {
+            corpse["loot"] = json!({ "size": frozen::CORPSE_LOOT_SIZE, "items": [] });
+            corpse["time_since_death"] = 0.0.into();
+        }
+    }
+}
+
+// Version 8 wears backpacks and rigs in equipment slots and keeps firearms in weapon slots,
+// so the equipped firearm moves out of its grid into the matching slot
+fn migrate_v7_equipment_slots(save: &mut Value) {
+    let equip = |player: &mut Value| {
+        let mut equipment = serde_json::Map::new();
+        equipment.insert("backpack".to_string(), player["backpack"].take());
+        equipment.insert("rig".to_string(), player["rig"].take());
+        let equipped = player["equipped"].take();
+        if let Some(item) = take_grid_item(player, &mut equipment, &equipped) {
+            // Only firearms could be equipped
+            let (key, slot) = match item["item"].as_str() {
+                Some("Rifle") => ("primary", json!("Primary")),
+                Some("Pistol") => ("secondary", json!("Secondary")),
+                _ => ("secondary", Value::Null),
+            };
+            player["active_weapon"] = slot;
+            equipment.insert(key.to_string(), item);
+        }
+        if let Some(player) = player.as_object_mut() {
+            player.remove("backpack");
+            player.remove("rig");
+            player.remove("equipped");
+        }
+        player["equipment"] = Value::Object(equipment);
+    };
+    equip(&mut save["player"]);
+    if let Some(dummies) = save["dummies"].as_array_mut() {
+        for dummy in dummies {
+            equip(dummy);
+        }
+    }
+}
+
+// Version 9 carries the firearm's damage on every bullet, older bullets are matched to a
+// firearm by their size
+fn migrate_v8_bullet_damage(save: &mut Value) {
+    let Some(bullets) = save["bullets"].as_array_mut() else {
+        return;
+    };
+    for bullet in bullets {
+        let radius = bullet["radius"].as_f64().unwrap_or(0.0) as f32;
+        let (_, damage, penetration) = frozen::BULLETS
+            .iter()
+            .min_by(|a, b| (a.0 - radius).abs().total_cmp(&(b.0 - radius).abs()))
+            .unwrap();
+        bullet["damage"] = (*damage).into();
+        bullet["penetration"] = (*penetration).into();
+    }
+}
+
+// Version 10 saves the time of day and which light is the ambient light it drives. Older
+// saves were always lit like midday, and their only ambient light becomes the driven one.
+fn migrate_v9_world_clock(save: &mut Value) {
+    save["clock"] = json!({ "day": 0, "time": 12.0 });
+    let ambient_light_id = save["lights"].as_array().and_then(|lights| {
+        lights
+            .iter()
+            .find(|light| light[1].get("Ambient").is_some())
+            .map(|light| light[0].clone())
+    });
+    save["ambient_light_id"] = ambient_light_id.unwrap_or(Value::Null);
+}
+
+// Removes the item at a version 7 location. Pockets hold `{grid_pos, item}` slots while
+// backpacks and rigs hold `[grid_pos, item]` pairs.
+fn take_grid_item(
+    player: &mut Value,
+    equipment: &mut serde_json::Map<String, Value>,
+    location: &Value,
+) -> Option<Value> {
+    let pos = &location["pos"];
+    let key = match location["grid"].as_str()? {
+        "Pockets" => {
+            let slots = player["inventory"].as_array_mut()?;
+            let i = slots.iter().position(|slot| slot["grid_pos"] == *pos)?;
+            return Some(slots.remove(i)["item"].take());
+        }
+        "Rig" => "rig",
+        "Backpack" => "backpack",
+        _ => return None,
+    };
+    let slots = equipment.get_mut(key)?["container"]["items"].as_array_mut()?;
+    let i = slots.iter().position(|slot| slot[0] == *pos)?;
+    Some(slots.remove(i)[1].take())
+}
+
+// Calls `f` with every item in the player's and dummies' inventories
+fn for_each_inventory_item(save: &mut Value, mut f: impl FnMut(&mut Value)) {
+    let mut visit_inventory = |inventory: &mut Value| {
+        let Some(inventory) = inventory.as_array_mut() else {
+            return;
+        };
+        for slot in inventory {
+            f(&mut slot["item"]);
+        }
+    };
+    visit_inventory(&mut save["player"]["inventory"]);
+    if let Some(dummies) = save["dummies"].as_array_mut() {
+        for dummy in dummies {
+            visit_inventory(&mut dummy["inventory"]);
+        }
+    }
+}
+
+// Items as they were when the migrations were written, matched on their raw json. Migrations
+// only read these, so later changes to the game's items never change what an old save becomes.
+mod frozen {
+    use serde_json::{json, Value};
+
+    pub const CORPSE_LOOT_SIZE: (i32, i32) = (8, 10);
+    // Radius, damage and penetration of rifle and pistol bullets
+    pub const BULLETS: [(f32, f32, u32); 2] = [(0.1, 30.0, 4), (0.08, 22.0, 2)];
+
+    pub fn firearm_magazine_size(item: &Value) -> Option<u32> {
+        match item.as_str()? {
+            "Rifle" => Some(20),
+            "Pistol" => Some(12),
+            _ => None,
+        }
+    }
+
+    pub fn consumable_charges(item: &Value) -> Option<u32> {
+        match item.as_str()? {
+            "MedKit" | "WaterBottle" => Some(3),
+            "CannedFood" => Some(2),
+            "Bandage" | "EnergyDrink" => Some(1),
+            _ => None,
+        }
+    }
+
+    fn size(item: &Value) -> Option<(u32, u32)> {
+        if item.get("Magazine").is_some() {
+            return Some((1, 2));
+        }
+        if item.get("Ammo").is_some() {
+            return Some((1, 1));
+        }
+        match item.as_str()? {
+            "Rifle" => Some((4, 2)),
+            "Pistol" => Some((2, 1)),
+            "MedKit" => Some((3, 2)),
+            "WaterBottle" => Some((1, 2)),
+            "Bandage" | "CannedFood" | "EnergyDrink" => Some((1, 1)),
+            _ => None,
+        }
+    }
+
+    // A freshly found item in the version 5 format, or None for items version 5 didn't know
+    pub fn v5_inventory_item(item: &Value) -> Option<Value> {
+        let size = size(item)?;
+        let count = match item.get("Ammo").and_then(Value::as_str) {
+            Some("Rifle") => 30,
+            Some("Pistol") => 50,
+            _ => 1,
+        };
+        let rounds = match item.get("Magazine").and_then(Value::as_str) {
+            Some("Rifle") => Some(20),
+            Some("Pistol") => Some(12),
+            _ => firearm_magazine_size(item),
+        };
+        Some(json!({
+            "rotated": false,
+            "size": size,
+            "item": item,
+            "count": count,
+            "rounds": rounds,
+            "charges": consumable_charges(item),
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    UnsupportedVersion(u32),
+    Light(LightError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "save file io error: {}", err),
+            SaveError::Format(err) => write!(f, "malformed save file: {}", err),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "save version {} is newer than supported version {}",
+                version, SAVE_VERSION
+            ),
+            SaveError::Light(err) => write!(f, "can't restore lights: {}", err),
+        }
+    }
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Format(err)
+    }
+}
+
+impl From<LightError> for SaveError {
+    fn from(err: LightError) -> Self {
+        SaveError::Light(err)
+    }
+}
+
+// Raylib vectors don't implement serde, so they are mirrored here
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SaveVector2 {
+    x: f32,
+    y: f32,
+}
+
+impl From<Vector2> for SaveVector2 {
+    fn from(v: Vector2) -> Self {
+        SaveVector2 { x: v.x, y: v.y }
+    }
+}
+
+impl From<SaveVector2> for Vector2 {
+    fn from(v: SaveVector2) -> Self {
+        Vector2::new(v.x, v.y)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct SaveVector4 {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32,
+}
+
+impl From<Vector4> for SaveVector4 {
+    fn from(v: Vector4) -> Self {
+        SaveVector4 {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+            w: v.w,
+        }
+    }
+}
+
+impl From<SaveVector4> for Vector4 {
+    fn from(v: SaveVector4) -> Self {
+        Vector4::new(v.x, v.y, v.z, v.w)
+    }
+}
+
+// Rapier body state
+#[derive(Serialize, Deserialize)]
+pub struct BodySave {
+    pos: SaveVector2,
+    #[serde(default)]
+    rotation: f32,
+    vel: SaveVector2,
+    angvel: f32,
+}
+
+impl BodySave {
+    fn capture(collider: &WorldColliderHandle, collision_world: &CollisionWorld) -> Self {
+        BodySave {
+            pos: collider.get_pos(collision_world).into(),
+            rotation: collider.get_angle(collision_world),
+            vel: collider.get_linvel(collision_world).into(),
+            angvel: collider.get_angvel(collision_world),
+        }
+    }
+
+    // Standing still at `pos`, for bodies that aren't in the physics world
+    fn at_rest(pos: Vector2) -> Self {
+        BodySave {
+            pos: pos.into(),
+            rotation: 0.0,
+            vel: Vector2::zero().into(),
+            angvel: 0.0,
+        }
+    }
+
+    fn apply(&self, collider: &WorldColliderHandle, collision_world: &mut CollisionWorld) {
+        collider.set_pos(self.pos.into(), collision_world);
+        collider.set_angle(self.rotation, collision_world);
+        collider.set_linvel(self.vel.into(), collision_world);
+        collider.set_angvel(self.angvel, collision_world);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PlayerSave {
+    body: BodySave,
+    angle: f32,
+    health: f32,
+    inventory_open: bool,
+    light_id: u32,
+    inventory: Vec<InventorySlotSave>,
+    #[serde(default)]
+    equipment: Equipment,
+    #[serde(default)]
+    active_weapon: Option<EquipmentSlot>,
+    #[serde(default)]
+    bleeding: f32,
+    #[serde(default)]
+    needs: Needs,
+}
+
+impl PlayerSave {
+    fn capture(player: &Player, body: BodySave) -> Self {
+        PlayerSave {
+            body,
+            angle: player.angle,
+            health: player.health,
+            inventory_open: player.inventory_open,
+            light_id: player.player_light.id(),
+            inventory: player
+                .inventory
+                .pockets
+                .items
+                .iter()
+                .map(|(grid_pos, item)| InventorySlotSave {
+                    grid_pos: *grid_pos,
+                    item: item.clone(),
+                })
+                .collect(),
+            equipment: player.inventory.equipment.clone(),
+            active_weapon: player.inventory.active_weapon,
+            bleeding: player.bleeding,
+            needs: player.needs.clone(),
+        }
+    }
+
+    fn restore(
+        self,
+        collision_world: &mut CollisionWorld,
+        light_handles: &mut HashMap<u32, LightHandle>,
+        light_engine: &mut LightEngine,
+    ) -> Result<Player, LightError> {
+        // Older saves, and dummies asleep in unloaded chunks, may reference a light that no
+        // longer exists, so give the player a new one
+        let player_light = match light_handles.remove(&self.light_id) {
+            Some(light) => light,
+            None => light_engine.spawn_light(Light::default_radial())?,
+        };
+        let mut player = Player::with_light(collision_world, self.body.pos.into(), player_light);
+        self.body.apply(&player.collider, collision_world);
+        player.angle = self.angle;
+        player.health = self.health;
+        player.bleeding = self.bleeding;
+        player.needs = self.needs;
+        player.inventory_open = self.inventory_open;
+        player.inventory.pockets.items = self
+            .inventory
+            .into_iter()
+            .map(|slot| (slot.grid_pos, slot.item))
+            .collect();
+        player.inventory.equipment = self.equipment;
+        player.inventory.active_weapon = self.active_weapon;
+        Ok(player)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CorpseSave {
+    pos: SaveVector2,
+    animation_stage: i32,
+    time_elapsed: f32,
+    angle: f32,
+    loot: Container,
+    time_since_death: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GroundItemSave {
+    item: InventoryItem,
+    pos: SaveVector2,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorldContainerSave {
+    pos: SaveVector2,
+    container: Container,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BulletSave {
+    body: BodySave,
+    radius: f32,
+    damage: f32,
+    penetration: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InventorySlotSave {
+    grid_pos: (i32, i32),
+    item: InventoryItem,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum LightSave {
+    Radial {
+        pos: SaveVector2,
+        color: SaveVector4,
+        radius: f32,
+    },
+    Ambient {
+        color: SaveVector4,
+    },
+    Cone {
+        pos: SaveVector2,
+        color: SaveVector4,
+        radius: f32,
+        rotation: f32,
+        angle: f32,
+    },
+}
+
+impl From<&Light> for LightSave {
+    fn from(light: &Light) -> Self {
+        match light {
+            Light::Radial { pos, color, radius } => LightSave::Radial {
+                pos: (*pos).into(),
+                color: (*color).into(),
+                radius: *radius,
+            },
+            Light::Ambient { color } => LightSave::Ambient {
+                color: (*color).into(),
+            },
+            Light::Cone {
+                pos,
+                color,
+                radius,
+                rotation,
+                angle,
+            } => LightSave::Cone {
+                pos: (*pos).into(),
+                color: (*color).into(),
+                radius: *radius,
+                rotation: *rotation,
+                angle: *angle,
+            },
+        }
+    }
+}
+
+impl From<LightSave> for Light {
+    fn from(light: LightSave) -> Self {
+        match light {
+            LightSave::Radial { pos, color, radius } => Light::Radial {
+                pos: pos.into(),
+                color: color.into(),
+                radius,
+            },
+            LightSave::Ambient { color } => Light::Ambient {
+                color: color.into(),
+            },
+            LightSave::Cone {
+                pos,
+                color,
+                radius,
+                rotation,
+                angle,
+            } => Light::Cone {
+                pos: pos.into(),
+                color: color.into(),
+                radius,
+                rotation,
+                angle,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SaveFile {
+    version: u32,
+    player: PlayerSave,
+    dummies: Vec<PlayerSave>,
+    corpses: Vec<CorpseSave>,
+    ground_items: Vec<GroundItemSave>,
+    #[serde(default)]
+    containers: Vec<WorldContainerSave>,
+    bullets: Vec<BulletSave>,
+    lights: Vec<(u32, LightSave)>,
+    clock: ClockSave,
+    ambient_light_id: Option<u32>,
+    // Older saves carry on with fresh random numbers
+    #[serde(default)]
+    rng: Option<ChaCha12Rng>,
+    // Time the physics world has yet to step through
+    #[serde(default)]
+    physics_time: f32,
+    // Older saves wait a whole interval before the first night spawn
+    #[serde(default = "default_night_spawn_timer")]
+    night_spawn_timer: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClockSave {
+    day: u32,
+    time: f32,
+    // Older saves were all made with the default day length
+    #[serde(default = "default_day_length")]
+    day_length: f32,
+}
+
+fn default_day_length() -> f32 {
+    WorldClock::DEFAULT_DAY_LENGTH
+}
+
+fn default_night_spawn_timer() -> f32 {
+    Simulation::NIGHT_SPAWN_INTERVAL
+}
+
+impl SaveFile {
+    pub fn capture(sim: &Simulation) -> Self {
+        let collision_world = &sim.collision_world;
+        SaveFile {
+            version: SAVE_VERSION,
+            player: PlayerSave::capture(
+                &sim.player,
+                BodySave::capture(&sim.player.collider, collision_world),
+            ),
+            // Entities sleeping in unloaded chunks are saved along with the active ones
+            dummies: sim
+                .game_world
+                .dummies
+                .iter()
+                .map(|dummy| {
+                    PlayerSave::capture(dummy, BodySave::capture(&dummy.collider, collision_world))
+                })
+                .chain(
+                    sim.chunks
+                        .dormant()
+                        .flat_map(|chunk| &chunk.dummies)
+                        .map(|(pos, dummy)| PlayerSave::capture(dummy, BodySave::at_rest(*pos))),
+                )
+                .collect(),
+            corpses: sim
+                .game_world
+                .corpses
+                .iter()
+                .chain(sim.chunks.dormant().flat_map(|chunk| &chunk.corpses))
+                .map(|corpse| CorpseSave {
+                    pos: corpse.pos.into(),
+                    animation_stage: corpse.animation_stage,
+                    time_elapsed: corpse.time_elapsed,
+                    angle: corpse.angle,
+                    loot: corpse.loot.clone(),
+                    time_since_death: corpse.time_since_death,
+                })
+                .collect(),
+            ground_items: sim
+                .game_world
+                .ground_items
+                .iter()
+                .chain(sim.chunks.dormant().flat_map(|chunk| &chunk.ground_items))
+                .map(|ground_item| GroundItemSave {
+                    item: ground_item.item().clone(),
+                    pos: ground_item.pos().into(),
+                })
+                .collect(),
+            containers: sim
+                .game_world
+                .containers
+                .iter()
+                .chain(sim.chunks.dormant().flat_map(|chunk| &chunk.containers))
+                .map(|container| WorldContainerSave {
+                    pos: container.pos.into(),
+                    container: container.container.clone(),
+                })
+                .collect(),
+            bullets: sim
+                .game_world
+                .bullets
+                .iter()
+                .map(|bullet| BulletSave {
+                    body: BodySave::capture(&bullet.collider, collision_world),
+                    radius: bullet
+                        .collider
+                        .get(&collision_world.rapier)
+                        .1
+                        .shape()
+                        .as_ball()
+                        .unwrap()
+                        .radius,
+                    damage: bullet.damage,
+                    penetration: bullet.penetration,
+                })
+                .collect(),
+            lights: sim
+                .light_engine
+                .lights()
+                .map(|(id, light)| (id, light.into()))
+                .collect(),
+            clock: ClockSave {
+                day: sim.clock.day(),
+                time: sim.clock.time(),
+                day_length: sim.clock.day_length,
+            },
+            ambient_light_id: Some(sim.ambient_light.id()),
+            rng: Some(sim.game_world.rng.clone()),
+            physics_time: collision_world.rapier.accumulated_time,
+            night_spawn_timer: sim.night_spawn_timer,
+        }
+    }
+
+    // Rebuilds the simulation from scratch, respawning every rapier body.
+    // Static geometry isn't saved, it is rebuilt from the map.
+    pub fn restore(self, map: &GameMap) -> Result<Simulation, SaveError> {
+        let mut collision_world = CollisionWorld::default();
+        collision_world.rapier.accumulated_time = self.physics_time;
+        let mut light_engine = LightEngine::new();
+        let mut light_handles = self
+            .lights
+            .into_iter()
+            .map(|(id, light)| (id, light_engine.restore_light(id, light.into())))
+            .collect::<HashMap<u32, LightHandle>>();
+
+        let player =
+            self.player
+                .restore(&mut collision_world, &mut light_handles, &mut light_engine)?;
+        let mut game_world = match self.rng {
+            Some(rng) => GameWorld::with_rng(rng),
+            None => GameWorld::new(),
+        };
+        game_world.dummies = self
+            .dummies
+            .into_iter()
+            .map(|dummy| {
+                let mut dummy =
+                    dummy.restore(&mut collision_world, &mut light_handles, &mut light_engine)?;
+                // The AI isn't saved, dummies start over from where they stand
+                dummy.brain = Some(Brain::new(dummy.collider.get_pos(&collision_world)));
+                Ok(dummy)
+            })
+            .collect::<Result<Vec<Player>, LightError>>()?;
+        game_world.corpses = self
+            .corpses
+            .into_iter()
+            .map(|corpse| Corpse {
+                pos: corpse.pos.into(),
+                animation_stage: corpse.animation_stage,
+                time_elapsed: corpse.time_elapsed,
+                angle: corpse.angle,
+                loot: corpse.loot,
+                time_since_death: corpse.time_since_death,
+            })
+            .collect();
+        game_world.ground_items = self
+            .ground_items
+            .into_iter()
+            .map(|ground_item| ground_item.item.to_ground_item(ground_item.pos.into()))
+            .collect();
+        game_world.containers = self
+            .containers
+            .into_iter()
+            .map(|container| WorldContainer::new(container.pos.into(), container.container))
+            .collect();
+        game_world.bullets = self
+            .bullets
+            .into_iter()
+            .map(|bullet| {
+                let handle = collision_world.spawn_bullet(
+                    bullet.body.pos.into(),
+                    bullet.body.vel.into(),
+                    bullet.radius,
+                );
+                bullet.body.apply(&handle, &mut collision_world);
+                Bullet {
+                    collider: handle,
+                    damage: bullet.damage,
+                    penetration: bullet.penetration,
+                }
+            })
+            .collect();
+        let clock = WorldClock::at(self.clock.day, self.clock.time, self.clock.day_length);
+        let ambient_light = self
+            .ambient_light_id
+            .and_then(|id| light_handles.remove(&id));
+        let ambient_light = match ambient_light {
+            Some(light) => light,
+            None => light_engine.spawn_light(clock.ambient_light())?,
+        };
+
+        let mut sim = Simulation {
+            collision_world,
+            game_world,
+            player,
+            light_engine,
+            interaction: Interaction::new(),
+            nav_grid: NavGrid::new(Rectangle::default(), vec![]),
+            ground: GroundMap::new(Vector2::one()),
+            chunks: WorldChunks::new(vec![]),
+            visibility: Visibility::new(Vector2::zero()),
+            clock,
+            ambient_light,
+            night_spawn_timer: self.night_spawn_timer,
+        };
+        sim.load_map(map);
+        Ok(sim)
+    }
+
+    pub fn slot_path(slot: u32) -> String {
+        format!("{}/slot{}.json", SAVE_DIR, slot)
+    }
+
+    pub fn write(&self, slot: u32) -> Result<(), SaveError> {
+        std::fs::create_dir_all(SAVE_DIR)?;
+        std::fs::write(Self::slot_path(slot), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn read(slot: u32) -> Result<Self, SaveError> {
+        Self::from_json(&std::fs::read_to_string(Self::slot_path(slot))?)
+    }
+
+    // Parses a save of any supported version, migrating it to the current one
+    pub fn from_json(json: &str) -> Result<Self, SaveError> {
+        let mut save: Value = serde_json::from_str(json)?;
+        Self::migrate(&mut save)?;
+        Ok(serde_json::from_value(save)?)
+    }
+
+    // Runs every migration between the save's version and the current one
+    fn migrate(save: &mut Value) -> Result<(), SaveError> {
+        let version = (save["version"].as_u64().unwrap_or(1) as u32).max(1);
+        if version > SAVE_VERSION {
+            return Err(SaveError::UnsupportedVersion(version));
+        }
+        for migration in &MIGRATIONS[(version - 1) as usize..] {
+            migration(save);
+        }
+        save["version"] = SAVE_VERSION.into();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::PlayerInput, inventory::Item, weapon::Caliber};
+    use rand::Rng;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // A save from before the first migration, with a rifle in the pockets, a medkit on the
+    // ground, a corpse, a rifle bullet in flight and the player's and ambient lights
+    const V1_SAVE: &str = r#"{
+        "version": 1,
+        "player": {
+            "body": { "pos": { "x": 20.0, "y": 20.0 }, "vel": { "x": 1.0, "y": 0.0 }, "angvel": 0.0 },
+            "angle": 45.0,
+            "health": 80.0,
+            "time_since_shot": 0.0,
+            "inventory_open": false,
+            "light_id": 0
+        },
+        "dummies": [],
+        "corpses": [
+            { "pos": { "x": 25.0, "y": 25.0 }, "animation_stage": 4, "time_elapsed": 0.0, "angle": 0.0 }
+        ],
+        "ground_items": [{ "item": "MedKit", "pos": { "x": 22.0, "y": 20.0 } }],
+        "bullets": [
+            {
+                "body": { "pos": { "x": 30.0, "y": 20.0 }, "vel": { "x": 150.0, "y": 0.0 }, "angvel": 0.0 },
+                "radius": 0.1
+            }
+        ],
+        "walls": [],
+        "inventory": [
+            { "grid_pos": [0, 0], "item": { "rotated": false, "size": [4, 2], "item": "Rifle" } }
+        ],
+        "lights": [
+            [0, { "Radial": { "pos": { "x": 20.0, "y": 20.0 }, "color": { "x": 1.0, "y": 1.0, "z": 1.0, "w": 0.15 }, "radius": 15.0 } }],
+            [1, { "Ambient": { "color": { "x": 1.0, "y": 1.0, "z": 1.0, "w": 1.0 } } }]
+        ]
+    }"#;
+
+    fn player_body(sim: &Simulation) -> (Vector2, f32, Vector2) {
+        let collider = &sim.player.collider;
+        (
+            collider.get_pos(&sim.collision_world),
+            collider.get_angle(&sim.collision_world),
+            collider.get_linvel(&sim.collision_world),
+        )
+    }
+
+    fn primary_rounds(sim: &Simulation) -> Option<u32> {
+        let primary = sim.player.inventory.equipment.primary.as_ref();
+        primary.and_then(|item| item.rounds)
+    }
+
+    #[test]
+    fn version_1_saves_go_through_every_migration() {
+        let save = SaveFile::from_json(V1_SAVE).unwrap();
+        assert_eq!(save.version, SAVE_VERSION);
+        let sim = save.restore(&GameMap::load_map("maps/map.tmx")).unwrap();
+
+        // Stats the items had when the migrations were written, not whatever they are now
+        let rifle = &sim.player.inventory.pockets.items[&(0, 0)];
+        assert_eq!(rifle.item, Item::Rifle);
+        assert_eq!(rifle.rounds, Some(20));
+        assert_eq!(sim.player.health, 80.0);
+        let medkit = sim.game_world.ground_items[0].item();
+        assert_eq!(
+            (medkit.item.clone(), medkit.charges),
+            (Item::MedKit, Some(3))
+        );
+        assert_eq!(sim.game_world.corpses[0].loot.size, (8, 10));
+        assert!(sim.game_world.corpses[0].loot.items.is_empty());
+        assert_eq!(sim.game_world.bullets[0].damage, 30.0);
+        assert_eq!(sim.game_world.bullets[0].penetration, 4);
+        assert_eq!(sim.clock.time(), 12.0);
+        assert_eq!(sim.clock.day_length, WorldClock::DEFAULT_DAY_LENGTH);
+        assert_eq!(sim.night_spawn_timer, Simulation::NIGHT_SPAWN_INTERVAL);
+        assert_eq!(sim.ambient_light.id(), 1);
+    }
+
+    #[test]
+    fn newer_saves_are_rejected() {
+        assert!(matches!(
+            SaveFile::from_json(&format!(r#"{{ "version": {} }}"#, SAVE_VERSION + 1)),
+            Err(SaveError::UnsupportedVersion(version)) if version == SAVE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn restored_games_carry_on_like_the_saved_one() {
+        let map = GameMap::load_map("maps/map.tmx");
+        let mut sim = Simulation::new(GameWorld::with_seed(5));
+        sim.load_map(&map);
+        let inventory = &mut sim.player.inventory;
+        inventory.equipment.primary = Some(Item::Rifle.to_inventory_item(false));
+        inventory.active_weapon = Some(EquipmentSlot::Primary);
+        inventory
+            .try_insert(Item::Ammo(Caliber::Rifle).to_inventory_item(false))
+            .unwrap();
+        sim.game_world
+            .ground_items
+            .push(Item::Bandage.to_ground_item(Vector2::new(22.0, 20.0)));
+        sim.clock.set_time(19.5);
+        sim.clock.day_length = 90.0;
+        let input = PlayerInput {
+            movement: Vector2::new(1.0, 1.0),
+            aim_pos: Vector2::new(40.0, 20.0),
+            firing: true,
+            ..Default::default()
+        };
+        for _ in 0..30 {
+            sim.update(DT, &input);
+        }
+
+        let json = serde_json::to_string(&SaveFile::capture(&sim)).unwrap();
+        let mut restored = SaveFile::from_json(&json).unwrap().restore(&map).unwrap();
+        let (pos, rotation, vel) = player_body(&restored);
+        let saved = player_body(&sim);
+        assert_eq!((pos, vel), (saved.0, saved.2));
+        assert!((rotation - saved.1).abs() < 1e-5);
+        assert_eq!(primary_rounds(&restored), primary_rounds(&sim));
+        assert_eq!(
+            restored.game_world.bullets.len(),
+            sim.game_world.bullets.len()
+        );
+        assert_eq!(restored.game_world.ground_items.len(), 1);
+        assert_eq!(restored.clock, sim.clock);
+        assert_eq!(
+            restored.game_world.rng.clone().gen::<u64>(),
+            sim.game_world.rng.clone().gen::<u64>()
+        );
+
+        let walk = PlayerInput {
+            firing: false,
+            ..input
+        };
+        for _ in 0..30 {
+            sim.update(DT, &walk);
+            restored.update(DT, &walk);
+        }
+        assert!(player_body(&restored).0.distance_to(player_body(&sim).0) < 1e-3);
+    }
+}
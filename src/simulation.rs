@@ -0,0 +1,557 @@
+use rand::Rng;
+use raylib::prelude::*;
+use std::f32::consts::TAU;
+
+use crate::{
+    chunks::WorldChunks,
+    clock::WorldClock,
+    collision_world::CollisionWorld,
+    game_map::GameMap,
+    ground::GroundMap,
+    input::PlayerInput,
+    interaction::Interaction,
+    lighting::{LightEngine, LightHandle},
+    nav::NavGrid,
+    visibility::Visibility,
+    GameWorld, Player,
+};
+
+// The game state that advances each frame. Owns nothing from raylib's window or GPU,
+// so it can be stepped with an explicit dt in CI or tests.
+pub struct Simulation {
+    pub collision_world: CollisionWorld,
+    pub game_world: GameWorld,
+    pub player: Player,
+    pub light_engine: LightEngine,
+    pub interaction: Interaction,
+    pub nav_grid: NavGrid,
+    // What the ground is like across the map, slowing characters down or making them swim
+    pub ground: GroundMap,
+    // Map colliders and sleeping entities, streamed in around the player
+    pub chunks: WorldChunks,
+    // What the player can see, updated at the end of every update
+    pub visibility: Visibility,
+    pub clock: WorldClock,
+    // Ambient light following the time of day
+    pub ambient_light: LightHandle,
+    // Game seconds until the next hostile character wanders in at night
+    pub night_spawn_timer: f32,
+}
+
+impl Simulation {
+    pub const NIGHT_SPAWN_INTERVAL: f32 = 30.0;
+    const MAX_NIGHT_SPAWNS: usize = 6;
+    // Out of the player's sight, but inside the chunks streamed in around them
+    const NIGHT_SPAWN_DISTANCE: f32 = 35.0;
+
+    pub fn new(game_world: GameWorld) -> Self {
+        let mut collision_world = CollisionWorld::default();
+        let mut light_engine = LightEngine::new();
+        // A new light engine has room for the player's and the ambient light
+        let player = Player::new(&mut collision_world, &mut light_engine).unwrap();
+        let clock = WorldClock::new(WorldClock::DEFAULT_DAY_LENGTH);
+        let ambient_light = light_engine.spawn_light(clock.ambient_light()).unwrap();
+        Simulation {
+            collision_world,
+            game_world,
+            player,
+            light_engine,
+            interaction: Interaction::new(),
+            nav_grid: NavGrid::new(Rectangle::default(), vec![]),
+            ground: GroundMap::new(Vector2::one()),
+            chunks: WorldChunks::new(vec![]),
+            visibility: Visibility::new(Vector2::zero()),
+            clock,
+            ambient_light,
+            night_spawn_timer: Self::NIGHT_SPAWN_INTERVAL,
+        }
+    }
+
+    // Spawns the map's walls around the player and bakes where characters can walk around them.
+    // Walls further away are spawned as the player gets near, and re-baked then.
+    pub fn load_map(&mut self, map: &GameMap) {
+        self.chunks = WorldChunks::new(map.colliders().to_vec());
+        self.stream_chunks();
+        self.nav_grid = NavGrid::new(map.bounds(), map.unwalkable_areas());
+        self.nav_grid.bake(&mut self.collision_world);
+        self.ground = map.ground();
+    }
+
+    fn stream_chunks(&mut self) {
+        self.chunks.update(
+            self.player.collider.get_pos(&self.collision_world),
+            &mut self.collision_world,
+            &mut self.game_world,
+            &mut self.light_engine,
+            &mut self.player.inventory.looting,
+        );
+    }
+
+    pub fn update(&mut self, dt: f32, input: &PlayerInput) {
+        self.clock.update(dt);
+        self.light_engine
+            .update_light(&self.ambient_light, self.clock.ambient_light());
+        self.stream_chunks();
+        self.game_world.footsteps.clear();
+        self.game_world
+            .handle_corpses(dt, &mut self.player.inventory.looting);
+        self.player
+            .handle_controls(input, dt, &mut self.collision_world, &self.ground);
+        self.game_world
+            .footsteps
+            .extend(self.player.take_step(dt, &self.collision_world));
+        self.player.handle_consumables(input, dt);
+        self.player.handle_bleeding(dt);
+        self.player.handle_needs(dt);
+        self.handle_dummies(dt);
+        self.interaction.update(
+            input,
+            dt,
+            &mut self.player,
+            &mut self.game_world,
+            &mut self.collision_world,
+        );
+        self.player.handle_shooting(
+            input,
+            dt,
+            &mut self.collision_world,
+            &mut self.game_world.bullets,
+            &mut self.game_world.rng,
+        );
+        self.player
+            .update_player_light(&mut self.light_engine, &mut self.collision_world);
+        self.player.handle_spawning_dunmmies(
+            input,
+            &mut self.collision_world,
+            &mut self.game_world,
+            &mut self.light_engine,
+        );
+        self.handle_night_spawns(dt);
+        self.game_world
+            .handle_bullet_physics(dt, &mut self.collision_world);
+        // Cleared right before the step, so brains still get to react to the last one's hits
+        self.game_world.damage_events.clear();
+        self.collision_world.step(
+            dt,
+            &mut self.player,
+            &mut self.game_world,
+            &mut self.light_engine,
+        );
+        self.handle_player_death();
+        self.game_world.handle_hit_markers(dt);
+        self.nav_grid.update(&mut self.collision_world);
+        self.visibility = Visibility::compute(
+            self.player.collider.get_pos(&self.collision_world),
+            &self.collision_world,
+        );
+    }
+
+    // A dead player leaves their body, with everything they carried, and starts over
+    fn handle_player_death(&mut self) {
+        if self.player.health > 0.0 {
+            return;
+        }
+        let corpse = self.player.get_corpse(&mut self.collision_world);
+        self.game_world.corpses.push(corpse);
+        self.player.respawn(&mut self.collision_world);
+    }
+
+    // Hostile characters wander in around the player at night, none come during the day.
+    // Dummies asleep in unloaded chunks count towards the limit too.
+    fn handle_night_spawns(&mut self, dt: f32) {
+        let dummies = self.game_world.dummies.len() + self.chunks.dormant_dummies();
+        if !self.clock.is_night() || dummies >= Self::MAX_NIGHT_SPAWNS {
+            return;
+        }
+        self.night_spawn_timer -= dt * self.clock.speed;
+        if self.night_spawn_timer > 0.0 {
+            return;
+        }
+        self.night_spawn_timer = Self::NIGHT_SPAWN_INTERVAL;
+        let angle = self.game_world.rng.gen_range(0.0..TAU);
+        let pos = self.player.collider.get_pos(&self.collision_world)
+            + Vector2::new(angle.cos(), angle.sin()) * Self::NIGHT_SPAWN_DISTANCE;
+        // Tried again next time if it fell in a wall, off the map or in the water
+        if !self.nav_grid.is_walkable_at(pos) || self.ground.ground_at(pos).swimmable {
+            return;
+        }
+        // Also tried again next time if there's no room for its light
+        let Ok(dummy) = Player::new_dummy(pos, &mut self.collision_world, &mut self.light_engine)
+        else {
+            return;
+        };
+        self.game_world.dummies.push(dummy);
+    }
+
+    // Lets every dummy's brain pick its input, then runs it through the same controls,
+    // consumables and shooting as the player
+    fn handle_dummies(&mut self, dt: f32) {
+        for dummy in &mut self.game_world.dummies {
+            dummy.handle_bleeding(dt);
+            let Some(mut brain) = dummy.brain.take() else {
+                continue;
+            };
+            let input = brain.think(
+                dt,
+                dummy,
+                &self.player,
+                &self.game_world.damage_events,
+                &self.collision_world,
+                &self.nav_grid,
+                &self.clock,
+                &mut self.game_world.rng,
+            );
+            dummy.brain = Some(brain);
+            dummy.handle_controls(&input, dt, &mut self.collision_world, &self.ground);
+            self.game_world
+                .footsteps
+                .extend(dummy.take_step(dt, &self.collision_world));
+            dummy.handle_consumables(&input, dt);
+            dummy.handle_shooting(
+                &input,
+                dt,
+                &mut self.collision_world,
+                &mut self.game_world.bullets,
+                &mut self.game_world.rng,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ai::{AiState, Brain},
+        chunks::CHUNK_SIZE,
+        collision_world::{ColliderArgs, RigidBodyArgs, ShapeArgs},
+        damage::{DamageTarget, HitZone},
+        equipment::EquipmentSlot,
+        inventory::Item,
+        weapon::Caliber,
+    };
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn run(sim: &mut Simulation, ticks: u32, input: &PlayerInput) {
+        for _ in 0..ticks {
+            sim.update(DT, input);
+        }
+    }
+
+    fn player_pos(sim: &Simulation) -> Vector2 {
+        sim.player.collider.get_pos(&sim.collision_world)
+    }
+
+    // Player holding a loaded rifle, in an empty world
+    fn armed_simulation(seed: u64) -> Simulation {
+        let mut sim = Simulation::new(GameWorld::with_seed(seed));
+        sim.player.inventory.equipment.primary = Some(Item::Rifle.to_inventory_item(false));
+        sim.player.inventory.active_weapon = Some(EquipmentSlot::Primary);
+        sim
+    }
+
+    // A dummy without a brain, so it stands still
+    fn spawn_dummy(sim: &mut Simulation, pos: Vector2) {
+        let mut dummy = Player::new(&mut sim.collision_world, &mut sim.light_engine).unwrap();
+        dummy.collider.set_pos(pos, &mut sim.collision_world);
+        sim.game_world.dummies.push(dummy);
+    }
+
+    #[test]
+    fn holding_move_walks_the_player() {
+        let mut sim = Simulation::new(GameWorld::with_seed(0));
+        let start = player_pos(&sim);
+        let input = PlayerInput {
+            movement: Vector2::new(1.0, 0.0),
+            aim_pos: start + Vector2::new(10.0, 0.0),
+            ..Default::default()
+        };
+        run(&mut sim, 60, &input);
+        // Close to walking speed after speeding up for a second
+        let moved = player_pos(&sim) - start;
+        assert!(moved.x > 3.0 && moved.x < 4.5, "moved {:?}", moved);
+        assert!(moved.y.abs() < 1e-3);
+
+        // and slows back down once the key is let go
+        run(&mut sim, 120, &PlayerInput::default());
+        let stopped_at = player_pos(&sim);
+        run(&mut sim, 60, &PlayerInput::default());
+        assert!(player_pos(&sim).distance_to(stopped_at) < 0.1);
+    }
+
+    #[test]
+    fn firing_at_a_dummy_lowers_its_health() {
+        let mut sim = armed_simulation(0);
+        let target = player_pos(&sim) + Vector2::new(0.0, 10.0);
+        spawn_dummy(&mut sim, target);
+        let aim = PlayerInput {
+            aim_pos: target,
+            ..Default::default()
+        };
+        sim.update(
+            DT,
+            &PlayerInput {
+                firing: true,
+                ..aim.clone()
+            },
+        );
+        assert_eq!(sim.game_world.bullets.len(), 1);
+        assert_eq!(sim.player.weapon.as_ref().unwrap().rounds_in_magazine, 19);
+        run(&mut sim, 30, &aim);
+        assert!(sim.game_world.dummies[0].health < Player::MAX_HEALTH);
+    }
+
+    #[test]
+    fn hits_are_reported_as_damage_events() {
+        let mut sim = armed_simulation(3);
+        // The dummy faces down and is shot from the side, so the bullet can't reach its head
+        let target = player_pos(&sim) + Vector2::new(10.0, 0.0);
+        spawn_dummy(&mut sim, target);
+        let aim = PlayerInput {
+            aim_pos: target,
+            ..Default::default()
+        };
+        sim.update(
+            DT,
+            &PlayerInput {
+                firing: true,
+                ..aim.clone()
+            },
+        );
+        let mut events = vec![];
+        for _ in 0..30 {
+            sim.update(DT, &aim);
+            if let Some(event) = sim.game_world.damage_events.first() {
+                assert_eq!(
+                    sim.game_world.dummies[0].health,
+                    Player::MAX_HEALTH - event.wound.damage
+                );
+            }
+            events.extend(sim.game_world.damage_events.iter().copied());
+        }
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].target, DamageTarget::Dummy);
+        assert_eq!(
+            events[0].collider,
+            sim.game_world.dummies[0].collider.collider_handle
+        );
+        assert!(events[0].direction.x > 0.99);
+        assert_ne!(events[0].zone, HitZone::Head);
+        assert!(events[0].wound.damage > 0.0);
+    }
+
+    #[test]
+    fn dead_players_leave_a_corpse_and_respawn() {
+        let mut sim = armed_simulation(0);
+        let died_at = Vector2::new(40.0, 40.0);
+        sim.player
+            .collider
+            .set_pos(died_at, &mut sim.collision_world);
+        sim.player.health = 0.01;
+        sim.player.bleeding = 5.0;
+        sim.update(DT, &PlayerInput::default());
+
+        assert_eq!(sim.game_world.corpses.len(), 1);
+        let corpse = &sim.game_world.corpses[0];
+        assert!(corpse.pos.distance_to(died_at) < 0.1);
+        assert!(corpse
+            .loot
+            .items
+            .values()
+            .any(|item| item.item == Item::Rifle));
+        assert_eq!(player_pos(&sim), Player::SPAWN_POS);
+        assert_eq!(sim.player.health, Player::MAX_HEALTH);
+        assert_eq!(sim.player.bleeding, 0.0);
+        assert!(sim.player.inventory.equipment.primary.is_none());
+    }
+
+    #[test]
+    fn dummy_chases_shoots_reloads_and_flees() {
+        let mut sim = Simulation::new(GameWorld::with_seed(11));
+        sim.clock.set_time(12.0);
+        let start = player_pos(&sim) + Vector2::new(25.0, 0.0);
+        let mut dummy = Player::new(&mut sim.collision_world, &mut sim.light_engine).unwrap();
+        dummy.collider.set_pos(start, &mut sim.collision_world);
+        dummy.aim_at(player_pos(&sim), &mut sim.collision_world);
+        dummy.brain = Some(Brain::new(start));
+        dummy.inventory.equipment.secondary = Some(Item::Pistol.to_inventory_item(false));
+        dummy.inventory.active_weapon = Some(EquipmentSlot::Secondary);
+        dummy
+            .inventory
+            .try_insert(Item::Magazine(Caliber::Pistol).to_inventory_item(false))
+            .unwrap();
+        sim.game_world.dummies.push(dummy);
+
+        let state = |sim: &Simulation| sim.game_world.dummies[0].brain.as_ref().unwrap().state;
+        let distance = |sim: &Simulation| {
+            let dummy = &sim.game_world.dummies[0];
+            dummy
+                .collider
+                .get_pos(&sim.collision_world)
+                .distance_to(player_pos(sim))
+        };
+        let rounds = |sim: &Simulation| {
+            let weapon = sim.game_world.dummies[0].weapon.as_ref();
+            weapon.map_or(0, |weapon| weapon.rounds_in_magazine)
+        };
+        // Keeps the player standing so the dummy keeps shooting
+        let update = |sim: &mut Simulation| {
+            sim.player.health = Player::MAX_HEALTH;
+            sim.player.bleeding = 0.0;
+            sim.update(DT, &PlayerInput::default());
+        };
+
+        // Sees the player out of range and closes in
+        update(&mut sim);
+        assert!(matches!(state(&sim), AiState::Chase { .. }));
+        for _ in 0..600 {
+            if matches!(state(&sim), AiState::Attack { .. }) {
+                break;
+            }
+            update(&mut sim);
+        }
+        assert!(matches!(state(&sim), AiState::Attack { .. }));
+        assert!(distance(&sim) < 20.5);
+
+        // Empties the pistol, pulling the trigger on the empty pistol swaps in the magazine
+        let mut shots = 0;
+        let mut reloaded = false;
+        for _ in 0..600 {
+            let before = rounds(&sim);
+            update(&mut sim);
+            if rounds(&sim) < before {
+                shots += 1;
+            }
+            if before == 0 && rounds(&sim) == Caliber::Pistol.magazine_size() {
+                reloaded = true;
+                break;
+            }
+        }
+        assert_eq!(shots, Caliber::Pistol.magazine_size());
+        assert!(reloaded);
+
+        // Badly hurt, it runs from the player
+        sim.game_world.dummies[0].health = 20.0;
+        let hurt_at = distance(&sim);
+        update(&mut sim);
+        assert!(matches!(state(&sim), AiState::Flee { .. }));
+        for _ in 0..90 {
+            update(&mut sim);
+        }
+        assert!(matches!(state(&sim), AiState::Flee { .. }));
+        assert!(distance(&sim) > hurt_at + 5.0);
+    }
+
+    #[test]
+    fn bleeding_dummies_do_not_sense_the_player_through_walls() {
+        let mut sim = Simulation::new(GameWorld::with_seed(13));
+        sim.clock.set_time(12.0);
+        sim.collision_world.spawn_collider(
+            RigidBodyArgs {
+                dynamic: false,
+                pos: player_pos(&sim) + Vector2::new(7.5, 0.0),
+                ..Default::default()
+            },
+            ColliderArgs::default(),
+            ShapeArgs::Cuboid {
+                half_extents: Vector2::new(0.5, 10.0),
+            },
+        );
+        // Facing the player, only the wall hides it
+        let start = player_pos(&sim) + Vector2::new(15.0, 0.0);
+        let mut dummy =
+            Player::new_dummy(start, &mut sim.collision_world, &mut sim.light_engine).unwrap();
+        dummy.aim_at(player_pos(&sim), &mut sim.collision_world);
+        dummy.health = 80.0;
+        dummy.bleeding = 2.0;
+        sim.game_world.dummies.push(dummy);
+
+        for _ in 0..300 {
+            sim.update(DT, &PlayerInput::default());
+            let dummy = &sim.game_world.dummies[0];
+            let state = dummy.brain.as_ref().unwrap().state;
+            assert!(
+                matches!(state, AiState::Idle { .. } | AiState::Patrol { .. }),
+                "{:?}",
+                state
+            );
+            let pos = dummy.collider.get_pos(&sim.collision_world);
+            assert!(pos.distance_to(player_pos(&sim)) > 8.0);
+        }
+        assert!(sim.game_world.dummies[0].health < 80.0);
+    }
+
+    #[test]
+    fn hostiles_wander_in_only_at_night() {
+        let mut sim = Simulation::new(GameWorld::with_seed(3));
+        sim.load_map(&GameMap::load_map("maps/map.tmx"));
+        // Hold the time of day still
+        sim.clock.day_length = 1.0e6;
+        let idle = PlayerInput::default();
+        for _ in 0..120 {
+            sim.update(0.5, &idle);
+        }
+        assert!(sim.game_world.dummies.is_empty());
+
+        sim.clock.set_time(0.0);
+        let spawned = (0..1200).any(|_| {
+            sim.update(0.5, &idle);
+            !sim.game_world.dummies.is_empty()
+        });
+        assert!(spawned);
+        let dummy = &sim.game_world.dummies[0];
+        let distance = dummy
+            .collider
+            .get_pos(&sim.collision_world)
+            .distance_to(player_pos(&sim));
+        assert!((distance - Simulation::NIGHT_SPAWN_DISTANCE).abs() < 1.0);
+        assert!(dummy.brain.is_some());
+    }
+
+    #[test]
+    fn sleeping_dummies_count_towards_the_night_spawn_limit() {
+        let mut sim = Simulation::new(GameWorld::with_seed(3));
+        sim.load_map(&GameMap::load_map("maps/map.tmx"));
+        sim.clock.day_length = 1.0e6;
+        let far = player_pos(&sim) + Vector2::new(CHUNK_SIZE * 10.0, 0.0);
+        for _ in 0..Simulation::MAX_NIGHT_SPAWNS {
+            spawn_dummy(&mut sim, far);
+        }
+        let idle = PlayerInput::default();
+        sim.update(DT, &idle);
+        assert_eq!(sim.chunks.dormant_dummies(), Simulation::MAX_NIGHT_SPAWNS);
+
+        sim.clock.set_time(0.0);
+        for _ in 0..1200 {
+            sim.update(0.5, &idle);
+        }
+        assert!(sim.game_world.dummies.is_empty());
+    }
+
+    #[test]
+    fn same_seed_and_input_give_the_same_run() {
+        let run_once = || {
+            let mut sim = armed_simulation(7);
+            let target = player_pos(&sim) + Vector2::new(12.0, 4.0);
+            spawn_dummy(&mut sim, target);
+            let input = PlayerInput {
+                movement: Vector2::new(0.0, 1.0),
+                aim_pos: target,
+                firing: true,
+                ..Default::default()
+            };
+            run(&mut sim, 120, &input);
+            (
+                player_pos(&sim),
+                sim.game_world
+                    .dummies
+                    .iter()
+                    .map(|dummy| dummy.health)
+                    .collect::<Vec<f32>>(),
+                sim.game_world.corpses.len(),
+            )
+        };
+        assert_eq!(run_once(), run_once());
+    }
+}
@@ -0,0 +1,47 @@
+use raylib::prelude::*;
+use std::collections::HashMap;
+
+use crate::ground::Footstep;
+
+// Footstep sounds by the ground's `footstep` property, loaded from sounds/footsteps/<footstep>.wav
+pub struct FootstepSounds<'aud> {
+    sounds: HashMap<String, Sound<'aud>>,
+}
+
+impl<'aud> FootstepSounds<'aud> {
+    const DIR: &str = "sounds/footsteps";
+    // Footsteps further away than this from the listener can't be heard
+    const HEARING_DISTANCE: f32 = 40.0;
+
+    // Without an audio device nothing is loaded, and footsteps stay silent
+    pub fn load(audio: Option<&'aud RaylibAudio>) -> Self {
+        let mut sounds = HashMap::new();
+        let (Some(audio), Ok(dir)) = (audio, std::fs::read_dir(Self::DIR)) else {
+            return FootstepSounds { sounds };
+        };
+        for path in dir.flatten().map(|entry| entry.path()) {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            match audio.new_sound(&path.to_string_lossy()) {
+                Ok(sound) => {
+                    sounds.insert(name.to_string(), sound);
+                }
+                Err(err) => eprintln!("Failed to load {}: {}", path.display(), err),
+            }
+        }
+        FootstepSounds { sounds }
+    }
+
+    // Plays the footsteps quieter the further they are from the listener
+    pub fn play(&self, footsteps: &[Footstep], listener: Vector2) {
+        for footstep in footsteps {
+            let volume = 1.0 - footstep.pos.distance_to(listener) / Self::HEARING_DISTANCE;
+            if volume <= 0.0 {
+                continue;
+            }
+            if let Some(sound) = self.sounds.get(&footstep.sound) {
+                sound.set_volume(volume);
+                sound.play();
+            }
+        }
+    }
+}
@@ -0,0 +1,377 @@
+use libnoise::prelude::*;
+use std::fmt::Write;
+
+// Ground types of `tilesets/ground_tiles.tsx`, in the order their layers are stacked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainKind {
+    Water,
+    Sand,
+    Grass,
+    Dirt,
+    Road,
+}
+
+impl TerrainKind {
+    pub const ALL: [TerrainKind; 5] = [
+        TerrainKind::Water,
+        TerrainKind::Sand,
+        TerrainKind::Grass,
+        TerrainKind::Dirt,
+        TerrainKind::Road,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            TerrainKind::Water => "Water",
+            TerrainKind::Sand => "Sand",
+            TerrainKind::Grass => "Grass",
+            TerrainKind::Dirt => "Dirt",
+            TerrainKind::Road => "Road",
+        }
+    }
+
+    // Tileset row the kind's tiles start at. Every kind has a 4x4 block of a patch with its
+    // edges, followed by a 4x4 block of a hole with its inner corners.
+    fn tileset_row(&self) -> u32 {
+        match self {
+            TerrainKind::Dirt => 0,
+            TerrainKind::Grass => 4,
+            TerrainKind::Sand => 8,
+            TerrainKind::Water => 12,
+            TerrainKind::Road => 16,
+        }
+    }
+
+    // Layer properties as (name, type, value), the same the hand made map uses
+    fn properties(&self) -> &'static [(&'static str, &'static str, &'static str)] {
+        match self {
+            TerrainKind::Water => &[
+                ("footstep", "string", "water"),
+                ("friction", "float", "0.4"),
+                ("speed_multiplier", "float", "0.4"),
+                ("swimmable", "bool", "true"),
+                ("walkable", "bool", "false"),
+            ],
+            TerrainKind::Sand => &[
+                ("footstep", "string", "sand"),
+                ("speed_multiplier", "float", "0.8"),
+            ],
+            TerrainKind::Grass => &[("footstep", "string", "grass")],
+            TerrainKind::Dirt => &[
+                ("footstep", "string", "dirt"),
+                ("speed_multiplier", "float", "0.9"),
+            ],
+            TerrainKind::Road => &[
+                ("footstep", "string", "road"),
+                ("speed_multiplier", "float", "1.1"),
+            ],
+        }
+    }
+}
+
+// One layer of generated tiles, as global tile ids with 0 for no tile
+pub struct TerrainLayer {
+    pub kind: TerrainKind,
+    pub tiles: Vec<u32>,
+}
+
+// A generated map, laid out like the tile layers of a Tiled map
+pub struct Terrain {
+    pub width: u32,
+    pub height: u32,
+    pub layers: Vec<TerrainLayer>,
+}
+
+impl Terrain {
+    pub const TILE_SIZE: u32 = 64;
+    const TILESET_COLUMNS: u32 = 13;
+    const FIRST_GID: u32 = 1;
+
+    // The map as a Tiled .tmx file. `tileset_source` is the path of `ground_tiles.tsx`
+    // relative to where the file is written.
+    pub fn to_tmx(&self, tileset_source: &str) -> String {
+        let mut tmx = String::new();
+        writeln!(tmx, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(
+            tmx,
+            r#"<map version="1.10" tiledversion="1.10.2" orientation="orthogonal" renderorder="right-down" width="{}" height="{}" tilewidth="{}" tileheight="{}" infinite="0" nextlayerid="{}" nextobjectid="1">"#,
+            self.width,
+            self.height,
+            Self::TILE_SIZE,
+            Self::TILE_SIZE,
+            self.layers.len() + 1
+        )
+        .unwrap();
+        writeln!(
+            tmx,
+            r#" <tileset firstgid="{}" source="{}"/>"#,
+            Self::FIRST_GID,
+            tileset_source
+        )
+        .unwrap();
+        for (i, layer) in self.layers.iter().enumerate() {
+            writeln!(
+                tmx,
+                r#" <layer id="{}" name="{}" width="{}" height="{}">"#,
+                i + 1,
+                layer.kind.name(),
+                self.width,
+                self.height
+            )
+            .unwrap();
+            tmx.push_str("  <properties>\n");
+            for (name, kind, value) in layer.kind.properties() {
+                writeln!(
+                    tmx,
+                    r#"   <property name="{}" type="{}" value="{}"/>"#,
+                    name, kind, value
+                )
+                .unwrap();
+            }
+            tmx.push_str("  </properties>\n");
+            tmx.push_str("  <data encoding=\"csv\">\n");
+            let rows = layer
+                .tiles
+                .chunks(self.width as usize)
+                .map(|row| {
+                    row.iter()
+                        .map(|tile| tile.to_string())
+                        .collect::<Vec<String>>()
+                        .join(",")
+                })
+                .collect::<Vec<String>>();
+            tmx.push_str(&rows.join(",\n"));
+            tmx.push_str("\n</data>\n </layer>\n");
+        }
+        tmx.push_str("</map>\n");
+        tmx
+    }
+
+    pub fn export(&self, path: &str, tileset_source: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_tmx(tileset_source))
+    }
+}
+
+// Builds maps from layers of noise: elevation decides water, sand and grass, moisture
+// leaves dry patches of dirt, and roads follow where a third noise crosses zero.
+// The same seed always gives the same map.
+pub struct TerrainGenerator {
+    seed: u64,
+    width: u32,
+    height: u32,
+}
+
+impl TerrainGenerator {
+    // Noise frequencies, in cycles per tile
+    const ELEVATION_FREQUENCY: f64 = 0.045;
+    const MOISTURE_FREQUENCY: f64 = 0.07;
+    const ROAD_FREQUENCY: f64 = 0.02;
+    const SEA_LEVEL: f64 = -0.2;
+    const SHORE_LEVEL: f64 = -0.1;
+    const DIRT_MOISTURE: f64 = -0.25;
+    // How close to zero the road noise has to be, sets how wide roads are
+    const ROAD_WIDTH: f64 = 0.035;
+    // Cells in a 4x4 tile block: rows then columns, 0 and 3 are edges, 1 and 2 fill
+    const FILL: [(u32, u32); 4] = [(1, 1), (1, 2), (2, 1), (2, 2)];
+
+    pub fn new(seed: u64, width: u32, height: u32) -> Self {
+        TerrainGenerator {
+            seed,
+            width,
+            height,
+        }
+    }
+
+    pub fn generate(&self) -> Terrain {
+        let elevation =
+            self.sample(Source::simplex(self.seed).fbm(5, Self::ELEVATION_FREQUENCY, 2.0, 0.5));
+        let moisture = self.sample(Source::simplex(self.seed.wrapping_add(1)).fbm(
+            3,
+            Self::MOISTURE_FREQUENCY,
+            2.0,
+            0.5,
+        ));
+        let roads = self.sample(Source::simplex(self.seed.wrapping_add(2)).fbm(
+            2,
+            Self::ROAD_FREQUENCY,
+            2.0,
+            0.4,
+        ));
+
+        // Each layer only covers cells the layer under it does, so its edges blend into it
+        let mut sand = elevation
+            .iter()
+            .map(|e| *e > Self::SEA_LEVEL)
+            .collect::<Vec<bool>>();
+        self.clean(&mut sand);
+        let mut grass = self.mask(&sand, |i| elevation[i] > Self::SHORE_LEVEL);
+        self.clean(&mut grass);
+        let mut dirt = self.mask(&grass, |i| moisture[i] < Self::DIRT_MOISTURE);
+        self.clean(&mut dirt);
+        let mut road = self.mask(&grass, |i| roads[i].abs() < Self::ROAD_WIDTH);
+        self.clean(&mut road);
+
+        let water = vec![true; (self.width * self.height) as usize];
+        let layers = TerrainKind::ALL
+            .into_iter()
+            .zip([water, sand, grass, dirt, road])
+            .map(|(kind, mask)| TerrainLayer {
+                kind,
+                tiles: self.autotile(kind, &mask),
+            })
+            .collect();
+        Terrain {
+            width: self.width,
+            height: self.height,
+            layers,
+        }
+    }
+
+    fn sample(&self, generator: impl Generator<2>) -> Vec<f64> {
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| generator.sample([x as f64, y as f64]))
+            .collect()
+    }
+
+    fn mask(&self, below: &[bool], f: impl Fn(usize) -> bool) -> Vec<bool> {
+        (0..below.len()).map(|i| below[i] && f(i)).collect()
+    }
+
+    // Whether the mask covers a cell. Cells past the map's edge count as covered, so layers
+    // run off the edge instead of ending with a border.
+    fn covered(&self, mask: &[bool], x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return true;
+        }
+        mask[(y as u32 * self.width + x as u32) as usize]
+    }
+
+    // Removes cells no tile fits, those with nothing on two opposite sides
+    fn clean(&self, mask: &mut [bool]) {
+        loop {
+            let thin = (0..self.height as i32)
+                .flat_map(|y| (0..self.width as i32).map(move |x| (x, y)))
+                .filter(|(x, y)| {
+                    self.covered(mask, *x, *y)
+                        && (!self.covered(mask, x - 1, *y) && !self.covered(mask, x + 1, *y)
+                            || !self.covered(mask, *x, y - 1) && !self.covered(mask, *x, y + 1))
+                })
+                .collect::<Vec<(i32, i32)>>();
+            if thin.is_empty() {
+                return;
+            }
+            for (x, y) in thin {
+                mask[(y as u32 * self.width + x as u32) as usize] = false;
+            }
+        }
+    }
+
+    // Picks the tile for every covered cell from which of its neighbours are covered
+    fn autotile(&self, kind: TerrainKind, mask: &[bool]) -> Vec<u32> {
+        (0..self.height as i32)
+            .flat_map(|y| (0..self.width as i32).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                if !self.covered(mask, x, y) {
+                    return 0;
+                }
+                let has = |dx: i32, dy: i32| self.covered(mask, x + dx, y + dy);
+                let variant = self.variant(x, y);
+                let (n, s, w, e) = (has(0, -1), has(0, 1), has(-1, 0), has(1, 0));
+                // (row, column) in the patch block, or in the hole block past it
+                let (row, column) = match (n, s, w, e) {
+                    (false, _, false, _) => (0, 0),
+                    (false, _, _, false) => (0, 3),
+                    (_, false, false, _) => (3, 0),
+                    (_, false, _, false) => (3, 3),
+                    (false, ..) => (0, 1 + variant % 2),
+                    (_, false, ..) => (3, 1 + variant % 2),
+                    (_, _, false, _) => (1 + variant % 2, 0),
+                    (_, _, _, false) => (1 + variant % 2, 3),
+                    // Surrounded, but a missing diagonal needs an inner corner of the hole
+                    _ if !has(1, 1) => (1, 5),
+                    _ if !has(-1, 1) => (1, 6),
+                    _ if !has(1, -1) => (2, 5),
+                    _ if !has(-1, -1) => (2, 6),
+                    _ => Self::FILL[variant as usize % Self::FILL.len()],
+                };
+                Terrain::FIRST_GID + (kind.tileset_row() + row) * Terrain::TILESET_COLUMNS + column
+            })
+            .collect()
+    }
+
+    // Stable pseudo random number for a cell, to vary the tiles used
+    fn variant(&self, x: i32, y: i32) -> u32 {
+        let hash = (x as u64)
+            .wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            .wrapping_add((y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F))
+            .wrapping_add(self.seed);
+        (hash >> 32) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(terrain: &Terrain, kind: TerrainKind, x: u32, y: u32) -> Option<u32> {
+        let layer = terrain.layers.iter().find(|layer| layer.kind == kind)?;
+        let tile = layer.tiles[(y * terrain.width + x) as usize];
+        (tile != 0).then_some(tile)
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let a = TerrainGenerator::new(7, 32, 24).generate();
+        let b = TerrainGenerator::new(7, 32, 24).generate();
+        let c = TerrainGenerator::new(8, 32, 24).generate();
+        let tiles = |terrain: &Terrain| {
+            terrain
+                .layers
+                .iter()
+                .flat_map(|layer| layer.tiles.clone())
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(tiles(&a), tiles(&b));
+        assert_ne!(tiles(&a), tiles(&c));
+    }
+
+    #[test]
+    fn layers_sit_on_the_layer_below() {
+        let terrain = TerrainGenerator::new(3, 48, 48).generate();
+        for y in 0..terrain.height {
+            for x in 0..terrain.width {
+                assert!(tile(&terrain, TerrainKind::Water, x, y).is_some());
+                for (kind, below) in [
+                    (TerrainKind::Grass, TerrainKind::Sand),
+                    (TerrainKind::Dirt, TerrainKind::Grass),
+                    (TerrainKind::Road, TerrainKind::Grass),
+                ] {
+                    if tile(&terrain, kind, x, y).is_some() {
+                        assert!(tile(&terrain, below, x, y).is_some());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exports_every_layer_as_csv() {
+        let terrain = TerrainGenerator::new(1, 5, 4).generate();
+        let tmx = terrain.to_tmx("../tilesets/ground_tiles.tsx");
+        for kind in TerrainKind::ALL {
+            assert!(tmx.contains(&format!(r#"name="{}""#, kind.name())));
+        }
+        assert_eq!(tmx.matches(r#"name="walkable""#).count(), 1);
+        assert_eq!(tmx.matches(r#"name="swimmable""#).count(), 1);
+        let water = tmx
+            .split("<data encoding=\"csv\">\n")
+            .nth(1)
+            .unwrap()
+            .split("\n</data>")
+            .next()
+            .unwrap();
+        assert_eq!(water.lines().count(), 4);
+        assert_eq!(water.split(',').count(), 20);
+    }
+}
@@ -0,0 +1,262 @@
+use rapier2d::prelude::{
+    point, vector, Collider, ColliderHandle, Isometry, Point, QueryFilter, Ray, Real, Shape,
+};
+use raylib::prelude::*;
+
+use crate::{collision_world::CollisionWorld, ImprovedCamera};
+
+// What can be seen from a point, as a polygon around it cut off by walls. The same walls
+// block sight as in `CollisionWorld::can_see`.
+pub struct Visibility {
+    origin: Vector2,
+    // Where the rays cast from the origin stopped, sorted by angle
+    points: Vec<Vector2>,
+}
+
+impl Visibility {
+    pub const VIEW_RANGE: f32 = 40.0;
+    // Rays cast evenly around the origin so the edge of the view range is round
+    const RANGE_RAYS: usize = 48;
+    // Radians either side of a wall corner to cast extra rays, so they slip past the corner
+    const CORNER_OFFSET: f32 = 0.0005;
+
+    // Sees nothing until computed
+    pub fn new(origin: Vector2) -> Self {
+        Visibility {
+            origin,
+            points: vec![],
+        }
+    }
+
+    pub fn compute(origin: Vector2, collision_world: &CollisionWorld) -> Self {
+        let rapier = &collision_world.rapier;
+        let range = Rectangle::new(
+            origin.x - Self::VIEW_RANGE,
+            origin.y - Self::VIEW_RANGE,
+            Self::VIEW_RANGE * 2.0,
+            Self::VIEW_RANGE * 2.0,
+        );
+        let mut angles = (0..Self::RANGE_RAYS)
+            .map(|i| i as f32 / Self::RANGE_RAYS as f32 * std::f32::consts::TAU)
+            .collect::<Vec<f32>>();
+        for (_, collider) in rapier.collider_set.iter() {
+            if !collision_world.blocks_sight(collider) {
+                continue;
+            }
+            for corner in Self::occluder_corners(collider, origin, &range) {
+                let angle = (corner.y - origin.y).atan2(corner.x - origin.x);
+                angles.extend(
+                    [-Self::CORNER_OFFSET, 0.0, Self::CORNER_OFFSET]
+                        .map(|offset| (angle + offset).rem_euclid(std::f32::consts::TAU)),
+                );
+            }
+        }
+        angles.sort_by(f32::total_cmp);
+
+        let predicate =
+            |_handle: ColliderHandle, collider: &Collider| collision_world.blocks_sight(collider);
+        let points = angles
+            .into_iter()
+            .map(|angle| {
+                let dir = Vector2::new(angle.cos(), angle.sin());
+                let ray = Ray::new(point![origin.x, origin.y], vector![dir.x, dir.y]);
+                let distance = rapier
+                    .query_pipeline
+                    .cast_ray(
+                        &rapier.rigid_body_set,
+                        &rapier.collider_set,
+                        &ray,
+                        Self::VIEW_RANGE,
+                        true,
+                        QueryFilter {
+                            predicate: Some(&predicate),
+                            ..Default::default()
+                        },
+                    )
+                    .map_or(Self::VIEW_RANGE, |(_, distance)| distance);
+                origin + dir * distance
+            })
+            .collect();
+        Visibility { origin, points }
+    }
+
+    // Corners of a wall that overlaps `range` which rays from the origin can slip past, in
+    // world units
+    fn occluder_corners(collider: &Collider, origin: Vector2, range: &Rectangle) -> Vec<Vector2> {
+        let aabb = collider.compute_aabb();
+        let bounds = Rectangle::new(
+            aabb.mins.x,
+            aabb.mins.y,
+            aabb.maxs.x - aabb.mins.x,
+            aabb.maxs.y - aabb.mins.y,
+        );
+        if !bounds.check_collision_recs(range) {
+            return vec![];
+        }
+        Self::shape_corners(collider.position(), collider.shape(), origin)
+    }
+
+    fn shape_corners(
+        position: &Isometry<Real>,
+        shape: &dyn Shape,
+        origin: Vector2,
+    ) -> Vec<Vector2> {
+        let to_world = |corner: Point<Real>| {
+            let corner = position * corner;
+            Vector2::new(corner.x, corner.y)
+        };
+        if let Some(cuboid) = shape.as_cuboid() {
+            let half_extents = cuboid.half_extents;
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .into_iter()
+                .map(|(x, y)| to_world(point![half_extents.x * x, half_extents.y * y]))
+                .collect()
+        } else if let Some(triangle) = shape.as_triangle() {
+            [triangle.a, triangle.b, triangle.c]
+                .into_iter()
+                .map(to_world)
+                .collect()
+        } else if let Some(polygon) = shape.as_convex_polygon() {
+            polygon.points().iter().copied().map(to_world).collect()
+        } else if let Some(compound) = shape.as_compound() {
+            compound
+                .shapes()
+                .iter()
+                .flat_map(|(part_position, part)| {
+                    Self::shape_corners(&(position * part_position), &*part.0, origin)
+                })
+                .collect()
+        } else if let Some(ball) = shape.as_ball() {
+            // Where the rays from the origin touch the edge of the circle
+            let center = to_world(point![0.0, 0.0]);
+            let distance = center.distance_to(origin);
+            if distance <= ball.radius {
+                return vec![];
+            }
+            let angle = (center.y - origin.y).atan2(center.x - origin.x);
+            let spread = (ball.radius / distance).asin();
+            let tangent_length = (distance * distance - ball.radius * ball.radius).sqrt();
+            [angle - spread, angle + spread]
+                .into_iter()
+                .map(|angle| origin + Vector2::new(angle.cos(), angle.sin()) * tangent_length)
+                .collect()
+        } else {
+            let aabb = shape.compute_aabb(position);
+            [
+                (aabb.mins.x, aabb.mins.y),
+                (aabb.maxs.x, aabb.mins.y),
+                (aabb.maxs.x, aabb.maxs.y),
+                (aabb.mins.x, aabb.maxs.y),
+            ]
+            .into_iter()
+            .map(|(x, y)| Vector2::new(x, y))
+            .collect()
+        }
+    }
+
+    // Whether the point is inside the visibility polygon
+    pub fn can_see(&self, pos: Vector2) -> bool {
+        if pos.distance_to(self.origin) > Self::VIEW_RANGE || self.points.is_empty() {
+            return false;
+        }
+        // Even-odd rule, counting the polygon edges crossed by a ray going right from `pos`
+        let mut inside = false;
+        let mut previous = self.points[self.points.len() - 1];
+        for &point in &self.points {
+            if (point.y > pos.y) != (previous.y > pos.y) {
+                let t = (pos.y - previous.y) / (point.y - previous.y);
+                if pos.x < previous.x + (point.x - previous.x) * t {
+                    inside = !inside;
+                }
+            }
+            previous = point;
+        }
+        inside
+    }
+
+    // Whether any part of a circle can be seen, checked at its centre and 4 points on its edge
+    pub fn can_see_circle(&self, center: Vector2, radius: f32) -> bool {
+        [
+            Vector2::zero(),
+            Vector2::new(0.0, 1.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(0.0, -1.0),
+            Vector2::new(-1.0, 0.0),
+        ]
+        .into_iter()
+        .any(|offset| self.can_see(center + offset * radius))
+    }
+
+    // Fills the visibility polygon on screen
+    pub fn render(&self, d: &mut impl RaylibDraw, camera: &Camera2D, color: Color) {
+        if self.points.is_empty() {
+            return;
+        }
+        // Fans wind counter clockwise on screen, where y points down, so go through the
+        // points from the largest angle down
+        let points = std::iter::once(self.origin)
+            .chain(self.points.iter().rev().copied())
+            .chain(self.points.last().copied())
+            .map(|point| camera.to_screen(point))
+            .collect::<Vec<Vector2>>();
+        d.draw_triangle_fan(&points, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision_world::ShapeArgs;
+
+    #[test]
+    fn walls_block_sight() {
+        let mut collision_world = CollisionWorld::default();
+        collision_world.spawn_static(
+            Vector2::new(5.0, 0.0),
+            ShapeArgs::Cuboid {
+                half_extents: Vector2::new(0.5, 2.0),
+            },
+        );
+        // Updates the query pipeline
+        collision_world.rapier.step();
+        let visibility = Visibility::compute(Vector2::zero(), &collision_world);
+
+        assert!(visibility.can_see(Vector2::new(3.0, 0.0)));
+        assert!(!visibility.can_see(Vector2::new(10.0, 0.0)));
+        // Beside the wall and past its corner
+        assert!(visibility.can_see(Vector2::new(4.0, 2.5)));
+        assert!(visibility.can_see(Vector2::new(8.0, 4.0)));
+        assert!(!visibility.can_see(Vector2::new(Visibility::VIEW_RANGE + 1.0, 5.0)));
+    }
+
+    #[test]
+    fn circles_are_seen_by_their_edge() {
+        let mut collision_world = CollisionWorld::default();
+        collision_world.spawn_static(
+            Vector2::new(5.0, 0.0),
+            ShapeArgs::Cuboid {
+                half_extents: Vector2::new(0.5, 2.0),
+            },
+        );
+        collision_world.rapier.step();
+        let visibility = Visibility::compute(Vector2::zero(), &collision_world);
+
+        // Hidden behind the wall except for its edge poking out below
+        let center = Vector2::new(10.0, 3.5);
+        assert!(!visibility.can_see(center));
+        assert!(visibility.can_see_circle(center, 1.0));
+    }
+
+    #[test]
+    fn round_walls_block_sight() {
+        let mut collision_world = CollisionWorld::default();
+        collision_world.spawn_static(Vector2::new(5.0, 0.0), ShapeArgs::Ball { radius: 1.0 });
+        collision_world.rapier.step();
+        let visibility = Visibility::compute(Vector2::zero(), &collision_world);
+
+        assert!(visibility.can_see(Vector2::new(3.0, 0.0)));
+        assert!(!visibility.can_see(Vector2::new(10.0, 0.0)));
+        // Just past the edge of the circle
+        assert!(visibility.can_see(Vector2::new(10.0, 2.5)));
+    }
+}
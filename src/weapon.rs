@@ -0,0 +1,358 @@
+#![allow(dead_code)]
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    equipment::EquipmentSlot,
+    inventory::{InventoryItem, Item},
+    world_collider::WorldColliderHandle,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Caliber {
+    Rifle,
+    Pistol,
+}
+
+impl Caliber {
+    pub fn magazine_size(&self) -> u32 {
+        match self {
+            Caliber::Rifle => 20,
+            Caliber::Pistol => 12,
+        }
+    }
+
+    pub fn ammo_box_size(&self) -> u32 {
+        match self {
+            Caliber::Rifle => 30,
+            Caliber::Pistol => 50,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FireMode {
+    Semi,
+    Auto,
+    Burst { rounds: u32 },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FirearmStats {
+    pub caliber: Caliber,
+    // Modes the selector switches between, starting from the first when the firearm is drawn
+    pub fire_modes: &'static [FireMode],
+    // Minimum seconds between two shots
+    pub fire_interval: f32,
+    pub muzzle_velocity: f32,
+    // Max angle in radians a bullet can stray from the aim while standing still
+    pub spread: f32,
+    // Impulse pushing the shooter back. A hundredth of it is added to the spread per shot.
+    pub recoil: f32,
+    pub magazine_size: u32,
+    // Seconds to swap in a loaded magazine. Loading loose rounds takes longer.
+    pub reload_time: f32,
+    pub bullet_radius: f32,
+    // Damage of a hit to the torso, before armor
+    pub damage: f32,
+    // Highest armor class a bullet goes through is one below this
+    pub penetration: u32,
+}
+
+impl Item {
+    pub fn firearm_stats(&self) -> Option<FirearmStats> {
+        match self {
+            Item::Rifle => Some(FirearmStats {
+                caliber: Caliber::Rifle,
+                fire_modes: &[
+                    FireMode::Auto,
+                    FireMode::Burst { rounds: 3 },
+                    FireMode::Semi,
+                ],
+                fire_interval: 0.1,
+                muzzle_velocity: 160.0,
+                spread: std::f32::consts::PI / 100.0,
+                recoil: 0.5,
+                magazine_size: Caliber::Rifle.magazine_size(),
+                reload_time: 2.5,
+                bullet_radius: 0.1,
+                damage: 30.0,
+                penetration: 4,
+            }),
+            Item::Pistol => Some(FirearmStats {
+                caliber: Caliber::Pistol,
+                fire_modes: &[FireMode::Semi],
+                fire_interval: 0.15,
+                muzzle_velocity: 120.0,
+                spread: std::f32::consts::PI / 70.0,
+                recoil: 0.3,
+                magazine_size: Caliber::Pistol.magazine_size(),
+                reload_time: 1.6,
+                bullet_radius: 0.08,
+                damage: 22.0,
+                penetration: 2,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// A fired bullet, carrying the damage of the firearm that shot it
+#[derive(Clone, Debug)]
+pub struct Bullet {
+    pub collider: WorldColliderHandle,
+    pub damage: f32,
+    pub penetration: u32,
+}
+
+impl Bullet {
+    // Slower bullets bounce off characters without hurting them
+    pub const MIN_DAMAGING_SPEED: f32 = 60.0;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReloadSource {
+    Magazine,
+    LooseRounds,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeaponEvent {
+    Fired,
+    // The reload timer ran out, the shooter should now load rounds from their inventory
+    Reloaded,
+}
+
+// The firearm currently in the shooter's hands and its trigger, magazine and recoil state.
+// Rounds are written back to the inventory item so every firearm keeps its own magazine.
+#[derive(Clone, Debug)]
+pub struct Weapon {
+    pub item: Item,
+    pub slot: EquipmentSlot,
+    pub stats: FirearmStats,
+    pub fire_mode: FireMode,
+    pub rounds_in_magazine: u32,
+    pub durability: f32,
+    pub reload_timer: Option<f32>,
+    reload_duration: f32,
+    time_since_shot: f32,
+    burst_remaining: u32,
+    recoil_spread: f32,
+    trigger_was_held: bool,
+}
+
+impl Weapon {
+    // Spread lost per second once the shooter stops firing
+    const RECOIL_RECOVERY: f32 = 0.2;
+    const MAX_RECOIL_SPREAD: f32 = 0.15;
+
+    const LOOSE_ROUNDS_RELOAD_MULTIPLIER: f32 = 1.5;
+    // Durability lost per shot, a worn out firearm has twice the spread
+    const WEAR_PER_SHOT: f32 = 0.0005;
+
+    pub fn new(inventory_item: &InventoryItem, slot: EquipmentSlot) -> Option<Self> {
+        let stats = inventory_item.item.firearm_stats()?;
+        Some(Weapon {
+            item: inventory_item.item.clone(),
+            slot,
+            stats,
+            fire_mode: stats.fire_modes[0],
+            rounds_in_magazine: inventory_item.rounds.unwrap_or(0).min(stats.magazine_size),
+            durability: inventory_item.durability.unwrap_or(1.0),
+            reload_timer: None,
+            reload_duration: stats.reload_time,
+            time_since_shot: stats.fire_interval,
+            burst_remaining: 0,
+            recoil_spread: 0.0,
+            trigger_was_held: false,
+        })
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload_timer.is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rounds_in_magazine == 0
+    }
+
+    pub fn can_reload(&self) -> bool {
+        !self.is_reloading() && self.rounds_in_magazine < self.stats.magazine_size
+    }
+
+    // Switches to the next fire mode, stopping any burst in progress
+    pub fn cycle_fire_mode(&mut self) {
+        let modes = self.stats.fire_modes;
+        let current = modes.iter().position(|mode| *mode == self.fire_mode);
+        self.fire_mode = modes[current.map_or(0, |i| (i + 1) % modes.len())];
+        self.burst_remaining = 0;
+    }
+
+    pub fn start_reload(&mut self, source: ReloadSource) {
+        if self.can_reload() {
+            self.reload_duration = match source {
+                ReloadSource::Magazine => self.stats.reload_time,
+                ReloadSource::LooseRounds => {
+                    self.stats.reload_time * Self::LOOSE_ROUNDS_RELOAD_MULTIPLIER
+                }
+            };
+            self.reload_timer = Some(self.reload_duration);
+            self.burst_remaining = 0;
+        }
+    }
+
+    // From 0 to 1 while reloading
+    pub fn reload_progress(&self) -> Option<f32> {
+        self.reload_timer
+            .map(|timer| 1.0 - timer / self.reload_duration)
+    }
+
+    // Current spread including recoil, scaled up while the shooter is moving and as it wears
+    pub fn current_spread(&self, movement_factor: f32) -> f32 {
+        let wear_factor = 2.0 - self.durability;
+        self.stats.spread * movement_factor.max(1.0) * wear_factor + self.recoil_spread
+    }
+
+    // Advances timers and returns whether a round was fired or a reload finished this frame
+    pub fn update(&mut self, dt: f32, trigger_held: bool) -> Option<WeaponEvent> {
+        let trigger_pulled = trigger_held && !self.trigger_was_held;
+        self.trigger_was_held = trigger_held;
+        self.time_since_shot += dt;
+        self.recoil_spread = (self.recoil_spread - Self::RECOIL_RECOVERY * dt).max(0.0);
+
+        if let Some(reload_timer) = &mut self.reload_timer {
+            *reload_timer -= dt;
+            if *reload_timer <= 0.0 {
+                self.reload_timer = None;
+                return Some(WeaponEvent::Reloaded);
+            }
+            return None;
+        }
+        if self.is_empty() || self.time_since_shot < self.stats.fire_interval {
+            return None;
+        }
+
+        let fire = match self.fire_mode {
+            FireMode::Semi => trigger_pulled,
+            FireMode::Auto => trigger_held,
+            FireMode::Burst { rounds } => {
+                if trigger_pulled && self.burst_remaining == 0 {
+                    self.burst_remaining = rounds;
+                }
+                self.burst_remaining > 0
+            }
+        };
+        if fire {
+            self.rounds_in_magazine -= 1;
+            self.durability = (self.durability - Self::WEAR_PER_SHOT).max(0.0);
+            self.time_since_shot = 0.0;
+            self.burst_remaining = self.burst_remaining.saturating_sub(1);
+            self.recoil_spread =
+                (self.recoil_spread + self.stats.recoil * 0.01).min(Self::MAX_RECOIL_SPREAD);
+            return Some(WeaponEvent::Fired);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exact in f32, so the rifle's 0.1 second fire interval always takes 4 ticks
+    const DT: f32 = 1.0 / 32.0;
+
+    fn rifle() -> Weapon {
+        Weapon::new(
+            &Item::Rifle.to_inventory_item(false),
+            EquipmentSlot::Primary,
+        )
+        .unwrap()
+    }
+
+    // Runs the weapon for `ticks` with the trigger held on the ticks `trigger` picks
+    fn shots_fired(weapon: &mut Weapon, ticks: u32, trigger: impl Fn(u32) -> bool) -> u32 {
+        (0..ticks)
+            .filter(|tick| weapon.update(DT, trigger(*tick)) == Some(WeaponEvent::Fired))
+            .count() as u32
+    }
+
+    #[test]
+    fn auto_fire_is_limited_by_the_fire_interval() {
+        let mut weapon = rifle();
+        assert_eq!(weapon.fire_mode, FireMode::Auto);
+        assert_eq!(shots_fired(&mut weapon, 20, |_| true), 5);
+        assert_eq!(weapon.rounds_in_magazine, 15);
+    }
+
+    #[test]
+    fn semi_fires_once_per_trigger_pull() {
+        let mut weapon = rifle();
+        weapon.cycle_fire_mode();
+        weapon.cycle_fire_mode();
+        assert_eq!(weapon.fire_mode, FireMode::Semi);
+        assert_eq!(shots_fired(&mut weapon, 32, |_| true), 1);
+
+        // Pulls made before the fire interval has passed are ignored
+        let mut weapon = rifle();
+        weapon.fire_mode = FireMode::Semi;
+        assert_eq!(shots_fired(&mut weapon, 32, |tick| tick % 2 == 0), 8);
+    }
+
+    #[test]
+    fn burst_fires_its_rounds_from_one_pull() {
+        let mut weapon = rifle();
+        weapon.cycle_fire_mode();
+        assert_eq!(weapon.fire_mode, FireMode::Burst { rounds: 3 });
+        assert_eq!(weapon.update(DT, true), Some(WeaponEvent::Fired));
+        // The burst carries on after the trigger is let go
+        assert_eq!(shots_fired(&mut weapon, 32, |_| false), 2);
+        assert_eq!(weapon.rounds_in_magazine, 17);
+        // Holding the trigger fires a single burst
+        assert_eq!(shots_fired(&mut weapon, 64, |_| true), 3);
+    }
+
+    #[test]
+    fn fire_modes_cycle_back_to_the_first() {
+        let mut weapon = rifle();
+        for _ in 0..weapon.stats.fire_modes.len() {
+            weapon.cycle_fire_mode();
+        }
+        assert_eq!(weapon.fire_mode, FireMode::Auto);
+
+        let mut pistol = Weapon::new(
+            &Item::Pistol.to_inventory_item(false),
+            EquipmentSlot::Secondary,
+        )
+        .unwrap();
+        pistol.cycle_fire_mode();
+        assert_eq!(pistol.fire_mode, FireMode::Semi);
+    }
+
+    #[test]
+    fn spread_grows_with_movement_and_wear() {
+        let mut weapon = rifle();
+        let standing = weapon.current_spread(0.0);
+        assert_eq!(standing, weapon.stats.spread);
+        assert_eq!(weapon.current_spread(1.0), standing);
+        assert_eq!(weapon.current_spread(2.0), standing * 2.0);
+        weapon.durability = 0.0;
+        assert_eq!(weapon.current_spread(0.0), standing * 2.0);
+    }
+
+    #[test]
+    fn recoil_adds_spread_that_wears_off() {
+        let mut weapon = rifle();
+        let standing = weapon.current_spread(0.0);
+        assert_eq!(weapon.update(DT, true), Some(WeaponEvent::Fired));
+        let recoil = weapon.stats.recoil * 0.01;
+        assert!((weapon.current_spread(0.0) - standing - recoil).abs() < 1e-6);
+
+        shots_fired(&mut weapon, 100, |_| true);
+        assert!(weapon.is_empty());
+        assert!(weapon.current_spread(0.0) - standing <= Weapon::MAX_RECOIL_SPREAD);
+
+        shots_fired(&mut weapon, 32, |_| false);
+        assert_eq!(weapon.current_spread(0.0), standing);
+    }
+}
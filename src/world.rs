@@ -1,24 +1,57 @@
 use crate::{
-    collision_world::*, lighting::LightEngine, lighting_renderer::LightingRenderer, traits::*,
-    world_collider::WorldColliderHandle, Assets, Corpse, GroundItem, Item, Player,
+    collision_world::*,
+    damage::{DamageEvent, HitMarker},
+    ground::Footstep,
+    lighting::LightEngine,
+    lighting_renderer::LightingRenderer,
+    traits::*,
+    visibility::Visibility,
+    weapon::Bullet,
+    Assets, Corpse, GroundItem, Item, LootTarget, Player, WorldContainer,
 };
-use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
 use raylib::prelude::*;
 
 pub struct GameWorld {
-    pub bullets: Vec<WorldColliderHandle>,
+    pub bullets: Vec<Bullet>,
+    // Hits that landed during the last physics step, kept until the next update steps again
+    pub damage_events: Vec<DamageEvent>,
+    hit_markers: Vec<HitMarker>,
+    // Steps taken during the current update
+    pub footsteps: Vec<Footstep>,
     pub dummies: Vec<Player>,
     pub corpses: Vec<Corpse>,
+    // Seconds before corpses despawn, or None to keep them forever
+    pub corpse_despawn_time: Option<f32>,
     pub ground_items: Vec<GroundItem>,
+    pub containers: Vec<WorldContainer>,
+    // Serializable so saves carry on with the same random numbers
+    pub rng: ChaCha12Rng,
 }
 
 impl GameWorld {
     pub fn new() -> Self {
+        Self::with_rng(ChaCha12Rng::from_entropy())
+    }
+
+    // Seeded world so headless runs are reproducible
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(ChaCha12Rng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng(rng: ChaCha12Rng) -> Self {
         GameWorld {
             bullets: vec![],
+            damage_events: vec![],
+            hit_markers: vec![],
+            footsteps: vec![],
             dummies: vec![],
             corpses: vec![],
+            corpse_despawn_time: Some(Corpse::DEFAULT_DESPAWN_TIME),
             ground_items: vec![],
+            containers: vec![],
+            rng,
         }
     }
     //TODO: Fix too many args
@@ -31,7 +64,7 @@ impl GameWorld {
         collision_world: &mut CollisionWorld,
         camera: &Camera2D,
         assets: &Assets,
-        player: &Player,
+        visibility: &Visibility,
     ) {
         self.render_bullets(
             d,
@@ -40,10 +73,18 @@ impl GameWorld {
             collision_world,
             &mut lighting_renderer.target,
         );
-        self.render_corpses(camera, d, assets, thread, &mut lighting_renderer.target);
-        self.render_ground_items(d, camera, assets, lighting_renderer, thread);
+        self.render_corpses(
+            camera,
+            d,
+            assets,
+            thread,
+            &mut lighting_renderer.target,
+            visibility,
+        );
+        self.render_containers(d, camera, assets, lighting_renderer, thread);
+        self.render_ground_items(d, camera, assets, lighting_renderer, thread, visibility);
         self.render_dummies(
-            player,
+            visibility,
             camera,
             collision_world,
             d,
@@ -51,31 +92,60 @@ impl GameWorld {
             thread,
             &mut lighting_renderer.target,
         );
+        let mut d = d.begin_texture_mode(thread, &mut lighting_renderer.target);
+        for hit_marker in &self.hit_markers {
+            hit_marker.render(&mut d, camera);
+        }
+    }
+
+    // Shows a marker for every hit of the last update and fades out older ones
+    pub fn handle_hit_markers(&mut self, dt: f32) {
+        self.hit_markers
+            .retain_mut(|hit_marker| hit_marker.update(dt));
+        self.hit_markers
+            .extend(self.damage_events.iter().copied().map(HitMarker::new));
     }
 
-    pub fn handle_corpses(&mut self, rl: &RaylibHandle) {
+    // Animates corpses and despawns old ones. The corpse being looted is kept until the
+    // player stops looting it, and `looting` is shifted to follow it.
+    pub fn handle_corpses(&mut self, dt: f32, looting: &mut Option<LootTarget>) {
         for corpse in &mut self.corpses {
-            corpse.update_animation(rl);
+            corpse.update_animation(dt);
+            corpse.time_since_death += dt;
+        }
+        let Some(despawn_time) = self.corpse_despawn_time else {
+            return;
+        };
+        let looted = match *looting {
+            Some(LootTarget::Corpse(looted)) => Some(looted),
+            _ => None,
+        };
+        let mut i = 0;
+        let mut despawned_before_looted = 0;
+        self.corpses.retain(|corpse| {
+            let keep = Some(i) == looted || corpse.time_since_death < despawn_time;
+            if !keep && looted.is_some_and(|looted| i < looted) {
+                despawned_before_looted += 1;
+            }
+            i += 1;
+            keep
+        });
+        if let Some(looted) = looted {
+            *looting = Some(LootTarget::Corpse(looted - despawned_before_looted));
         }
     }
 
-    pub fn handle_bullet_physics(
-        &mut self,
-        rl: &RaylibHandle,
-        collision_world: &mut CollisionWorld,
-    ) {
+    pub fn handle_bullet_physics(&mut self, dt: f32, collision_world: &mut CollisionWorld) {
         let drag_amount = 25.0;
         for bullet in &mut self.bullets {
-            let drag_dir = -bullet.get_linvel(collision_world).normalized();
-            let drag_vector = drag_dir * drag_amount * rl.get_frame_time();
-            bullet.add_linvel(drag_vector, collision_world)
+            let drag_dir = -bullet.collider.get_linvel(collision_world).normalized();
+            let drag_vector = drag_dir * drag_amount * dt;
+            bullet.collider.add_linvel(drag_vector, collision_world)
         }
 
-        self.bullets.retain(|bullet_handle| {
-            if bullet_handle.get_linvel(collision_world).length()
-                < drag_amount * rl.get_frame_time()
-            {
-                collision_world.delete_collider(bullet_handle.clone());
+        self.bullets.retain(|bullet| {
+            if bullet.collider.get_linvel(collision_world).length() < drag_amount * dt {
+                collision_world.delete_collider(bullet.collider.clone());
                 false
             } else {
                 true
@@ -90,9 +160,25 @@ impl GameWorld {
         assets: &Assets,
         lighting_renderer: &mut LightingRenderer,
         thread: &RaylibThread,
+        visibility: &Visibility,
     ) {
         for item in &self.ground_items {
-            item.render(d, camera, assets, lighting_renderer, thread);
+            if visibility.can_see(item.pos()) {
+                item.render(d, camera, assets, lighting_renderer, thread);
+            }
+        }
+    }
+
+    fn render_containers(
+        &self,
+        d: &mut RaylibDrawHandle,
+        camera: &Camera2D,
+        assets: &Assets,
+        lighting_renderer: &mut LightingRenderer,
+        thread: &RaylibThread,
+    ) {
+        for container in &self.containers {
+            container.render(d, camera, assets, lighting_renderer, thread);
         }
     }
 
@@ -109,12 +195,14 @@ impl GameWorld {
             d.get_screen_height() as f32,
         ));
         for bullet in &self.bullets {
-            let bounding_sphere = bullet.get_bounding_sphere(collision_world);
+            let bounding_sphere = bullet.collider.get_bounding_sphere(collision_world);
             if camera_world_rect.check_collision_circle_rec(
                 bounding_sphere.center().coords.to_raylib_vector2(),
                 bounding_sphere.radius,
             ) {
-                bullet.draw(collision_world, camera, d, thread, target);
+                bullet
+                    .collider
+                    .draw(collision_world, camera, d, thread, target);
             }
         }
     }
@@ -126,13 +214,16 @@ impl GameWorld {
         assets: &Assets,
         thread: &RaylibThread,
         target: &mut RenderTexture2D,
+        visibility: &Visibility,
     ) {
         let camera_world_rect = camera.get_visible_rect(Vector2::new(
             d.get_screen_width() as f32,
             d.get_screen_height() as f32,
         ));
         for corpse in &self.corpses {
-            if camera_world_rect.check_collision_point_rec(corpse.pos) {
+            if camera_world_rect.check_collision_point_rec(corpse.pos)
+                && visibility.can_see(corpse.pos)
+            {
                 corpse.render(d, assets, camera, thread, target)
             }
         }
@@ -141,7 +232,7 @@ impl GameWorld {
     #[allow(clippy::too_many_arguments)]
     fn render_dummies(
         &self,
-        player: &Player,
+        visibility: &Visibility,
         camera: &Camera2D,
         collision_world: &mut CollisionWorld,
         d: &mut RaylibDrawHandle,
@@ -154,88 +245,13 @@ impl GameWorld {
             d.get_screen_height() as f32,
         ));
         for dummy in &self.dummies {
-            for i in 0..4 {
-                // check 4 diffrent points on dummy
-                let offset = match i {
-                    0 => Vector2::new(0.0, 1.0),
-                    1 => Vector2::new(1.0, 0.0),
-                    2 => Vector2::new(0.0, -1.0),
-                    3 => Vector2::new(-1.0, 0.0),
-                    _ => unreachable!(),
-                };
-                let bounding_sphere = dummy.collider.get_bounding_sphere(collision_world);
-                let player_bounding_sphere = player.collider.get_bounding_sphere(collision_world);
-                let player_pos = player.collider.get_pos(collision_world);
-                let dummy_pos = dummy.collider.get_pos(collision_world) + offset;
-                let dx = dummy_pos - player_pos;
-                let dn = dx.normalized();
-
-                let ray_origin = player_pos + dn * 2.0 * player_bounding_sphere.radius;
-                let ray = &rapier2d::geometry::Ray::new(
-                    rapier2d::na::Vector2::new(ray_origin.x, ray_origin.y).into(),
-                    rapier2d::na::Vector2::new(dn.x, dn.y),
-                );
-                let ray_length =
-                    dx.length() - (bounding_sphere.radius + player_bounding_sphere.radius);
-                fn predicate(
-                    _handle: rapier2d::geometry::ColliderHandle,
-                    collider: &rapier2d::geometry::Collider,
-                ) -> bool {
-                    collider.shape().as_cuboid().is_some()
-                }
-                let intersection = collision_world
-                    .rapier
-                    .query_pipeline
-                    .cast_ray_and_get_normal(
-                        &collision_world.rapier.rigid_body_set,
-                        &collision_world.rapier.collider_set,
-                        ray,
-                        ray_length,
-                        true,
-                        rapier2d::pipeline::QueryFilter {
-                            exclude_rigid_body: Some(dummy.collider.rigid_body_handle),
-                            predicate: Some(&predicate),
-                            ..Default::default()
-                        },
-                    );
-                if let Some(_intersection) = intersection {
-                } else if camera_world_rect.check_collision_circle_rec(
-                    bounding_sphere.center().coords.to_raylib_vector2(),
-                    bounding_sphere.radius,
-                ) {
-                    dummy.render(d, camera, collision_world, assets, thread, target);
-                }
+            let bounding_sphere = dummy.collider.get_bounding_sphere(collision_world);
+            let center = bounding_sphere.center().coords.to_raylib_vector2();
+            if visibility.can_see_circle(center, bounding_sphere.radius)
+                && camera_world_rect.check_collision_circle_rec(center, bounding_sphere.radius)
+            {
+                dummy.render(d, camera, collision_world, assets, thread, target);
             }
         }
     }
 }
-
-pub fn spawn_debug_colldier_world(
-    debug_colliders: &mut Vec<WorldColliderHandle>,
-    collision_world: &mut CollisionWorld,
-) {
-    for _ in 0..10 {
-        let size_x = rand::thread_rng().gen_range(1.0..6.4);
-        let size_y = rand::thread_rng().gen_range(1.0..6.4);
-        let pos_x = rand::thread_rng().gen_range(0.0..16.0 * 6.4);
-        let pos_y = rand::thread_rng().gen_range(0.0..16.0 * 6.4);
-        debug_colliders.push(collision_world.spawn_collider(
-            RigidBodyArgs {
-                dynamic: false,
-                pos: Vector2::new(pos_x, pos_y),
-                vel: Vector2::zero(),
-                user_data: ColliderUserData::WALL,
-            },
-            ColliderArgs {
-                density: 1.0,
-                restitution: 0.5,
-                friction: 0.5,
-                user_data: ColliderUserData::WALL,
-                sensor: false,
-            },
-            ShapeArgs::Cuboid {
-                half_extents: Vector2::new(size_x, size_y),
-            },
-        ));
-    }
-}
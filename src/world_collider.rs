@@ -47,6 +47,12 @@ impl WorldColliderHandle {
             .set_position(nalgebra::Vector2::from_raylib_vector2(pos).into(), true)
     }
 
+    // In radians. `set_pos` resets the rotation, so set it afterwards.
+    pub fn set_angle(&self, angle: f32, collision_world: &mut CollisionWorld) {
+        collision_world.rapier.rigid_body_set[self.rigid_body_handle]
+            .set_rotation(Rotation::new(angle), true)
+    }
+
     pub fn get_angvel(&self, collision_world: &CollisionWorld) -> f32 {
         let rigid_body = &collision_world.rapier.rigid_body_set[self.rigid_body_handle];
         rigid_body.angvel()